@@ -1,7 +1,7 @@
 use comfy_table::modifiers::UTF8_ROUND_CORNERS;
 use comfy_table::presets::UTF8_FULL;
 use comfy_table::{Cell, Color, Table};
-use mc_lib::{Config, FileSize};
+use mc_lib::{Config, ConflictPolicy, FileSize};
 
 /// 显示配置信息（表格格式）
 pub fn show_config(config: &Config, config_path: &str) {
@@ -37,6 +37,19 @@ fn show_global_settings(config: &Config) {
         "Directory Template",
         &config.global.directory_template,
     ]);
+    table.add_row(vec![
+        "Filename Template",
+        config
+            .global
+            .filename_template
+            .as_deref()
+            .unwrap_or("(original name)"),
+    ]);
+    table.add_row(vec!["Collision Suffix", &config.global.collision_suffix]);
+    table.add_row(vec![
+        "Missing Placeholder",
+        &config.global.missing_placeholder,
+    ]);
     table.add_row(vec![
         "Clean Empty Dirs",
         if config.global.clean_empty_dirs {
@@ -46,6 +59,25 @@ fn show_global_settings(config: &Config) {
         },
     ]);
 
+    table.add_row(vec!["Transfer Mode", config.global.transfer_mode.as_str()]);
+
+    let jobs = if config.global.jobs == 0 {
+        "auto".to_string()
+    } else {
+        config.global.jobs.to_string()
+    };
+    table.add_row(vec!["Jobs", jobs.as_str()]);
+    table.add_row(vec!["Compare", config.global.compare.as_str()]);
+    table.add_row(vec!["Conflict Policy", config.global.conflict.as_str()]);
+    if config.global.conflict == ConflictPolicy::Quarantine
+        || config
+            .rules
+            .iter()
+            .any(|r| r.conflict == Some(ConflictPolicy::Quarantine))
+    {
+        table.add_row(vec!["Quarantine Dir", &config.global.quarantine_dir]);
+    }
+
     // 全局文件大小过滤（可选）
     if let Some(file_size) = &config.global.file_size {
         let min = file_size.min.as_deref().unwrap_or("∞");
@@ -80,6 +112,9 @@ fn show_rules(config: &Config) {
             Cell::new("Min Size").fg(Color::Cyan),
             Cell::new("Max Size").fg(Color::Cyan),
             Cell::new("Directory Template").fg(Color::Cyan),
+            Cell::new("Filename Template").fg(Color::Cyan),
+            Cell::new("Places").fg(Color::Cyan),
+            Cell::new("Conflict").fg(Color::Cyan),
             Cell::new("Enabled").fg(Color::Cyan),
         ]);
 
@@ -124,6 +159,16 @@ fn show_rules(config: &Config) {
             Cell::new(min_size),
             Cell::new(max_size),
             Cell::new(&rule.directory_template),
+            Cell::new(
+                rule.effective_filename_template(&config.global)
+                    .unwrap_or("-"),
+            ),
+            Cell::new(if rule.places.is_empty() {
+                "-".to_string()
+            } else {
+                rule.places.join(",")
+            }),
+            Cell::new(rule.effective_conflict_policy(&config.global).as_str()),
             enabled_cell,
         ]);
     }
@@ -1,18 +1,22 @@
 mod config_display;
 
 use anyhow::{Context, Result};
-use clap::Parser;
+use clap::{Parser, Subcommand};
 use config_display::show_config;
 use log::info;
 use mc_lib::{
-    ClassifyResult, Config, FileFilter, classify_file_with_config, get_media_info,
-    remove_empty_dirs,
+    CancelToken, ClassifyResult, CompareStrategy, Config, ConflictPolicy, DedupeAction, Geocoder,
+    Journal, KeepStrategy, LibraryIndex, Observer, Plan, PlanEntry, PlannedAction, Planner,
+    Progress, Recovery, Roots, Scanner, SimilarIndex, TransferMode, UndoOutcome,
+    find_duplicate_groups, find_run, find_similar_clusters, incomplete_runs, list_runs,
+    recover_run, remove_duplicate, run_plan, undo_run,
 };
 use simplelog::*;
+use std::collections::HashSet;
 use std::fs::File;
 use std::io::{Write, stdout};
 use std::path::{Path, PathBuf};
-use walkdir::WalkDir;
+use std::time::Duration;
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
@@ -21,6 +25,14 @@ struct Args {
     #[arg(short, long, default_value = ".")]
     dir: String,
 
+    /// Source directory to scan, repeatable (default: --dir)
+    #[arg(short = 'i', long = "source", value_name = "DIR")]
+    sources: Vec<PathBuf>,
+
+    /// Destination library root, never scanned as input (default: --dir)
+    #[arg(short = 'o', long = "dest", value_name = "DIR")]
+    dest: Option<PathBuf>,
+
     /// Config file path (default: ~/.config/media-classifier/config.yaml)
     #[arg(short = 'f', long = "file")]
     config_file: Option<PathBuf>,
@@ -36,6 +48,90 @@ struct Args {
     /// Remove empty directories after processing (default: from config)
     #[arg(long)]
     clean: Option<bool>,
+
+    /// Transfer mode for this run: move, copy or copy-then-delete (rules may override)
+    #[arg(long, value_name = "MODE")]
+    mode: Option<TransferMode>,
+
+    /// How to decide whether an existing target is the same file:
+    /// size, quick-hash, full-hash or byte-by-byte (default: from config)
+    #[arg(long, value_name = "STRATEGY")]
+    compare: Option<CompareStrategy>,
+
+    /// What to do when a different file already exists at the target:
+    /// keep-both, skip, overwrite, keep-newer, keep-larger or quarantine
+    /// (default: from config, rules may override)
+    #[arg(long, value_name = "POLICY")]
+    conflict: Option<ConflictPolicy>,
+
+    /// Number of threads used to read metadata (default: from config, 0 = all cores)
+    #[arg(short = 'j', long, value_name = "N")]
+    jobs: Option<usize>,
+
+    /// Skip files whose content already exists anywhere in the destination library
+    #[arg(long)]
+    skip_duplicates: bool,
+
+    /// Move images that look like a re-compressed or resized library image into the review folder
+    #[arg(long)]
+    review_similar: bool,
+
+    /// Print the classification plan without moving any files
+    #[arg(long)]
+    dry_run: bool,
+
+    /// Write the dry-run plan to a file (YAML, or JSON with a .json extension)
+    #[arg(long, value_name = "FILE", requires = "dry_run")]
+    export_plan: Option<PathBuf>,
+
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Execute a previously exported (and optionally edited) plan file
+    Apply {
+        /// Plan file (YAML, or JSON with a .json extension)
+        plan: PathBuf,
+    },
+    /// Undo a previous classification run, or roll back an interrupted one
+    #[command(alias = "rollback")]
+    Undo {
+        /// Run ID to undo, or "last" for the most recent run
+        #[arg(default_value = "last")]
+        run: String,
+
+        /// List recorded runs instead of undoing
+        #[arg(long)]
+        list: bool,
+    },
+    /// Resume an interrupted run, cleaning up partially transferred files first
+    Resume {
+        /// Run ID to resume, or "last" for the most recent interrupted run
+        #[arg(default_value = "last")]
+        run: String,
+    },
+    /// Find duplicate files in the destination library
+    Dedupe {
+        /// Which copy to keep: oldest, newest, shortest-path or first
+        #[arg(long, value_name = "STRATEGY", default_value = "oldest")]
+        keep: KeepStrategy,
+
+        /// Delete the other copies (cannot be undone)
+        #[arg(long, conflicts_with = "move_to")]
+        delete: bool,
+
+        /// Move the other copies into DIR, keeping their relative paths (can be undone)
+        #[arg(long, value_name = "DIR")]
+        move_to: Option<PathBuf>,
+    },
+    /// List clusters of visually similar images in the destination library
+    Similar {
+        /// Minimum similarity in percent (default: from config)
+        #[arg(long, value_name = "PERCENT", value_parser = clap::value_parser!(u8).range(0..=100))]
+        threshold: Option<u8>,
+    },
 }
 
 /// 统计信息
@@ -44,7 +140,13 @@ struct Statistics {
     success: usize,
     skipped: usize,
     renamed: usize,
+    replaced: usize,
+    quarantined: usize,
     failed: usize,
+    /// 按传输方式统计的成功数（含重命名、替换和隔离）
+    moved: usize,
+    copied: usize,
+    copied_deleted: usize,
 }
 
 impl Statistics {
@@ -54,20 +156,91 @@ impl Statistics {
 
     fn record(&mut self, result: &ClassifyResult) {
         match result {
-            ClassifyResult::Success { .. } => self.success += 1,
+            ClassifyResult::Success { mode, .. } => {
+                self.success += 1;
+                self.record_mode(*mode);
+            },
             ClassifyResult::Skipped { .. } => self.skipped += 1,
-            ClassifyResult::Renamed { .. } => self.renamed += 1,
+            ClassifyResult::Renamed { mode, .. } => {
+                self.renamed += 1;
+                self.record_mode(*mode);
+            },
+            ClassifyResult::Replaced { mode, .. } => {
+                self.replaced += 1;
+                self.record_mode(*mode);
+            },
+            ClassifyResult::Quarantined { mode, .. } => {
+                self.quarantined += 1;
+                self.record_mode(*mode);
+            },
             ClassifyResult::Failed { .. } => self.failed += 1,
         }
     }
 
+    fn record_plan(&mut self, entry: &PlanEntry) {
+        match entry.action {
+            PlannedAction::Move => {
+                self.success += 1;
+                self.record_mode(entry.transfer_mode);
+            },
+            PlannedAction::Skip { .. } => self.skipped += 1,
+            PlannedAction::Rename => {
+                self.renamed += 1;
+                self.record_mode(entry.transfer_mode);
+            },
+            PlannedAction::Replace => {
+                self.replaced += 1;
+                self.record_mode(entry.transfer_mode);
+            },
+            PlannedAction::Quarantine => {
+                self.quarantined += 1;
+                self.record_mode(entry.transfer_mode);
+            },
+            PlannedAction::Fail { .. } => self.failed += 1,
+        }
+    }
+
+    fn record_mode(&mut self, mode: TransferMode) {
+        match mode {
+            TransferMode::Move => self.moved += 1,
+            TransferMode::Copy => self.copied += 1,
+            TransferMode::CopyThenDelete => self.copied_deleted += 1,
+        }
+    }
+
+    /// 摘要中使用的传输动词：全部移动时为 moved，全部复制时为 copied，其余为 transferred
+    fn transfer_verb(&self) -> &'static str {
+        if self.copied + self.copied_deleted == 0 {
+            "moved"
+        } else if self.moved + self.copied_deleted == 0 {
+            "copied"
+        } else {
+            "transferred"
+        }
+    }
+
     fn print_summary(&self) {
+        let verb = self.transfer_verb();
+        let success = format!("Successfully {}:", verb);
+        let renamed = format!("Renamed and {}:", verb);
+        // 标签列宽度随动词变化，保持数字对齐
+        let width = (success.len() + 2).max(21);
         println!("\n========== Classification Summary ==========");
-        println!("✅ Successfully moved:  {}", self.success);
-        println!("🔄 Renamed and moved:   {}", self.renamed);
-        println!("⏭️  Skipped (same file): {}", self.skipped);
-        println!("❌ Failed:              {}", self.failed);
-        println!("📊 Total processed:     {}", self.total());
+        println!("✅ {:<width$}{}", success, self.success);
+        println!("🔄 {:<width$}{}", renamed, self.renamed);
+        println!("⏭️  {:<width$}{}", "Skipped (same file):", self.skipped);
+        if self.replaced + self.quarantined > 0 {
+            println!("♻️  {:<width$}{}", "Replaced existing:", self.replaced);
+            println!("🚧 {:<width$}{}", "Quarantined:", self.quarantined);
+        }
+        println!("❌ {:<width$}{}", "Failed:", self.failed);
+        println!("📊 {:<width$}{}", "Total processed:", self.total());
+        if self.copied + self.copied_deleted > 0 {
+            println!(
+                "📦 {:<width$}move {}, copy {}, copy_then_delete {}",
+                "Transfer modes:", self.moved, self.copied, self.copied_deleted
+            );
+        }
         println!("==========================================\n");
 
         info!(
@@ -77,7 +250,101 @@ impl Statistics {
     }
 
     fn total(&self) -> usize {
-        self.success + self.skipped + self.renamed + self.failed
+        self.success + self.skipped + self.renamed + self.replaced + self.quarantined + self.failed
+    }
+}
+
+/// 在终端显示进度并把结果写入日志文件的观察者
+#[derive(Debug, Default)]
+struct CliObserver {
+    stats: Statistics,
+    /// 终端中的进度行尚未换行
+    progress_line: bool,
+}
+
+impl CliObserver {
+    /// 结束覆盖中的进度行，之后的输出另起一行
+    fn end_progress_line(&mut self) {
+        if self.progress_line {
+            println!();
+            self.progress_line = false;
+        }
+    }
+}
+
+impl Observer for CliObserver {
+    fn scan_started(&mut self, _sources: &[PathBuf]) {
+        print!("🔍 Scanning for media files...");
+        stdout().flush().ok();
+    }
+
+    fn file_discovered(&mut self, _path: &Path, count: usize) {
+        if count.is_multiple_of(100) {
+            print!("\r🔍 Scanning for media files... {}", count);
+            stdout().flush().ok();
+        }
+    }
+
+    fn file_classified(
+        &mut self,
+        source: &Path,
+        result: &Result<ClassifyResult>,
+        progress: &Progress,
+    ) {
+        match result {
+            Ok(result) => {
+                // 记录详细日志到文件
+                log_result(result);
+                self.stats.record(result);
+            },
+            Err(e) => {
+                info!("[ERROR] {} -> {}", source.display(), e);
+                self.stats.failed += 1;
+            },
+        }
+
+        // 在终端显示进度（覆盖同一行）
+        let (done, total) = (progress.files_done, progress.files_total);
+        if done == total {
+            println!("\r⚙️  Processing: [{}/{}] ✓{:30}", done, total, "");
+            self.progress_line = false;
+        } else {
+            let eta = progress.eta().map(format_duration).unwrap_or_default();
+            print!(
+                "\r⚙️  Processing: [{}/{}] {:.1} MB transferred, ETA {:8}",
+                done,
+                total,
+                progress.bytes_transferred as f64 / 1024.0 / 1024.0,
+                eta
+            );
+            stdout().flush().ok();
+            self.progress_line = true;
+        }
+    }
+
+    fn dir_cleaned(&mut self, dir: &Path) {
+        self.end_progress_line();
+        println!("🗑️  Removed empty directory: {}", dir.display());
+    }
+
+    fn run_finished(&mut self, progress: &Progress) {
+        self.end_progress_line();
+        info!(
+            "Run finished in {}: {} files, {} bytes transferred",
+            format_duration(progress.elapsed()),
+            progress.files_done,
+            progress.bytes_transferred
+        );
+    }
+}
+
+/// 将时长格式化为 `m:ss` 或 `h:mm:ss`
+fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs >= 3600 {
+        format!("{}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60)
+    } else {
+        format!("{}:{:02}", secs / 60, secs % 60)
     }
 }
 
@@ -100,7 +367,27 @@ fn main() -> Result<()> {
     Config::ensure_config_exists(&config_path)?;
 
     // 加载配置
-    let config = Config::load(&config_path)?;
+    let mut config = Config::load(&config_path)?;
+
+    // 命令行指定的传输方式覆盖全局默认值（规则中单独设置的仍然优先）
+    if let Some(mode) = args.mode {
+        config.global.transfer_mode = mode;
+    }
+    if let Some(jobs) = args.jobs {
+        config.global.jobs = jobs;
+    }
+    if let Some(compare) = args.compare {
+        config.global.compare = compare;
+    }
+    if let Some(conflict) = args.conflict {
+        config.global.conflict = conflict;
+    }
+    if args.skip_duplicates {
+        config.dedupe.skip_existing = true;
+    }
+    if args.review_similar {
+        config.similar.enabled = true;
+    }
 
     // 如果是显示配置模式
     if args.show_config {
@@ -119,13 +406,72 @@ fn main() -> Result<()> {
         return Ok(());
     }
 
-    // 获取目标目录
-    let target_dir = if args.dir.is_empty() || args.dir == "." {
+    // 执行计划文件
+    if let Some(Command::Apply { plan }) = &args.command {
+        let should_clean = args.clean.unwrap_or(config.global.clean_empty_dirs);
+        return run_apply(plan, should_clean);
+    }
+
+    // 获取工作目录
+    let work_dir = if args.dir.is_empty() || args.dir == "." {
         std::env::current_dir().context("Failed to get current directory")?
     } else {
         PathBuf::from(&args.dir)
     };
 
+    // 撤销或列出历史运行
+    if let Some(Command::Undo { run, list }) = &args.command {
+        let dest = args.dest.clone().unwrap_or(work_dir);
+        return if *list {
+            run_list(&dest)
+        } else {
+            run_undo(&dest, run)
+        };
+    }
+
+    // 继续被中断的运行
+    if let Some(Command::Resume { run }) = &args.command {
+        let dest = args.dest.clone().unwrap_or(work_dir);
+        let should_clean = args.clean.unwrap_or(config.global.clean_empty_dirs);
+        return run_resume(&config, &dest, run, should_clean);
+    }
+
+    // 查找并处理库中的重复文件
+    if let Some(Command::Dedupe {
+        keep,
+        delete,
+        move_to,
+    }) = &args.command
+    {
+        let dest = args.dest.clone().unwrap_or(work_dir);
+        let action = if *delete {
+            Some(DedupeAction::Delete)
+        } else {
+            move_to.clone().map(DedupeAction::MoveTo)
+        };
+        return run_dedupe(&config, &dest, *keep, action);
+    }
+
+    // 列出库中的相似图片
+    if let Some(Command::Similar { threshold }) = &args.command {
+        let dest = args.dest.clone().unwrap_or(work_dir);
+        let threshold = threshold.unwrap_or(config.similar.threshold);
+        return run_similar(&config, &dest, threshold);
+    }
+
+    // 确定源目录和目标目录
+    let sources = if args.sources.is_empty() {
+        vec![work_dir.clone()]
+    } else {
+        args.sources.clone()
+    };
+    let dest = args.dest.clone().unwrap_or(work_dir);
+    if !dest.exists() {
+        std::fs::create_dir_all(&dest).context("Failed to create destination directory")?;
+    }
+    let roots = Roots::new(sources, &dest)?;
+    let target_dir = roots.destination().to_path_buf();
+
     // 初始化日志系统
     let log_path = get_log_file_path(&target_dir);
     init_logger(&log_path)?;
@@ -135,86 +481,742 @@ fn main() -> Result<()> {
     println!("🚀 MediaClassifier - Organizing your media files...\n");
     println!("📋 Config: {}\n", config_path.display());
 
-    info!("Working directory: {:?}", target_dir);
-    println!("📁 Working directory: {}\n", target_dir.display());
+    if roots.is_in_place() {
+        info!("Working directory: {:?}", target_dir);
+        println!("📁 Working directory: {}\n", target_dir.display());
+    } else {
+        for source in roots.sources() {
+            info!("Source directory: {:?}", source);
+            println!("📥 Source: {}", source.display());
+        }
+        info!("Destination directory: {:?}", target_dir);
+        println!("📦 Destination: {}\n", target_dir.display());
+    }
+
+    // 存在被中断的运行时先要求继续或回滚
+    if !args.dry_run {
+        check_interrupted_runs(&target_dir)?;
+    }
 
     // 扫描并收集所有媒体文件
-    print!("🔍 Scanning for media files...");
-    stdout().flush().ok();
-    let (media_files, skipped_dirs) = scan_media_files(&target_dir, &config)?;
+    let mut observer = CliObserver::default();
+    let scan = Scanner::new(&config, &roots).scan(&mut observer);
+    let media_files = scan.files;
 
     if media_files.is_empty() {
-        println!(" Done");
-        println!("ℹ️  No media files found in the current directory.");
+        println!("\r🔍 Scanning for media files... Done");
+        println!("ℹ️  No media files found in the source directories.");
         info!("No media files found");
         return Ok(());
     }
 
-    println!(" Found {} files", media_files.len());
+    println!(
+        "\r🔍 Scanning for media files... Found {} files",
+        media_files.len()
+    );
     info!("Found {} media files", media_files.len());
 
     // 记录跳过的目录到日志
-    if !skipped_dirs.is_empty() {
+    if !scan.skipped.is_empty() {
         info!("Skipped directories:");
-        for dir in &skipped_dirs {
-            info!("  [SKIP DIR] {}", dir.display());
+        for skipped in &scan.skipped {
+            info!(
+                "  [SKIP DIR] {} ({})",
+                skipped.path.display(),
+                skipped.reason
+            );
         }
     }
 
+    // 仅生成计划，不移动文件
+    if args.dry_run {
+        return run_dry_run(&config, &roots, &media_files, args.export_plan.as_deref());
+    }
+
     // 处理每个文件
-    println!("⚙️  Processing files...");
-    let mut stats = Statistics::new();
-    let total = media_files.len();
+    let mut journal = Journal::create(&target_dir, roots.sources(), None)?;
+    journal.record_queue(&media_files)?;
+    info!("Run journal: {:?}", journal.path());
 
-    for (index, file) in media_files.iter().enumerate() {
-        // 在终端显示进度（覆盖同一行）
-        print!("\r⚙️  Processing: [{}/{}]", index + 1, total);
-        let _ = stdout().flush();
+    // 使用配置或命令行参数决定是否清理空目录
+    let should_clean = args.clean.unwrap_or(config.global.clean_empty_dirs);
+    let clean_dirs = dirs_to_clean(should_clean, roots.sources(), &target_dir);
+    let progress = classify_files(
+        &config,
+        &target_dir,
+        &media_files,
+        &mut journal,
+        &clean_dirs,
+        &mut observer,
+    )?;
 
-        match classify_file_with_config(&config, &target_dir, file) {
-            Ok(result) => {
-                // 记录详细日志到文件
-                log_result(&result);
-                stats.record(&result);
+    // 打印统计信息
+    observer.stats.print_summary();
+    finish_journal(journal, &progress)?;
+
+    // 显示日志文件路径
+    println!("📝 Detailed logs saved to: {}", log_path.display());
+    println!("✨ Done!\n");
+
+    Ok(())
+}
+
+/// 生成并打印分类计划（不修改磁盘）
+fn run_dry_run(
+    config: &Config,
+    roots: &Roots,
+    media_files: &[PathBuf],
+    export_path: Option<&Path>,
+) -> Result<()> {
+    println!("🧪 Dry run: no files will be moved\n");
+    info!("Dry run enabled");
+
+    let mut stats = Statistics::new();
+    let mut entries = Vec::new();
+
+    create_planner(config, roots.destination())?.plan_files(
+        media_files,
+        |file, entry| match entry {
+            Ok(entry) => {
+                print_plan_entry(&entry);
+                stats.record_plan(&entry);
+                entries.push(entry);
             },
             Err(e) => {
-                info!("[ERROR] {} -> {}", file.display(), e);
+                println!("[FAIL]   {} | {}", file.display(), e);
+                info!("[PLAN ERROR] {} -> {}", file.display(), e);
                 stats.failed += 1;
             },
+        },
+    );
+
+    stats.print_summary();
+
+    if let Some(path) = export_path {
+        Plan::new(roots.destination(), roots.sources(), entries).save(path)?;
+        info!("Plan exported to {:?}", path);
+        println!("💾 Plan saved to: {}\n", path.display());
+    }
+
+    Ok(())
+}
+
+/// 执行计划文件，跳过自生成计划后已变化的源文件
+fn run_apply(plan_path: &Path, should_clean: bool) -> Result<()> {
+    let plan = Plan::load(plan_path)?;
+
+    let log_path = get_log_file_path(&plan.target_dir);
+    init_logger(&log_path)?;
+
+    info!("MediaClassifier started (apply plan)");
+    info!("Using plan: {:?}", plan_path);
+    println!("🚀 MediaClassifier - Applying plan...\n");
+    println!(
+        "📋 Plan: {} ({} entries)\n",
+        plan_path.display(),
+        plan.entries.len()
+    );
+
+    check_interrupted_runs(&plan.target_dir)?;
+
+    // 记录计划文件的绝对路径，继续中断的运行时重新加载
+    let plan_path = std::path::absolute(plan_path).context("Failed to resolve plan path")?;
+    let mut journal = Journal::create(&plan.target_dir, &plan.sources, Some(&plan_path))?;
+    let queue: Vec<PathBuf> = plan.entries.iter().map(|e| e.source.clone()).collect();
+    journal.record_queue(&queue)?;
+    info!("Run journal: {:?}", journal.path());
+
+    let entries: Vec<&PlanEntry> = plan.entries.iter().collect();
+    let clean_dirs = dirs_to_clean(should_clean, &plan.sources, &plan.target_dir);
+    let mut observer = CliObserver::default();
+    let (progress, stale) = execute_entries(&entries, &mut journal, &clean_dirs, &mut observer)?;
+
+    observer.stats.print_summary();
+    finish_journal(journal, &progress)?;
+    print_stale(&stale);
+
+    println!("📝 Detailed logs saved to: {}", log_path.display());
+    println!("✨ Done!\n");
+
+    Ok(())
+}
+
+/// 继续被中断的运行
+fn run_resume(config: &Config, dest: &Path, run_id: &str, should_clean: bool) -> Result<()> {
+    let runs = incomplete_runs(dest)?;
+    let run = if run_id == "last" {
+        runs.last()
+    } else {
+        runs.iter().find(|r| r.run_id == run_id)
+    };
+    let journal_path = run
+        .map(|r| r.path.clone())
+        .ok_or_else(|| anyhow::anyhow!("No interrupted run found: {}", run_id))?;
+
+    let log_path = get_log_file_path(dest);
+    init_logger(&log_path)?;
+
+    info!("MediaClassifier started (resume)");
+    info!("Using journal: {:?}", journal_path);
+    println!("🚀 MediaClassifier - Resuming interrupted run...\n");
+    println!("🧾 Journal: {}\n", journal_path.display());
+
+    let recovery = recover_run(&journal_path)?;
+    print_recovery(&recovery);
+
+    let mut journal = Journal::open(&journal_path)?;
+    let clean_dirs = dirs_to_clean(should_clean, &recovery.sources, &recovery.destination);
+    let mut observer = CliObserver::default();
+    let (progress, stale) = if let Some(plan_path) = &recovery.plan {
+        let plan = Plan::load(plan_path)?;
+        let pending: HashSet<&PathBuf> = recovery.pending.iter().collect();
+        let entries: Vec<&PlanEntry> = plan
+            .entries
+            .iter()
+            .filter(|e| pending.contains(&e.source))
+            .collect();
+        execute_entries(&entries, &mut journal, &clean_dirs, &mut observer)?
+    } else {
+        // 中断后被用户移走的文件不再处理
+        let files: Vec<PathBuf> = recovery
+            .pending
+            .iter()
+            .filter(|f| f.exists())
+            .cloned()
+            .collect();
+        let progress = classify_files(
+            config,
+            &recovery.destination,
+            &files,
+            &mut journal,
+            &clean_dirs,
+            &mut observer,
+        )?;
+        (progress, Vec::new())
+    };
+
+    observer.stats.print_summary();
+    finish_journal(journal, &progress)?;
+    print_stale(&stale);
+
+    println!("📝 Detailed logs saved to: {}", log_path.display());
+    println!("✨ Done!\n");
+
+    Ok(())
+}
+
+/// 目标目录中存在被中断的运行时拒绝开始新的运行
+fn check_interrupted_runs(dest: &Path) -> Result<()> {
+    if let Some(run) = incomplete_runs(dest)?.last() {
+        anyhow::bail!(
+            "Found an interrupted run {} in {:?}. Resume it with `MediaClassifier resume` \
+             or roll it back with `MediaClassifier undo` before starting a new run",
+            run.run_id,
+            dest
+        );
+    }
+    Ok(())
+}
+
+/// 创建计划器，启用了重复或相似检测时先索引目标库，模板使用地点变量时加载城市数据
+fn create_planner<'a>(config: &'a Config, target_dir: &Path) -> Result<Planner<'a>> {
+    let mut planner = Planner::new(config, target_dir);
+
+    if config.dedupe.skip_existing {
+        print!("📚 Indexing library for duplicates...");
+        stdout().flush().ok();
+        let index = LibraryIndex::build(target_dir)?;
+        println!(" Done");
+        planner = planner.with_library_index(index);
+    }
+
+    if config.similar.enabled {
+        print!("🖼️  Fingerprinting library images...");
+        stdout().flush().ok();
+        let index = SimilarIndex::build(target_dir, &config.similar, config.global.jobs)?;
+        println!(" Done");
+        planner = planner.with_similar_index(index);
+    }
+
+    if config.uses_geocoding() {
+        print!("🌍 Loading city dataset...");
+        stdout().flush().ok();
+        let geocoder = Geocoder::from_config(&config.geocoding)?;
+        println!(" Done ({} cities)", geocoder.len());
+        if config.geocoding.cities.is_none() {
+            println!(
+                "   ℹ️  Using the bundled major-cities list, which is coarse; \
+                 set geocoding.cities to a GeoNames dataset for smaller towns"
+            );
         }
+        planner = planner.with_geocoder(geocoder);
     }
 
-    // 清除进度行并打印完成信息
-    print!("\r⚙️  Processing: [{}/{}] ✓\n", total, total);
+    Ok(planner)
+}
 
-    // 使用配置或命令行参数决定是否清理空目录
-    let should_clean = args.clean.unwrap_or(config.global.clean_empty_dirs);
-    if should_clean {
-        println!("🧹 Cleaning up empty directories...");
-        remove_empty_dirs(&target_dir)?;
+/// 分类文件（并行提取元数据，按顺序传输），每次传输都写入运行日志
+fn classify_files(
+    config: &Config,
+    target_dir: &Path,
+    files: &[PathBuf],
+    journal: &mut Journal,
+    clean_dirs: &[PathBuf],
+    observer: &mut CliObserver,
+) -> Result<Progress> {
+    let mut planner = create_planner(config, target_dir)?.with_cancel_token(cancel_on_ctrl_c());
+    println!("⚙️  Processing files...");
+    planner.run(files, Some(journal), clean_dirs, observer)
+}
+
+/// 逐个执行计划条目，跳过自生成计划后已变化的源文件
+///
+/// 返回 (运行进度, 失效条目列表)
+fn execute_entries(
+    entries: &[&PlanEntry],
+    journal: &mut Journal,
+    clean_dirs: &[PathBuf],
+    observer: &mut CliObserver,
+) -> Result<(Progress, Vec<(PathBuf, String)>)> {
+    println!("⚙️  Processing files...");
+    let cancel = cancel_on_ctrl_c();
+    run_plan(entries, Some(journal), clean_dirs, &cancel, observer)
+}
+
+/// 按 Ctrl-C 时取消运行：进行中的复制被中止并删除不完整的目标文件，
+/// 剩余文件留在原处；再按一次立即退出
+fn cancel_on_ctrl_c() -> CancelToken {
+    let cancel = CancelToken::new();
+    let handler = cancel.clone();
+    let installed = ctrlc::set_handler(move || {
+        if handler.is_cancelled() {
+            std::process::exit(130);
+        }
+        handler.cancel();
+        println!("\n⛔ Cancelling... (press Ctrl-C again to quit immediately)");
+    });
+    if let Err(e) = installed {
+        info!("Failed to install Ctrl-C handler: {}", e);
     }
+    cancel
+}
 
-    // 打印统计信息
-    stats.print_summary();
+/// 打印运行 ID 并结束运行日志；被取消的运行保持未完成，可以继续
+fn finish_journal(journal: Journal, progress: &Progress) -> Result<()> {
+    print_run_id(&journal);
+    if progress.cancelled {
+        let remaining = progress.files_total - progress.files_done;
+        info!("Run cancelled, {} files not processed", remaining);
+        println!("⛔ Cancelled: {} files not processed", remaining);
+        println!(
+            "   Continue with: MediaClassifier resume {}\n",
+            journal.run_id()
+        );
+        return Ok(());
+    }
+    journal.finish()
+}
+
+/// 需要清理空目录的目录：源目录，没有源目录时为目标目录
+fn dirs_to_clean(should_clean: bool, sources: &[PathBuf], target_dir: &Path) -> Vec<PathBuf> {
+    if !should_clean {
+        Vec::new()
+    } else if sources.is_empty() {
+        vec![target_dir.to_path_buf()]
+    } else {
+        sources.to_vec()
+    }
+}
+
+/// 打印未执行的失效计划条目
+fn print_stale(stale: &[(PathBuf, String)]) {
+    if !stale.is_empty() {
+        println!("⚠️  Stale entries (not applied): {}", stale.len());
+        for (path, reason) in stale {
+            println!("   {} | {}", path.display(), reason);
+        }
+        println!();
+    }
+}
+
+/// 打印中断运行的恢复结果
+fn print_recovery(recovery: &Recovery) {
+    println!(
+        "🔧 Recovered: {} completed before interruption, {} partial files removed, {} pending",
+        recovery.completed.len(),
+        recovery.cleaned.len(),
+        recovery.pending.len()
+    );
+    for path in &recovery.cleaned {
+        println!("   🧹 {}", path.display());
+    }
+    if !recovery.unresolved.is_empty() {
+        println!("⚠️  Needs manual check:");
+        for (path, reason) in &recovery.unresolved {
+            println!("   {} | {}", path.display(), reason);
+        }
+    }
+    println!();
+}
+
+/// 打印运行 ID 和撤销方法
+fn print_run_id(journal: &Journal) {
+    println!("🧾 Run ID: {}", journal.run_id());
+    println!("   Undo with: MediaClassifier undo {}\n", journal.run_id());
+}
+
+/// 列出目标目录下记录的运行
+fn run_list(dest: &Path) -> Result<()> {
+    let runs = list_runs(dest)?;
+    if runs.is_empty() {
+        println!("ℹ️  No recorded runs in {}", dest.display());
+        return Ok(());
+    }
+
+    println!("🧾 Recorded runs in {}:\n", dest.display());
+    for run in &runs {
+        let status = if run.undone {
+            "undone"
+        } else if run.cancelled {
+            "cancelled"
+        } else if run.finished {
+            "finished"
+        } else {
+            "incomplete"
+        };
+        println!(
+            "   {}  {:>6} files  {}",
+            run.run_id, run.transferred, status
+        );
+    }
+    println!();
+
+    Ok(())
+}
+
+/// 撤销一次运行
+fn run_undo(dest: &Path, run_id: &str) -> Result<()> {
+    let journal_path = find_run(dest, run_id)?;
+
+    let log_path = get_log_file_path(dest);
+    init_logger(&log_path)?;
+
+    info!("MediaClassifier started (undo)");
+    info!("Using journal: {:?}", journal_path);
+    println!("🚀 MediaClassifier - Undoing run...\n");
+    println!("🧾 Journal: {}\n", journal_path.display());
+
+    let report = undo_run(&journal_path)?;
+
+    let mut restored = 0;
+    let mut removed = 0;
+    let mut changed = Vec::new();
+    for outcome in &report.outcomes {
+        match outcome {
+            UndoOutcome::Restored { .. } => restored += 1,
+            UndoOutcome::CopyRemoved { .. } => removed += 1,
+            UndoOutcome::Skipped { path, reason } => changed.push((path, reason)),
+        }
+    }
+
+    println!("\n============== Undo Summary ==============");
+    println!("↩️  Moved back:          {}", restored);
+    println!("🗑️  Copies removed:      {}", removed);
+    println!("📁 Directories restored: {}", report.recreated_dirs.len());
+    if !report.cleaned.is_empty() {
+        println!("🧹 Partial files removed: {}", report.cleaned.len());
+    }
+    println!("⚠️  Not undone:          {}", changed.len());
+    println!("==========================================\n");
+
+    if !changed.is_empty() {
+        println!("⚠️  Files changed since the run (left in place):");
+        for (path, reason) in &changed {
+            println!("   {} | {}", path.display(), reason);
+        }
+        println!();
+    }
+
+    println!("📝 Detailed logs saved to: {}", log_path.display());
+    println!("✨ Done!\n");
+
+    Ok(())
+}
+
+/// 报告库中的重复文件，可选地删除或移走多余的副本
+fn run_dedupe(
+    config: &Config,
+    dest: &Path,
+    keep: KeepStrategy,
+    action: Option<DedupeAction>,
+) -> Result<()> {
+    let log_path = get_log_file_path(dest);
+    init_logger(&log_path)?;
+
+    info!("MediaClassifier started (dedupe)");
+    info!("Library: {:?}", dest);
+    println!("🚀 MediaClassifier - Finding duplicates...\n");
+    println!("📚 Library: {}\n", dest.display());
+
+    if action.is_some() {
+        check_interrupted_runs(dest)?;
+    }
+
+    print!("🔍 Comparing files...");
+    stdout().flush().ok();
+    let groups = find_duplicate_groups(dest, config.global.jobs)?;
+    println!(" Found {} duplicate groups\n", groups.len());
+    if groups.is_empty() {
+        println!("✨ Done!\n");
+        return Ok(());
+    }
+
+    // 只有移动副本时才需要运行日志（删除无法撤销）
+    let mut journal = match &action {
+        Some(DedupeAction::MoveTo(_)) => Some(Journal::create(dest, &[], None)?),
+        _ => None,
+    };
+
+    let mut duplicates = 0;
+    let mut wasted = 0;
+    let mut failed = 0;
+    for group in &groups {
+        let keeper = group.keeper(keep);
+        println!("[KEEP] {}", keeper.display());
+        for path in group.duplicates(keep) {
+            duplicates += 1;
+            wasted += group.size;
+            let Some(action) = &action else {
+                println!("[DUP]  {}", path.display());
+                continue;
+            };
+            match remove_duplicate(path, dest, action, journal.as_mut()) {
+                Ok(Some(moved)) => println!("[MOVED]   {} -> {}", path.display(), moved.display()),
+                Ok(None) => println!("[DELETED] {}", path.display()),
+                Err(e) => {
+                    println!("[FAIL]    {} | {}", path.display(), e);
+                    info!("[ERROR] {} -> {}", path.display(), e);
+                    failed += 1;
+                },
+            }
+        }
+        println!();
+    }
+
+    println!("============== Dedupe Summary ==============");
+    println!("📦 Duplicate groups:    {}", groups.len());
+    println!("📄 Redundant copies:    {}", duplicates);
+    println!(
+        "💾 Reclaimable space:   {:.1} MB",
+        wasted as f64 / 1024.0 / 1024.0
+    );
+    if action.is_some() {
+        println!("❌ Failed:              {}", failed);
+    }
+    println!("============================================\n");
+    info!(
+        "Dedupe completed: {} groups, {} redundant copies, {} failed",
+        groups.len(),
+        duplicates,
+        failed
+    );
+
+    if let Some(journal) = journal {
+        print_run_id(&journal);
+        journal.finish()?;
+    } else if action.is_none() {
+        println!("ℹ️  Nothing was changed. Use --move-to DIR or --delete to remove the copies.\n");
+    }
 
-    // 显示日志文件路径
     println!("📝 Detailed logs saved to: {}", log_path.display());
     println!("✨ Done!\n");
 
     Ok(())
 }
 
+/// 列出库中的相似图片簇
+fn run_similar(config: &Config, dest: &Path, threshold: u8) -> Result<()> {
+    let log_path = get_log_file_path(dest);
+    init_logger(&log_path)?;
+
+    info!("MediaClassifier started (similar)");
+    info!("Library: {:?}, threshold: {}%", dest, threshold);
+    println!("🚀 MediaClassifier - Finding similar images...\n");
+    println!("📚 Library: {}", dest.display());
+    println!("🎚️  Threshold: {}%\n", threshold);
+
+    print!("🖼️  Fingerprinting images...");
+    stdout().flush().ok();
+    let clusters = find_similar_clusters(dest, threshold, config.global.jobs)?;
+    println!(" Found {} clusters\n", clusters.len());
+
+    for (index, cluster) in clusters.iter().enumerate() {
+        println!("Cluster {} ({} images)", index + 1, cluster.len());
+        for image in cluster {
+            println!(
+                "   {:>5}x{:<5} {:>9.1} KB  {:>3}%  {}",
+                image.width,
+                image.height,
+                image.size as f64 / 1024.0,
+                image.similarity,
+                image.path.display()
+            );
+        }
+        println!();
+    }
+    info!("Found {} similar image clusters", clusters.len());
+
+    println!(
+        "ℹ️  Nothing was changed. The first image of each cluster has the highest resolution.\n"
+    );
+    println!("✨ Done!\n");
+
+    Ok(())
+}
+
+/// 打印单个计划条目
+fn print_plan_entry(entry: &PlanEntry) {
+    let target = entry
+        .target
+        .as_ref()
+        .map(|p| p.display().to_string())
+        .unwrap_or_default();
+    let line = match &entry.action {
+        PlannedAction::Move => format!("[MOVE]   {} -> {}", entry.source.display(), target),
+        PlannedAction::Rename => format!("[RENAME] {} -> {}", entry.source.display(), target),
+        PlannedAction::Replace => format!("[REPLACE] {} -> {}", entry.source.display(), target),
+        PlannedAction::Quarantine => {
+            format!("[QUARANTINE] {} -> {}", entry.source.display(), target)
+        },
+        PlannedAction::Skip { reason } => {
+            format!("[SKIP]   {} | {}", entry.source.display(), reason)
+        },
+        PlannedAction::Fail { error } => format!("[FAIL]   {} | {}", entry.source.display(), error),
+    };
+
+    let mut details = Vec::new();
+    if let Some(rule) = &entry.rule {
+        details.push(format!("rule: {}", rule));
+    }
+    if let (Some(date), Some(source)) = (&entry.date, &entry.date_source) {
+        details.push(format!("date: {} ({})", date, source.as_str()));
+    }
+    if entry.transfer_mode != TransferMode::Move && entry.action.transfers() {
+        details.push(format!("mode: {}", entry.transfer_mode.as_str()));
+    }
+    if matches!(
+        entry.action,
+        PlannedAction::Replace | PlannedAction::Quarantine
+    ) {
+        details.push(format!("conflict: {}", entry.conflict.as_str()));
+    }
+    if !entry.sidecars.is_empty() {
+        let names: Vec<String> = entry
+            .sidecars
+            .iter()
+            .map(|s| {
+                s.target
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        details.push(format!("sidecars: {}", names.join(", ")));
+    }
+    if !entry.members.is_empty() {
+        let names: Vec<String> = entry
+            .members
+            .iter()
+            .map(|m| {
+                let name = m.target.file_name().unwrap_or_default().to_string_lossy();
+                match m.skip {
+                    Some(_) => format!("{} (skipped, identical)", name),
+                    None => name.into_owned(),
+                }
+            })
+            .collect();
+        details.push(format!("group: {}", names.join(", ")));
+    }
+    if let Some(sequence) = &entry.sequence {
+        details.push(format!("sequence: {}", sequence));
+    }
+    if let Some(event) = &entry.event {
+        details.push(format!("event: {}", event));
+    }
+
+    if details.is_empty() {
+        println!("{}", line);
+    } else {
+        println!("{}\n         {}", line, details.join(", "));
+    }
+    info!("[PLAN] {}", line);
+}
+
 /// 记录分类结果到日志文件
 fn log_result(result: &ClassifyResult) {
     match result {
-        ClassifyResult::Success { from, to } => {
-            info!("[SUCCESS] {} -> {}", from.display(), to.display());
+        ClassifyResult::Success { from, to, mode, .. } => {
+            info!(
+                "[SUCCESS] {} -> {} ({})",
+                from.display(),
+                to.display(),
+                mode.as_str()
+            );
+        },
+        ClassifyResult::Renamed {
+            from,
+            to,
+            mode,
+            policy,
+        } => {
+            info!(
+                "[RENAMED] {} -> {} ({}, {})",
+                from.display(),
+                to.display(),
+                mode.as_str(),
+                policy.as_str()
+            );
         },
-        ClassifyResult::Renamed { from, to } => {
-            info!("[RENAMED] {} -> {}", from.display(), to.display());
+        ClassifyResult::Replaced {
+            from,
+            to,
+            mode,
+            policy,
+        } => {
+            info!(
+                "[REPLACED] {} -> {} ({}, {})",
+                from.display(),
+                to.display(),
+                mode.as_str(),
+                policy.as_str()
+            );
         },
-        ClassifyResult::Skipped { path, reason } => {
-            info!("[SKIPPED] {} | Reason: {}", path.display(), reason);
+        ClassifyResult::Quarantined {
+            from,
+            to,
+            mode,
+            policy,
+        } => {
+            info!(
+                "[QUARANTINED] {} -> {} ({}, {})",
+                from.display(),
+                to.display(),
+                mode.as_str(),
+                policy.as_str()
+            );
+        },
+        ClassifyResult::Skipped {
+            path,
+            reason,
+            policy,
+        } => {
+            info!(
+                "[SKIPPED] {} | Reason: {} ({})",
+                path.display(),
+                reason,
+                policy.as_str()
+            );
         },
         ClassifyResult::Failed { path, error } => {
             info!("[FAILED] {} | Error: {}", path.display(), error);
@@ -233,47 +1235,3 @@ fn init_logger(log_path: &Path) -> Result<()> {
 
     Ok(())
 }
-
-/// 扫描目录中的所有媒体文件
-/// 返回 (媒体文件列表, 跳过的目录列表)
-fn scan_media_files(dir: &PathBuf, config: &Config) -> Result<(Vec<PathBuf>, Vec<PathBuf>)> {
-    let mut media_files = Vec::new();
-    let mut skipped_dirs = Vec::new();
-    let filter = FileFilter::new(&config.exclude);
-
-    // 首先收集被跳过的目录
-    for entry in WalkDir::new(dir)
-        .min_depth(1)
-        .max_depth(9)
-        .into_iter()
-        .flatten()
-    {
-        if entry.file_type().is_dir() && filter.should_exclude_entry(&entry) {
-            skipped_dirs.push(entry.into_path());
-        }
-    }
-
-    // 收集媒体文件
-    for entry in WalkDir::new(dir)
-        .min_depth(1)
-        .max_depth(9)
-        .into_iter()
-        .filter_entry(|e| !filter.should_exclude_entry(e))
-    {
-        let entry = entry.context("Failed to read directory entry")?;
-
-        // 只处理文件
-        if !entry.file_type().is_file() {
-            continue;
-        }
-
-        let path = entry.path();
-
-        // 检查是否为媒体文件
-        if get_media_info(path).is_some() {
-            media_files.push(path.to_path_buf());
-        }
-    }
-
-    Ok((media_files, skipped_dirs))
-}
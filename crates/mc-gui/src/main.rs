@@ -9,8 +9,10 @@ use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::thread;
 
-use mc_lib::{ClassifyResult, Config, FileFilter, classify_file_with_config};
-use walkdir::WalkDir;
+use mc_lib::{
+    CancelToken, ClassifyResult, Config, Geocoder, Journal, LibraryIndex, Observer, Planner,
+    Progress, Roots, Scanner, SimilarIndex, UndoOutcome, incomplete_runs, list_runs, undo_run,
+};
 
 slint::include_modules!();
 
@@ -32,6 +34,138 @@ fn save_config(config: &Config) -> anyhow::Result<()> {
     Ok(())
 }
 
+/// 把运行事件转发到界面线程的观察者
+struct WindowObserver {
+    window: slint::Weak<MainWindow>,
+}
+
+impl WindowObserver {
+    fn new(window: slint::Weak<MainWindow>) -> Self {
+        WindowObserver { window }
+    }
+
+    /// 在界面线程中更新窗口
+    fn update(&self, f: impl FnOnce(&MainWindow) + Send + 'static) {
+        let window_weak = self.window.clone();
+        slint::invoke_from_event_loop(move || {
+            if let Some(window) = window_weak.upgrade() {
+                f(&window);
+            }
+        })
+        .ok();
+    }
+
+    /// 追加一行日志
+    fn log(&self, msg: String) {
+        self.update(move |window| {
+            let current_log = window.get_log_content().to_string();
+            window.set_log_content((current_log + &msg).into());
+        });
+    }
+
+    /// 更新进度条和进度说明（已处理/总数、预计剩余时间）
+    fn show_progress(&self, progress: &Progress) {
+        let fraction = progress.fraction();
+        let mut detail = format!("{}/{}", progress.files_done, progress.files_total);
+        if let Some(eta) = progress.eta() {
+            let secs = eta.as_secs();
+            detail += &format!(" · ~{}:{:02}", secs / 60, secs % 60);
+        }
+        self.update(move |window| {
+            window.set_progress(fraction);
+            window.set_progress_detail(detail.into());
+        });
+    }
+}
+
+impl Observer for WindowObserver {
+    fn scan_started(&mut self, _sources: &[PathBuf]) {
+        self.update(|window| window.set_log_content("🔍 Scanning files...\n".into()));
+    }
+
+    fn file_classified(
+        &mut self,
+        source: &std::path::Path,
+        result: &anyhow::Result<ClassifyResult>,
+        progress: &Progress,
+    ) {
+        self.show_progress(progress);
+        self.log(match result {
+            Ok(ClassifyResult::Success { from, to, mode, .. }) => format!(
+                "[SUCCESS] {} -> {} ({})\n",
+                from.display(),
+                to.display(),
+                mode.as_str()
+            ),
+            Ok(ClassifyResult::Skipped { path, reason, .. }) => {
+                format!("[SKIPPED] {} | Reason: {}\n", path.display(), reason)
+            },
+            Ok(ClassifyResult::Renamed { from, to, mode, .. }) => format!(
+                "[RENAMED] {} -> {} ({})\n",
+                from.display(),
+                to.display(),
+                mode.as_str()
+            ),
+            Ok(ClassifyResult::Replaced {
+                from,
+                to,
+                mode,
+                policy,
+            }) => format!(
+                "[REPLACED] {} -> {} ({}, {})\n",
+                from.display(),
+                to.display(),
+                mode.as_str(),
+                policy.as_str()
+            ),
+            Ok(ClassifyResult::Quarantined {
+                from,
+                to,
+                mode,
+                policy,
+            }) => format!(
+                "[QUARANTINED] {} -> {} ({}, {})\n",
+                from.display(),
+                to.display(),
+                mode.as_str(),
+                policy.as_str()
+            ),
+            Ok(ClassifyResult::Failed { path, error }) => {
+                format!("[FAILED] {} | Error: {}\n", path.display(), error)
+            },
+            Err(e) => format!("[ERROR] {} | {}\n", source.display(), e),
+        });
+    }
+
+    fn dir_cleaned(&mut self, dir: &std::path::Path) {
+        self.log(format!("🗑️ Removed empty directory: {}\n", dir.display()));
+    }
+
+    fn run_finished(&mut self, progress: &Progress) {
+        let remaining = if progress.cancelled {
+            progress.files_total - progress.files_done
+        } else {
+            0
+        };
+        if remaining > 0 {
+            self.log(format!("⛔ Cancelled, {} files not processed\n", remaining));
+        }
+        // 按冲突策略替换或隔离的文件也已传输，计入成功
+        let stats = Statistics {
+            total: progress.files_total as i32,
+            success: (progress.success + progress.replaced + progress.quarantined) as i32,
+            skipped: progress.skipped as i32,
+            renamed: progress.renamed as i32,
+            failed: progress.failed as i32,
+            remaining: remaining as i32,
+        };
+        self.update(move |window| {
+            window.set_progress(1.0);
+            window.set_stats(stats);
+        });
+    }
+}
+
 /// 从 Config 规则转换为 GUI RuleItem
 fn rules_to_gui(config: &Config) -> Vec<RuleItem> {
     config
@@ -61,6 +195,91 @@ fn rules_to_gui(config: &Config) -> Vec<RuleItem> {
         .collect()
 }
 
+/// 在界面上显示源目录列表
+fn show_sources(window: &MainWindow, sources: &[PathBuf]) {
+    let items: Vec<slint::SharedString> = sources
+        .iter()
+        .map(|path| path.display().to_string().into())
+        .collect();
+    let model = std::rc::Rc::new(slint::VecModel::from(items));
+    window.set_source_directories(model.into());
+}
+
+/// 历史运行所在的目标目录：指定的目标目录，或原地整理时唯一的源目录
+fn runs_destination(window: &MainWindow, sources: &[PathBuf]) -> Option<PathBuf> {
+    let destination = window.get_destination_directory().to_string();
+    if !destination.is_empty() {
+        return Some(PathBuf::from(destination));
+    }
+    match sources {
+        [single] => Roots::in_place(single)
+            .ok()
+            .map(|roots| roots.destination().to_path_buf()),
+        _ => None,
+    }
+}
+
+/// 在后台线程撤销一次运行，把结果追加到日志
+///
+/// 完成后在界面线程中调用 `done`，参数表示是否撤销成功
+fn spawn_undo<F>(window_weak: slint::Weak<MainWindow>, journal_path: PathBuf, done: F)
+where
+    F: FnOnce(&MainWindow, bool) + Send + 'static,
+{
+    if let Some(window) = window_weak.upgrade() {
+        window.set_can_undo(false);
+        window.set_show_stats_popup(false);
+        window.set_show_runs_popup(false);
+        window.set_app_state(AppState::Working);
+        window.set_show_log(true);
+    }
+
+    thread::spawn(move || {
+        let run_id = journal_path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let mut msg = format!("\n↩️ Undoing run {}...\n", run_id);
+        let result = undo_run(&journal_path);
+        match &result {
+            Ok(report) => {
+                for path in &report.cleaned {
+                    msg += &format!("[PARTIAL REMOVED] {}\n", path.display());
+                }
+                for dir in &report.recreated_dirs {
+                    msg += &format!("[RESTORED DIR] {}\n", dir.display());
+                }
+                for outcome in &report.outcomes {
+                    msg += &match outcome {
+                        UndoOutcome::Restored { from, to } => {
+                            format!("[RESTORED] {} -> {}\n", from.display(), to.display())
+                        },
+                        UndoOutcome::CopyRemoved { path } => {
+                            format!("[COPY REMOVED] {}\n", path.display())
+                        },
+                        UndoOutcome::Skipped { path, reason } => {
+                            format!("[NOT UNDONE] {} | {}\n", path.display(), reason)
+                        },
+                    };
+                }
+                msg += "✅ Undo completed\n";
+            },
+            Err(e) => msg += &format!("❌ Undo failed: {}\n", e),
+        }
+        let succeeded = result.is_ok();
+
+        slint::invoke_from_event_loop(move || {
+            if let Some(window) = window_weak.upgrade() {
+                let current_log = window.get_log_content().to_string();
+                window.set_log_content((current_log + &msg).into());
+                window.set_app_state(AppState::Idle);
+                done(&window, succeeded);
+            }
+        })
+        .ok();
+    });
+}
+
 /// 打开 GitHub 页面
 fn open_github_url() {
     let url = "https://github.com/Zander-1024/MediaClassifier";
@@ -102,19 +321,55 @@ fn main() -> anyhow::Result<()> {
     // 加载配置
     let config = Arc::new(Mutex::new(load_config()));
 
-    // 设置默认值
-    main_window.set_working_directory("".into());
+    // 最近一次运行的日志文件（用于撤销）
+    let last_journal: Arc<Mutex<Option<PathBuf>>> = Arc::new(Mutex::new(None));
+
+    // 当前运行的取消和暂停标志，每次开始工作时替换
+    let cancel_token = Arc::new(Mutex::new(CancelToken::new()));
+
+    // 选择的源目录，界面上的列表只用于显示
+    let sources: Arc<Mutex<Vec<PathBuf>>> = Arc::new(Mutex::new(Vec::new()));
 
     // ========================================================================
     // 浏览目录 - 使用 rfd 文件对话框
     // ========================================================================
     let main_window_weak = main_window.as_weak();
+    let sources_clone = sources.clone();
     main_window.on_browse_directory(move || {
         let window_weak = main_window_weak.clone();
-        // 使用 rfd 打开目录选择对话框
-        let folder = rfd::FileDialog::new().pick_folder();
-        if let (Some(path), Some(window)) = (folder, window_weak.upgrade()) {
-            window.set_working_directory(path.display().to_string().into());
+        // 使用 rfd 打开目录选择对话框（可多选源目录），添加到已选择的目录后
+        let folders = rfd::FileDialog::new().pick_folders();
+        if let (Some(paths), Some(window)) = (folders, window_weak.upgrade()) {
+            let mut sources = sources_clone.lock().unwrap();
+            for path in paths {
+                if !sources.contains(&path) {
+                    sources.push(path);
+                }
+            }
+            show_sources(&window, &sources);
+        }
+    });
+
+    // 移除源目录
+    let main_window_weak = main_window.as_weak();
+    let sources_clone = sources.clone();
+    main_window.on_remove_source_directory(move |index| {
+        if let Some(window) = main_window_weak.upgrade() {
+            let mut sources = sources_clone.lock().unwrap();
+            if (index as usize) < sources.len() {
+                sources.remove(index as usize);
+                show_sources(&window, &sources);
+            }
+        }
+    });
+
+    // 浏览目标目录
+    let main_window_weak = main_window.as_weak();
+    main_window.on_browse_destination(move || {
+        if let Some(window) = main_window_weak.upgrade()
+            && let Some(path) = rfd::FileDialog::new().pick_folder()
+        {
+            window.set_destination_directory(path.display().to_string().into());
         }
     });
 
@@ -123,155 +378,291 @@ fn main() -> anyhow::Result<()> {
     // ========================================================================
     let main_window_weak = main_window.as_weak();
     let config_clone = config.clone();
+    let last_journal_clone = last_journal.clone();
+    let cancel_token_clone = cancel_token.clone();
+    let sources_clone = sources.clone();
     main_window.on_start_work(move || {
         let window_weak = main_window_weak.clone();
         let config = config_clone.clone();
+        let last_journal = last_journal_clone.clone();
+        let cancel = CancelToken::new();
+        *cancel_token_clone.lock().unwrap() = cancel.clone();
 
         if let Some(window) = window_weak.upgrade() {
-            let working_dir = window.get_working_directory().to_string();
-            if working_dir.is_empty() {
+            // 源目录（可多个）与目标目录（为空时原地整理）
+            let sources = sources_clone.lock().unwrap().clone();
+            if sources.is_empty() {
                 return;
             }
+            let destination = window.get_destination_directory().to_string();
+            let roots = if destination.is_empty() {
+                match sources.as_slice() {
+                    [single] => Roots::in_place(single),
+                    _ => Err(anyhow::anyhow!(
+                        "A destination directory is required for multiple sources"
+                    )),
+                }
+            } else {
+                Roots::new(sources, &PathBuf::from(&destination))
+            };
+            let roots = match roots {
+                Ok(roots) => roots,
+                Err(e) => {
+                    // Note: Log messages are not localized as they are for file processing output
+                    window.set_log_content(format!("❌ Error: {}\n", e).into());
+                    return;
+                },
+            };
+            let target_dir = roots.destination().to_path_buf();
 
-            let target_dir = PathBuf::from(&working_dir);
-            if !target_dir.exists() || !target_dir.is_dir() {
-                // Note: Log messages are not localized as they are for file processing output
-                window.set_log_content("❌ Error: Directory does not exist\n".into());
+            // 存在被中断的运行时不开始新的运行
+            if let Ok(runs) = incomplete_runs(&target_dir)
+                && let Some(run) = runs.last()
+            {
+                window.set_log_content(
+                    format!(
+                        "❌ Interrupted run {} found in {}.\n\
+                         Resume it with `MediaClassifier resume` or roll it back with \
+                         `MediaClassifier undo` before starting a new run.\n",
+                        run.run_id,
+                        target_dir.display()
+                    )
+                    .into(),
+                );
+                window.set_show_log(true);
                 return;
             }
 
             window.set_app_state(AppState::Working);
             window.set_progress(0.0);
-            window.set_log_content("🔍 Scanning files...\n".into());
+            window.set_progress_detail("".into());
+            window.set_paused(false);
 
             // 在新线程中处理文件
             let window_weak_thread = window_weak.clone();
             thread::spawn(move || {
                 let config_guard = config.lock().unwrap();
-                let filter = FileFilter::new(&config_guard.exclude);
-
-                // 收集所有媒体文件
-                let files: Vec<PathBuf> = WalkDir::new(&target_dir)
-                    .into_iter()
-                    .filter_entry(|e| !filter.should_exclude_entry(e))
-                    .filter_map(|e| e.ok())
-                    .filter(|e| e.file_type().is_file())
-                    .filter(|e| !filter.should_exclude_file(e.path()))
-                    .filter(|e| mc_lib::get_media_info(e.path()).is_some())
-                    .map(|e| e.into_path())
-                    .collect();
-
-                let total = files.len();
-                let mut success = 0;
-                let mut skipped = 0;
-                let mut renamed = 0;
-                let mut failed = 0;
-
-                for (idx, file) in files.iter().enumerate() {
-                    let progress = (idx + 1) as f32 / total as f32;
+                let mut observer = WindowObserver::new(window_weak_thread);
+
+                // 收集所有源目录中的媒体文件（不扫描目标目录）
+                let scan = Scanner::new(&config_guard, &roots)
+                    .with_cancel_token(cancel.clone())
+                    .scan(&mut observer);
+                let files = scan.files;
+                observer.log(format!("📂 Found {} media files\n", files.len()));
+                if !scan.skipped.is_empty() {
+                    observer.log(format!("⏭️ Skipped {} directories\n", scan.skipped.len()));
+                }
 
-                    // 更新 UI 进度
-                    let window_weak_ui = window_weak_thread.clone();
-                    slint::invoke_from_event_loop(move || {
-                        if let Some(window) = window_weak_ui.upgrade() {
-                            window.set_progress(progress);
-                        }
-                    })
-                    .ok();
-
-                    // 分类文件
-                    match classify_file_with_config(&config_guard, &target_dir, file) {
-                        Ok(ClassifyResult::Success { from, to }) => {
-                            success += 1;
-                            let msg = format!("[SUCCESS] {} -> {}\n", from.display(), to.display());
-                            let window_weak_ui = window_weak_thread.clone();
-                            slint::invoke_from_event_loop(move || {
-                                if let Some(window) = window_weak_ui.upgrade() {
-                                    let current_log = window.get_log_content().to_string();
-                                    window.set_log_content((current_log + &msg).into());
-                                }
-                            })
-                            .ok();
-                        },
-                        Ok(ClassifyResult::Skipped { path, reason }) => {
-                            skipped += 1;
-                            let msg =
-                                format!("[SKIPPED] {} | Reason: {}\n", path.display(), reason);
-                            let window_weak_ui = window_weak_thread.clone();
-                            slint::invoke_from_event_loop(move || {
-                                if let Some(window) = window_weak_ui.upgrade() {
-                                    let current_log = window.get_log_content().to_string();
-                                    window.set_log_content((current_log + &msg).into());
-                                }
-                            })
-                            .ok();
-                        },
-                        Ok(ClassifyResult::Renamed { from, to }) => {
-                            renamed += 1;
-                            let msg = format!("[RENAMED] {} -> {}\n", from.display(), to.display());
-                            let window_weak_ui = window_weak_thread.clone();
-                            slint::invoke_from_event_loop(move || {
-                                if let Some(window) = window_weak_ui.upgrade() {
-                                    let current_log = window.get_log_content().to_string();
-                                    window.set_log_content((current_log + &msg).into());
-                                }
-                            })
-                            .ok();
-                        },
-                        Ok(ClassifyResult::Failed { path, error }) => {
-                            failed += 1;
-                            let msg = format!("[FAILED] {} | Error: {}\n", path.display(), error);
-                            let window_weak_ui = window_weak_thread.clone();
-                            slint::invoke_from_event_loop(move || {
-                                if let Some(window) = window_weak_ui.upgrade() {
-                                    let current_log = window.get_log_content().to_string();
-                                    window.set_log_content((current_log + &msg).into());
-                                }
-                            })
-                            .ok();
-                        },
-                        Err(e) => {
-                            failed += 1;
-                            let msg = format!("[ERROR] {} | {}\n", file.display(), e);
-                            let window_weak_ui = window_weak_thread.clone();
-                            slint::invoke_from_event_loop(move || {
-                                if let Some(window) = window_weak_ui.upgrade() {
-                                    let current_log = window.get_log_content().to_string();
-                                    window.set_log_content((current_log + &msg).into());
-                                }
-                            })
-                            .ok();
+                // 运行日志，用于撤销
+                let mut journal = match Journal::create(&target_dir, roots.sources(), None)
+                    .and_then(|mut journal| journal.record_queue(&files).map(|_| journal))
+                {
+                    Ok(journal) => Some(journal),
+                    Err(e) => {
+                        observer.log(format!("⚠️ Journal unavailable, undo disabled: {}\n", e));
+                        None
+                    },
+                };
+
+                // 并行提取元数据，按顺序传输
+                let mut planner =
+                    Planner::new(&config_guard, &target_dir).with_cancel_token(cancel.clone());
+                if config_guard.dedupe.skip_existing {
+                    match LibraryIndex::build(&target_dir) {
+                        Ok(index) => planner = planner.with_library_index(index),
+                        Err(e) => observer.log(format!("⚠️ Library index unavailable: {}\n", e)),
+                    }
+                }
+                if config_guard.similar.enabled {
+                    let jobs = config_guard.global.jobs;
+                    match SimilarIndex::build(&target_dir, &config_guard.similar, jobs) {
+                        Ok(index) => planner = planner.with_similar_index(index),
+                        Err(e) => observer.log(format!("⚠️ Image index unavailable: {}\n", e)),
+                    }
+                }
+                if config_guard.uses_geocoding() {
+                    match Geocoder::from_config(&config_guard.geocoding) {
+                        Ok(geocoder) => {
+                            if config_guard.geocoding.cities.is_none() {
+                                observer.log(
+                                    "ℹ️ Using the bundled major-cities list, which is coarse; \
+                                     set geocoding.cities to a GeoNames dataset for smaller towns\n"
+                                        .to_string(),
+                                );
+                            }
+                            planner = planner.with_geocoder(geocoder)
                         },
+                        Err(e) => observer.log(format!("⚠️ City dataset unavailable: {}\n", e)),
                     }
                 }
 
-                // 清理空目录
-                if config_guard.global.clean_empty_dirs {
-                    mc_lib::remove_empty_dirs(&target_dir).ok();
+                // 分类完成后清理空目录
+                let clean_dirs = if config_guard.global.clean_empty_dirs {
+                    roots.sources().to_vec()
+                } else {
+                    Vec::new()
+                };
+                if let Err(e) = planner.run(&files, journal.as_mut(), &clean_dirs, &mut observer) {
+                    observer.log(format!("❌ {}\n", e));
                 }
 
-                // 完成，更新统计
-                let window_weak_final = window_weak_thread.clone();
-                let total_val = total as i32;
-                slint::invoke_from_event_loop(move || {
-                    if let Some(window) = window_weak_final.upgrade() {
-                        window.set_progress(1.0);
-                        window.set_app_state(AppState::Completed);
-                        window.set_stats(Statistics {
-                            total: total_val,
-                            success,
-                            skipped,
-                            renamed,
-                            failed,
-                        });
-                        window.set_show_stats_popup(true);
-                    }
-                })
-                .ok();
+                // 记录运行日志路径，供撤销使用；取消的运行记为已取消，
+                // 剩余文件留在源目录中，再次开始时重新扫描
+                let can_undo = match journal {
+                    Some(journal) => {
+                        *last_journal.lock().unwrap() = Some(journal.path().to_path_buf());
+                        if cancel.is_cancelled() {
+                            observer.log(
+                                "   Remaining files were left in place; start again to continue\n"
+                                    .to_string(),
+                            );
+                            journal.finish_cancelled().is_ok()
+                        } else {
+                            journal.finish().is_ok()
+                        }
+                    },
+                    None => false,
+                };
+
+                // 完成，显示统计
+                observer.update(move |window| {
+                    window.set_paused(false);
+                    window.set_app_state(AppState::Completed);
+                    window.set_can_undo(can_undo);
+                    window.set_show_stats_popup(true);
+                });
             });
         }
     });
 
+    // ========================================================================
+    // 暂停、继续和取消当前运行
+    // ========================================================================
+    let main_window_weak = main_window.as_weak();
+    let cancel_token_clone = cancel_token.clone();
+    main_window.on_pause_work(move || {
+        let cancel = cancel_token_clone.lock().unwrap().clone();
+        let Some(window) = main_window_weak.upgrade() else {
+            return;
+        };
+        let msg = if cancel.is_paused() {
+            cancel.resume();
+            "▶️ Resumed\n"
+        } else {
+            cancel.pause();
+            "⏸️ Paused after the current file\n"
+        };
+        window.set_paused(cancel.is_paused());
+        let current_log = window.get_log_content().to_string();
+        window.set_log_content((current_log + msg).into());
+    });
+
+    let main_window_weak = main_window.as_weak();
+    main_window.on_cancel_work(move || {
+        let cancel = cancel_token.lock().unwrap().clone();
+        if cancel.is_cancelled() {
+            return;
+        }
+        cancel.cancel();
+        if let Some(window) = main_window_weak.upgrade() {
+            let current_log = window.get_log_content().to_string();
+            window.set_log_content((current_log + "⛔ Cancelling...\n").into());
+        }
+    });
+
+    // ========================================================================
+    // 撤销最近一次运行
+    // ========================================================================
+    let main_window_weak = main_window.as_weak();
+    let last_journal_clone = last_journal.clone();
+    main_window.on_undo_last_run(move || {
+        let Some(journal_path) = last_journal_clone.lock().unwrap().take() else {
+            return;
+        };
+        // 撤销失败时保留日志路径，可以再次尝试
+        let last_journal = last_journal_clone.clone();
+        let retry_path = journal_path.clone();
+        spawn_undo(
+            main_window_weak.clone(),
+            journal_path,
+            move |window, succeeded| {
+                if !succeeded {
+                    *last_journal.lock().unwrap() = Some(retry_path);
+                    window.set_can_undo(true);
+                }
+            },
+        );
+    });
+
+    // ========================================================================
+    // 历史运行 - 列出目标目录中记录的运行，选择其中一个撤销
+    // ========================================================================
+    let run_paths: Arc<Mutex<Vec<PathBuf>>> = Arc::new(Mutex::new(Vec::new()));
+
+    let main_window_weak = main_window.as_weak();
+    let sources_clone = sources.clone();
+    let run_paths_clone = run_paths.clone();
+    main_window.on_show_runs(move || {
+        let Some(window) = main_window_weak.upgrade() else {
+            return;
+        };
+        let destination = runs_destination(&window, &sources_clone.lock().unwrap());
+        let runs = match destination.as_deref().map(list_runs) {
+            Some(Ok(runs)) => runs,
+            Some(Err(e)) => {
+                let current_log = window.get_log_content().to_string();
+                window.set_log_content((current_log + &format!("❌ {}\n", e)).into());
+                window.set_show_log(true);
+                return;
+            },
+            None => return,
+        };
+
+        // 新的运行在前
+        let items: Vec<RunItem> = runs
+            .iter()
+            .rev()
+            .map(|run| RunItem {
+                run_id: run.run_id.clone().into(),
+                transferred: run.transferred as i32,
+                finished: run.finished,
+                cancelled: run.cancelled,
+                undone: run.undone,
+            })
+            .collect();
+        *run_paths_clone.lock().unwrap() = runs.into_iter().rev().map(|run| run.path).collect();
+        let model = std::rc::Rc::new(slint::VecModel::from(items));
+        window.set_runs(model.into());
+        window.set_show_runs_popup(true);
+    });
+
+    let main_window_weak = main_window.as_weak();
+    main_window.on_undo_selected_run(move |index| {
+        let Some(journal_path) = run_paths.lock().unwrap().get(index as usize).cloned() else {
+            return;
+        };
+        // 选中的是最近一次运行时，统计弹窗中不再提供撤销
+        let was_last = last_journal
+            .lock()
+            .unwrap()
+            .take_if(|path| *path == journal_path)
+            .is_some();
+        let last_journal = last_journal.clone();
+        let retry_path = journal_path.clone();
+        // 撤销后重新列出运行，失败的运行仍可再次选择
+        spawn_undo(
+            main_window_weak.clone(),
+            journal_path,
+            move |window, succeeded| {
+                if was_last && !succeeded {
+                    *last_journal.lock().unwrap() = Some(retry_path);
+                }
+                window.invoke_show_runs();
+            },
+        );
+    });
+
     // ========================================================================
     // 切换日志显示
     // ========================================================================
@@ -426,6 +817,10 @@ fn main() -> anyhow::Result<()> {
                 directory_template: template,
                 date_format: Some("YYYYMMDD".to_string()),
                 enabled,
+                transfer_mode: None,
+                conflict: None,
+                filename_template: None,
+                places: Vec::new(),
             };
 
             // 添加或更新配置并保存
@@ -434,8 +829,16 @@ fn main() -> anyhow::Result<()> {
 
                 if is_editing && editing_id >= 0 && (editing_id as usize) < config_guard.rules.len()
                 {
-                    // 更新现有规则
-                    config_guard.rules[editing_id as usize] = new_rule;
+                    // 更新现有规则（保留界面中不可编辑的字段）
+                    let existing = &config_guard.rules[editing_id as usize];
+                    let updated = mc_lib::Rule {
+                        transfer_mode: existing.transfer_mode,
+                        conflict: existing.conflict,
+                        filename_template: existing.filename_template.clone(),
+                        places: existing.places.clone(),
+                        ..new_rule
+                    };
+                    config_guard.rules[editing_id as usize] = updated;
                 } else {
                     // 添加新规则
                     config_guard.rules.push(new_rule);
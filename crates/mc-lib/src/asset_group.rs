@@ -0,0 +1,347 @@
+//! 多文件资产组
+//!
+//! RAW+JPEG 连拍、iPhone 实况照片（HEIC + MOV）以及 `-edited` 导出的编辑版本
+//! 由多个文件组成同一个资产。同一目录下去掉编辑后缀后文件名相同的图片和视频
+//! 归为一组，整组只按主成员匹配规则和提取日期，其余成员跟随主成员的目标路径。
+//! 实况照片的图片和视频按 Apple 内容标识符配对，导出时被重命名的实况照片
+//! 同样能配对，文件名相同但标识符不同的视频不会并入图片；两者都没有标识符时
+//! 才按文件名配对。
+
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+use crate::config::AssetGroupConfig;
+use crate::media_types::{MediaType, get_media_info, is_raw_extension};
+use crate::sidecar::Sidecar;
+
+/// 资产组成员的种类，用于选择主成员
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AssetKind {
+    /// RAW 图片
+    Raw,
+    /// 其他图片（JPEG、HEIC 等）
+    Image,
+    /// 视频
+    Video,
+}
+
+impl AssetKind {
+    /// 文件所属的种类，音频和未知类型返回 None
+    pub fn of(path: &Path) -> Option<AssetKind> {
+        let info = get_media_info(path)?;
+        match info.media_type {
+            MediaType::Image if is_raw_extension(&info.extension.to_lowercase()) => {
+                Some(AssetKind::Raw)
+            },
+            MediaType::Image => Some(AssetKind::Image),
+            MediaType::Video => Some(AssetKind::Video),
+            MediaType::Audio => None,
+        }
+    }
+}
+
+/// 一个资产组
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct AssetGroup {
+    /// 决定整组规则、日期和目标目录的主成员
+    pub primary: PathBuf,
+    /// 其余成员
+    pub members: Vec<PathBuf>,
+}
+
+/// 随主成员传输的组成员
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct GroupMember {
+    /// 成员文件路径
+    pub source: PathBuf,
+    /// 跟随主成员目标路径计算出的目标路径
+    pub target: PathBuf,
+    /// 成员自己的编辑后缀（如 `-edited`），追加到组的文件名主干后
+    #[serde(default)]
+    pub suffix: String,
+    /// 主成员的编辑后缀，计算组的文件名主干时从主成员目标中去掉
+    #[serde(default)]
+    pub primary_suffix: String,
+    /// 成员自己的附属文件
+    #[serde(default)]
+    pub sidecars: Vec<Sidecar>,
+    /// 计划时目标处已有相同文件，成员不会被传输
+    #[serde(default)]
+    pub skip: Option<String>,
+}
+
+impl GroupMember {
+    /// 创建组成员，目标路径跟随主成员的目标路径
+    pub fn new(
+        primary: &Path,
+        member: &Path,
+        primary_target: &Path,
+        edited_suffixes: &[String],
+    ) -> Self {
+        let mut member = GroupMember {
+            source: member.to_path_buf(),
+            target: PathBuf::new(),
+            suffix: edit_suffix(file_stem(member), edited_suffixes).to_string(),
+            primary_suffix: edit_suffix(file_stem(primary), edited_suffixes).to_string(),
+            sidecars: Vec::new(),
+            skip: None,
+        };
+        member.target = member.target_for(primary_target);
+        member
+    }
+
+    /// 根据主成员的最终目标路径计算成员的目标路径
+    ///
+    /// 成员使用主成员最终的文件名主干，保留自己的编辑后缀和扩展名，
+    /// 例如主成员 `IMG_0001.HEIC -> 20251118_143005.HEIC` 时，
+    /// `IMG_0001-edited.jpg` 的目标为 `20251118_143005-edited.jpg`
+    pub fn target_for(&self, primary_target: &Path) -> PathBuf {
+        let target_stem = file_stem(primary_target);
+        let base = target_stem
+            .strip_suffix(self.primary_suffix.as_str())
+            .unwrap_or(target_stem);
+
+        let mut name = format!("{}{}", base, self.suffix);
+        if let Some(ext) = self.source.extension().and_then(|e| e.to_str()) {
+            name.push('.');
+            name.push_str(ext);
+        }
+        primary_target.with_file_name(name)
+    }
+}
+
+/// 文件名主干末尾的编辑后缀（按原样返回），没有时返回空字符串
+fn edit_suffix<'a>(stem: &'a str, suffixes: &[String]) -> &'a str {
+    suffixes
+        .iter()
+        .filter(|suffix| !suffix.is_empty() && stem.len() > suffix.len())
+        .find_map(|suffix| {
+            let start = stem.len() - suffix.len();
+            (stem.is_char_boundary(start) && stem[start..].eq_ignore_ascii_case(suffix))
+                .then(|| &stem[start..])
+        })
+        .unwrap_or("")
+}
+
+fn file_stem(path: &Path) -> &str {
+    path.file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+}
+
+/// 从文件列表中找出资产组（至少两个成员）
+///
+/// 扩展名属于 `excluded_extensions`（附属文件）的文件不参与分组。
+/// 视频和同目录中的图片按 `content_id` 读取的内容标识符配对，
+/// 只有在图片和视频都没有标识符时才按文件名配对。
+/// 主成员按 `config.primary` 的种类顺序选择，同种类中优先未编辑的原件；
+/// `can_be_primary` 返回 false 的成员（如没有匹配的规则）不会被选为主成员，
+/// 没有可选主成员的组不分组
+pub(crate) fn find_asset_groups<F, I>(
+    files: &[PathBuf],
+    config: &AssetGroupConfig,
+    excluded_extensions: &[String],
+    content_id: I,
+    can_be_primary: F,
+) -> Vec<AssetGroup>
+where
+    F: Fn(&Path) -> bool,
+    I: Fn(&Path) -> Option<String>,
+{
+    let candidates: Vec<(&PathBuf, AssetKind)> = files
+        .iter()
+        .filter(|file| {
+            !file
+                .extension()
+                .and_then(|ext| ext.to_str())
+                .is_some_and(|ext| {
+                    excluded_extensions
+                        .iter()
+                        .any(|e| e.eq_ignore_ascii_case(ext))
+                })
+        })
+        .filter_map(|file| Some((file, AssetKind::of(file)?)))
+        .collect();
+    let parent = |file: &Path| file.parent().map(Path::to_path_buf).unwrap_or_default();
+    let stem_key = |file: &Path| {
+        let stem = file_stem(file);
+        let base = &stem[..stem.len() - edit_suffix(stem, &config.edited_suffixes).len()];
+        (parent(file), base.to_lowercase())
+    };
+
+    // 只在有视频的目录中读取内容标识符
+    let video_dirs: HashSet<PathBuf> = candidates
+        .iter()
+        .filter(|(_, kind)| *kind == AssetKind::Video)
+        .map(|(file, _)| parent(file))
+        .collect();
+    let ids: HashMap<&PathBuf, String> = candidates
+        .iter()
+        .filter(|(file, kind)| *kind != AssetKind::Raw && video_dirs.contains(&parent(file)))
+        .filter_map(|(file, _)| Some((*file, content_id(file)?)))
+        .collect();
+    // (目录, 标识符) -> 带该标识符的第一张图片所在的组
+    let mut image_keys = HashMap::new();
+    for (file, kind) in &candidates {
+        if *kind == AssetKind::Image
+            && let Some(id) = ids.get(file)
+        {
+            image_keys
+                .entry((parent(file), id.as_str()))
+                .or_insert_with(|| stem_key(file));
+        }
+    }
+
+    let mut groups: HashMap<(PathBuf, String), Vec<&PathBuf>> = HashMap::new();
+    let mut order = Vec::new();
+    for (file, kind) in &candidates {
+        let key = match (kind, ids.get(file)) {
+            // 带标识符的视频并入标识符相同的图片所在的组，没有时单独成组
+            (AssetKind::Video, Some(id)) => match image_keys.get(&(parent(file), id.as_str())) {
+                Some(key) => key.clone(),
+                None => (parent(file), format!("\0{}", file.display())),
+            },
+            _ => stem_key(file),
+        };
+        let members = groups.entry(key.clone()).or_default();
+        if members.is_empty() {
+            order.push(key);
+        }
+        members.push(file);
+    }
+
+    // 组中的图片带有标识符时，没有标识符的视频不是它的实况视频
+    for members in groups.values_mut() {
+        let image_has_id = members
+            .iter()
+            .any(|file| AssetKind::of(file) == Some(AssetKind::Image) && ids.contains_key(file));
+        if image_has_id {
+            members.retain(|file| {
+                AssetKind::of(file) != Some(AssetKind::Video) || ids.contains_key(file)
+            });
+        }
+    }
+
+    let rank = |file: &Path| {
+        let kind = AssetKind::of(file)
+            .and_then(|kind| config.primary.iter().position(|k| *k == kind))
+            .unwrap_or(config.primary.len());
+        let edited = !edit_suffix(file_stem(file), &config.edited_suffixes).is_empty();
+        (kind, edited)
+    };
+
+    let mut result = Vec::new();
+    for key in order {
+        let files = &groups[&key];
+        if files.len() < 2 {
+            continue;
+        }
+        let Some(primary) = files
+            .iter()
+            .filter(|file| can_be_primary(file))
+            .min_by_key(|file| (rank(file), file.as_path()))
+        else {
+            continue;
+        };
+        result.push(AssetGroup {
+            primary: (*primary).clone(),
+            members: files
+                .iter()
+                .filter(|file| *file != primary)
+                .map(|file| (*file).clone())
+                .collect(),
+        });
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_find_asset_groups() {
+        let config = AssetGroupConfig::default();
+        let files: Vec<PathBuf> = [
+            "/card/DSC_0001.JPG",
+            "/card/DSC_0001.NEF",
+            "/card/DSC_0001.WAV",
+            "/card/IMG_0002.HEIC",
+            "/card/IMG_0002.MOV",
+            "/card/IMG_0002-edited.jpg",
+            "/card/IMG_0003.JPG",
+            "/other/DSC_0001.JPG",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+
+        let no_id = |_: &Path| None;
+        let groups = find_asset_groups(&files, &config, &["wav".to_string()], no_id, |_| true);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].primary, Path::new("/card/DSC_0001.NEF"));
+        assert_eq!(groups[0].members, [PathBuf::from("/card/DSC_0001.JPG")]);
+        assert_eq!(groups[1].primary, Path::new("/card/IMG_0002.HEIC"));
+        assert_eq!(groups[1].members.len(), 2);
+
+        // 没有匹配规则的成员不作为主成员
+        let groups = find_asset_groups(&files, &config, &[], no_id, |p| {
+            p.extension().is_some_and(|e| e != "NEF")
+        });
+        assert_eq!(groups[0].primary, Path::new("/card/DSC_0001.JPG"));
+
+        // 实况照片按内容标识符配对：文件名相同但标识符不同的视频不并入图片，
+        // 重命名过的实况照片仍然配对，没有标识符的视频不并入带标识符的图片
+        let files: Vec<PathBuf> = [
+            "/phone/IMG_0004.HEIC",
+            "/phone/IMG_0004.MOV",
+            "/phone/Sunset.HEIC",
+            "/phone/Sunset Live.MOV",
+            "/phone/IMG_0005.HEIC",
+            "/phone/IMG_0005.MOV",
+            "/phone/IMG_0006.JPG",
+            "/phone/IMG_0006.MOV",
+        ]
+        .iter()
+        .map(PathBuf::from)
+        .collect();
+        let ids = |p: &Path| {
+            let id = match p.file_name()?.to_str()? {
+                "IMG_0004.HEIC" => "A",
+                "IMG_0004.MOV" => "B",
+                "Sunset.HEIC" | "Sunset Live.MOV" => "C",
+                "IMG_0005.HEIC" => "D",
+                _ => return None,
+            };
+            Some(id.to_string())
+        };
+        let groups = find_asset_groups(&files, &config, &[], ids, |_| true);
+        assert_eq!(groups.len(), 2);
+        assert_eq!(groups[0].primary, Path::new("/phone/Sunset.HEIC"));
+        assert_eq!(groups[0].members, [PathBuf::from("/phone/Sunset Live.MOV")]);
+        // 两者都没有标识符时按文件名配对
+        assert_eq!(groups[1].primary, Path::new("/phone/IMG_0006.JPG"));
+        assert_eq!(groups[1].members, [PathBuf::from("/phone/IMG_0006.MOV")]);
+
+        let member = GroupMember::new(
+            Path::new("/card/IMG_0002.HEIC"),
+            Path::new("/card/IMG_0002-edited.jpg"),
+            Path::new("/lib/Photos/IMG_0002.HEIC"),
+            &config.edited_suffixes,
+        );
+        assert_eq!(member.target, Path::new("/lib/Photos/IMG_0002-edited.jpg"));
+        assert_eq!(
+            member.target_for(Path::new("/lib/Photos/20251118_143005_1.HEIC")),
+            Path::new("/lib/Photos/20251118_143005_1-edited.jpg")
+        );
+        let member = GroupMember::new(
+            Path::new("/card/IMG_0002-edited.jpg"),
+            Path::new("/card/IMG_0002.MOV"),
+            Path::new("/lib/Photos/IMG_0002-edited.jpg"),
+            &config.edited_suffixes,
+        );
+        assert_eq!(member.target, Path::new("/lib/Photos/IMG_0002.MOV"));
+    }
+}
@@ -0,0 +1,773 @@
+//! 音频标签
+//!
+//! 读取 ID3v1/ID3v2（MP3）、Vorbis comment（FLAC、Ogg Vorbis、Opus）和
+//! iTunes 元数据（M4A/M4B）中的艺术家、专辑、曲目等信息，用于展开
+//! `{artist}` / `{album}` / `{track}` 等模板变量。
+
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+use crate::metadata::{be_u32, child_boxes, find_box, find_top_level_box, mp4_boxes, read_box};
+
+/// 读取的标签或注释块的最大字节数（包含封面图片时可能较大）
+const MAX_TAG_SIZE: u64 = 16 << 20;
+
+/// ID3v1 的前 80 种流派，ID3v2 和 MP4 中的数字流派也使用这张表
+const ID3_GENRES: [&str; 80] = [
+    "Blues",
+    "Classic Rock",
+    "Country",
+    "Dance",
+    "Disco",
+    "Funk",
+    "Grunge",
+    "Hip-Hop",
+    "Jazz",
+    "Metal",
+    "New Age",
+    "Oldies",
+    "Other",
+    "Pop",
+    "R&B",
+    "Rap",
+    "Reggae",
+    "Rock",
+    "Techno",
+    "Industrial",
+    "Alternative",
+    "Ska",
+    "Death Metal",
+    "Pranks",
+    "Soundtrack",
+    "Euro-Techno",
+    "Ambient",
+    "Trip-Hop",
+    "Vocal",
+    "Jazz+Funk",
+    "Fusion",
+    "Trance",
+    "Classical",
+    "Instrumental",
+    "Acid",
+    "House",
+    "Game",
+    "Sound Clip",
+    "Gospel",
+    "Noise",
+    "AlternRock",
+    "Bass",
+    "Soul",
+    "Punk",
+    "Space",
+    "Meditative",
+    "Instrumental Pop",
+    "Instrumental Rock",
+    "Ethnic",
+    "Gothic",
+    "Darkwave",
+    "Techno-Industrial",
+    "Electronic",
+    "Pop-Folk",
+    "Eurodance",
+    "Dream",
+    "Southern Rock",
+    "Comedy",
+    "Cult",
+    "Gangsta",
+    "Top 40",
+    "Christian Rap",
+    "Pop/Funk",
+    "Jungle",
+    "Native American",
+    "Cabaret",
+    "New Wave",
+    "Psychedelic",
+    "Rave",
+    "Showtunes",
+    "Trailer",
+    "Lo-Fi",
+    "Tribal",
+    "Acid Punk",
+    "Acid Jazz",
+    "Polka",
+    "Retro",
+    "Musical",
+    "Rock & Roll",
+    "Hard Rock",
+];
+
+/// 音频文件的标签
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AudioTags {
+    pub artist: Option<String>,
+    pub album_artist: Option<String>,
+    pub album: Option<String>,
+    pub title: Option<String>,
+    pub genre: Option<String>,
+    /// 曲目号（不含总数）
+    pub track: Option<u32>,
+    /// 碟号（不含总数）
+    pub disc: Option<u32>,
+    pub year: Option<i32>,
+}
+
+impl AudioTags {
+    /// 模板变量名和值，曲目号补齐为两位；文件中没有的标签值为 None
+    pub fn template_values(&self) -> [(&'static str, Option<String>); 8] {
+        [
+            ("artist", self.artist.clone()),
+            ("album_artist", self.album_artist.clone()),
+            ("album", self.album.clone()),
+            ("track", self.track.map(|n| format!("{:02}", n))),
+            ("disc", self.disc.map(|n| n.to_string())),
+            ("title", self.title.clone()),
+            ("genre", self.genre.clone()),
+            ("tag_year", self.year.map(|y| format!("{:04}", y))),
+        ]
+    }
+
+    /// 用 `other` 中的值补齐缺少的标签
+    fn fill_from(&mut self, other: AudioTags) {
+        fn fill<T>(field: &mut Option<T>, value: Option<T>) {
+            if field.is_none() {
+                *field = value;
+            }
+        }
+        fill(&mut self.artist, other.artist);
+        fill(&mut self.album_artist, other.album_artist);
+        fill(&mut self.album, other.album);
+        fill(&mut self.title, other.title);
+        fill(&mut self.genre, other.genre);
+        fill(&mut self.track, other.track);
+        fill(&mut self.disc, other.disc);
+        fill(&mut self.year, other.year);
+    }
+
+    fn is_empty(&self) -> bool {
+        *self == AudioTags::default()
+    }
+}
+
+/// 读取音频文件的标签，按文件头识别格式
+pub fn read_audio_tags(path: &Path) -> Result<AudioTags> {
+    let mut file = File::open(path).context("Failed to open file for tag reading")?;
+    let mut magic = [0u8; 12];
+    let len = read_up_to(&mut file, &mut magic)?;
+    let magic = &magic[..len];
+
+    let mut tags = AudioTags::default();
+    let mut offset = 0;
+    if magic.starts_with(b"ID3") {
+        file.seek(SeekFrom::Start(0))?;
+        let (id3, size) = read_id3v2(&mut file)?;
+        tags = id3;
+        offset = size;
+    }
+
+    file.seek(SeekFrom::Start(offset))?;
+    let mut head = [0u8; 12];
+    let len = read_up_to(&mut file, &mut head)?;
+    let head = &head[..len];
+    file.seek(SeekFrom::Start(offset))?;
+    if head.starts_with(b"fLaC") {
+        let mut flac = read_flac(&mut file)?;
+        flac.fill_from(tags);
+        tags = flac;
+    } else if head.starts_with(b"OggS") {
+        tags = read_ogg(&mut file)?;
+    } else if head.get(4..8) == Some(b"ftyp") {
+        tags = read_mp4(&mut file)?;
+    } else if let Some(id3v1) = read_id3v1(&mut file)? {
+        tags.fill_from(id3v1);
+    }
+
+    if tags.is_empty() {
+        anyhow::bail!("No audio tags found");
+    }
+    Ok(tags)
+}
+
+fn read_up_to<R: Read>(reader: &mut R, buf: &mut [u8]) -> Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match reader.read(&mut buf[filled..])? {
+            0 => break,
+            n => filled += n,
+        }
+    }
+    Ok(filled)
+}
+
+fn read_exact_vec<R: Read>(reader: &mut R, len: u64) -> Result<Vec<u8>> {
+    if len > MAX_TAG_SIZE {
+        anyhow::bail!("Tag too large: {} bytes", len);
+    }
+    let mut data = vec![0u8; len as usize];
+    reader.read_exact(&mut data).context("Truncated tag")?;
+    Ok(data)
+}
+
+/// 读取文件开头的 ID3v2 标签，返回标签和标签占用的字节数
+fn read_id3v2<R: Read>(reader: &mut R) -> Result<(AudioTags, u64)> {
+    let mut header = [0u8; 10];
+    reader.read_exact(&mut header)?;
+    let version = header[3];
+    let flags = header[5];
+    let size = syncsafe(&header[6..10]) as u64;
+    let footer = if version >= 4 && flags & 0x10 != 0 {
+        10
+    } else {
+        0
+    };
+    let mut body = read_exact_vec(reader, size)?;
+
+    // 2.3 及以前的非同步化作用于整个标签
+    if version < 4 && flags & 0x80 != 0 {
+        body = remove_unsync(&body);
+    }
+    let mut frames = body.as_slice();
+    if flags & 0x40 != 0 && version >= 3 {
+        let ext_size = be_u32(frames, 0).context("Truncated extended header")? as usize;
+        let skip = if version >= 4 {
+            syncsafe(&frames[..4]) as usize
+        } else {
+            ext_size + 4
+        };
+        frames = frames.get(skip..).unwrap_or_default();
+    }
+
+    let (id_len, header_len) = if version == 2 { (3, 6) } else { (4, 10) };
+    let mut tags = AudioTags::default();
+    while frames.len() >= header_len && frames[0] != 0 {
+        let id = String::from_utf8_lossy(&frames[..id_len]).into_owned();
+        let size = match version {
+            2 => u32::from_be_bytes([0, frames[3], frames[4], frames[5]]),
+            3 => be_u32(frames, 4).unwrap_or_default(),
+            _ => syncsafe(&frames[4..8]),
+        } as usize;
+        let format = if version >= 3 { frames[9] } else { 0 };
+        let Some(data) = frames.get(header_len..header_len.saturating_add(size)) else {
+            break;
+        };
+        frames = &frames[header_len + size..];
+
+        // 只解码文本帧，跳过封面等二进制帧
+        if !id.starts_with('T') {
+            continue;
+        }
+        if let Some(value) = frame_data(data, version, format).and_then(|d| decode_text_frame(&d)) {
+            apply_id3_frame(&mut tags, &id, value);
+        }
+    }
+
+    Ok((tags, 10 + size + footer))
+}
+
+/// 按帧格式标志去掉分组标识、数据长度和非同步化，压缩或加密的帧返回 None
+fn frame_data(data: &[u8], version: u8, format: u8) -> Option<Vec<u8>> {
+    match version {
+        3 => {
+            if format & 0xC0 != 0 {
+                return None;
+            }
+            let skip = if format & 0x20 != 0 { 1 } else { 0 };
+            Some(data.get(skip..)?.to_vec())
+        },
+        4 => {
+            if format & 0x0C != 0 {
+                return None;
+            }
+            let skip = (format & 0x40 != 0) as usize + 4 * (format & 0x01 != 0) as usize;
+            let data = data.get(skip..)?;
+            Some(if format & 0x02 != 0 {
+                remove_unsync(data)
+            } else {
+                data.to_vec()
+            })
+        },
+        _ => Some(data.to_vec()),
+    }
+}
+
+fn apply_id3_frame(tags: &mut AudioTags, id: &str, value: String) {
+    match id {
+        "TPE1" | "TP1" => tags.artist = Some(value),
+        "TPE2" | "TP2" => tags.album_artist = Some(value),
+        "TALB" | "TAL" => tags.album = Some(value),
+        "TIT2" | "TT2" => tags.title = Some(value),
+        "TCON" | "TCO" => tags.genre = parse_id3_genre(&value),
+        "TRCK" | "TRK" => tags.track = parse_number(&value),
+        "TPOS" | "TPA" => tags.disc = parse_number(&value),
+        "TDRC" | "TYER" | "TYE" => tags.year = parse_year(&value),
+        "TDOR" | "TORY" if tags.year.is_none() => tags.year = parse_year(&value),
+        _ => {},
+    }
+}
+
+/// 解码文本帧：编码字节之后是文本，多个值以 NUL 分隔时取第一个
+fn decode_text_frame(data: &[u8]) -> Option<String> {
+    let (&encoding, text) = data.split_first()?;
+    let text = match encoding {
+        0 => text.iter().map(|&b| b as char).collect(),
+        1 => decode_utf16(text, None),
+        2 => decode_utf16(text, Some(false)),
+        3 => String::from_utf8_lossy(text).into_owned(),
+        _ => return None,
+    };
+    clean_text(text.split('\0').next().unwrap_or_default())
+}
+
+/// 解码 UTF-16，`little_endian` 为 None 时由 BOM 决定（没有 BOM 时按小端）
+fn decode_utf16(data: &[u8], little_endian: Option<bool>) -> String {
+    let (little_endian, data) = match (little_endian, data) {
+        (Some(le), data) => (le, data),
+        (None, [0xFF, 0xFE, rest @ ..]) => (true, rest),
+        (None, [0xFE, 0xFF, rest @ ..]) => (false, rest),
+        (None, data) => (true, data),
+    };
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|pair| {
+            let pair = [pair[0], pair[1]];
+            if little_endian {
+                u16::from_le_bytes(pair)
+            } else {
+                u16::from_be_bytes(pair)
+            }
+        })
+        .collect();
+    String::from_utf16_lossy(&units)
+}
+
+/// ID3v2 的流派可以是名称、"(13)"、"(13)Pop" 或 "13"
+fn parse_id3_genre(value: &str) -> Option<String> {
+    if let Some(rest) = value.strip_prefix('(')
+        && let Some((number, name)) = rest.split_once(')')
+    {
+        return clean_text(name).or_else(|| genre_name(number.parse().ok()?));
+    }
+    match value.parse::<usize>() {
+        Ok(index) => genre_name(index),
+        Err(_) => clean_text(value),
+    }
+}
+
+fn genre_name(index: usize) -> Option<String> {
+    ID3_GENRES.get(index).map(|name| name.to_string())
+}
+
+/// 读取文件末尾的 ID3v1 标签
+fn read_id3v1<R: Read + Seek>(reader: &mut R) -> Result<Option<AudioTags>> {
+    if reader.seek(SeekFrom::End(0))? < 128 {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::End(-128))?;
+    let mut tag = [0u8; 128];
+    reader.read_exact(&mut tag)?;
+    if &tag[..3] != b"TAG" {
+        return Ok(None);
+    }
+
+    let text = |range: std::ops::Range<usize>| {
+        let field = &tag[range];
+        let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+        clean_text(&field[..end].iter().map(|&b| b as char).collect::<String>())
+    };
+    // ID3v1.1：注释的第 29 字节为 0 时第 30 字节是曲目号
+    let track = (tag[125] == 0 && tag[126] != 0).then_some(tag[126] as u32);
+    Ok(Some(AudioTags {
+        title: text(3..33),
+        artist: text(33..63),
+        album: text(63..93),
+        year: text(93..97).and_then(|y| parse_year(&y)),
+        track,
+        genre: genre_name(tag[127] as usize),
+        ..AudioTags::default()
+    }))
+}
+
+/// FLAC：元数据块中的 VORBIS_COMMENT
+fn read_flac<R: Read + Seek>(reader: &mut R) -> Result<AudioTags> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic)?;
+    loop {
+        let mut header = [0u8; 4];
+        reader
+            .read_exact(&mut header)
+            .context("No Vorbis comment found")?;
+        let last = header[0] & 0x80 != 0;
+        let block_type = header[0] & 0x7F;
+        let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as u64;
+        if block_type == 4 {
+            return parse_vorbis_comment(&read_exact_vec(reader, len)?)
+                .context("Invalid Vorbis comment");
+        }
+        if last {
+            anyhow::bail!("No Vorbis comment found");
+        }
+        reader.seek(SeekFrom::Current(len as i64))?;
+    }
+}
+
+/// Ogg Vorbis / Opus：第一个逻辑流的第二个数据包是注释头
+fn read_ogg<R: Read>(reader: &mut R) -> Result<AudioTags> {
+    let mut packets: Vec<Vec<u8>> = vec![Vec::new()];
+    let mut serial = None;
+    let mut total = 0u64;
+    while packets.len() < 3 {
+        let mut header = [0u8; 27];
+        reader
+            .read_exact(&mut header)
+            .context("No comment header found")?;
+        if &header[..4] != b"OggS" {
+            anyhow::bail!("Invalid Ogg page");
+        }
+        let mut segments = vec![0u8; header[26] as usize];
+        reader.read_exact(&mut segments)?;
+        let page_len: u64 = segments.iter().map(|&n| n as u64).sum();
+        let data = read_exact_vec(reader, page_len)?;
+        total += page_len;
+        if total > MAX_TAG_SIZE {
+            anyhow::bail!("Comment header too large");
+        }
+
+        let page_serial = u32::from_le_bytes([header[14], header[15], header[16], header[17]]);
+        if *serial.get_or_insert(page_serial) != page_serial {
+            continue;
+        }
+        // 长度小于 255 的段结束一个数据包
+        let mut offset = 0;
+        for &len in &segments {
+            let len = len as usize;
+            let current = packets.len() - 1;
+            packets[current].extend_from_slice(&data[offset..offset + len]);
+            offset += len;
+            if len < 255 {
+                packets.push(Vec::new());
+            }
+        }
+    }
+
+    let comment = &packets[1];
+    let body = if let Some(body) = comment.strip_prefix(b"\x03vorbis") {
+        body
+    } else if let Some(body) = comment.strip_prefix(b"OpusTags") {
+        body
+    } else {
+        anyhow::bail!("Unsupported Ogg codec");
+    };
+    parse_vorbis_comment(body).context("Invalid Vorbis comment")
+}
+
+/// Vorbis comment：供应商字符串，之后是若干 "KEY=value"（小端长度前缀）
+fn parse_vorbis_comment(data: &[u8]) -> Option<AudioTags> {
+    let le_u32 = |at: usize| -> Option<usize> {
+        Some(u32::from_le_bytes(data.get(at..at + 4)?.try_into().ok()?) as usize)
+    };
+    let mut offset = 4 + le_u32(0)?;
+    let count = le_u32(offset)?;
+    offset += 4;
+
+    let mut tags = AudioTags::default();
+    for _ in 0..count {
+        let len = le_u32(offset)?;
+        let entry = data.get(offset + 4..offset + 4 + len)?;
+        offset += 4 + len;
+        let entry = String::from_utf8_lossy(entry);
+        let Some((key, value)) = entry.split_once('=') else {
+            continue;
+        };
+        let Some(value) = clean_text(value) else {
+            continue;
+        };
+        let field = match key.to_ascii_uppercase().as_str() {
+            "ARTIST" => &mut tags.artist,
+            "ALBUMARTIST" | "ALBUM ARTIST" | "ALBUM_ARTIST" => &mut tags.album_artist,
+            "ALBUM" => &mut tags.album,
+            "TITLE" => &mut tags.title,
+            "GENRE" => &mut tags.genre,
+            "TRACKNUMBER" => {
+                tags.track = tags.track.or(parse_number(&value));
+                continue;
+            },
+            "DISCNUMBER" => {
+                tags.disc = tags.disc.or(parse_number(&value));
+                continue;
+            },
+            "DATE" | "YEAR" => {
+                tags.year = tags.year.or(parse_year(&value));
+                continue;
+            },
+            _ => continue,
+        };
+        // 同一个键出现多次时取第一个值
+        if field.is_none() {
+            *field = Some(value);
+        }
+    }
+    Some(tags)
+}
+
+/// M4A/M4B：moov/udta/meta/ilst 中的 iTunes 元数据
+fn read_mp4<R: Read + Seek>(reader: &mut R) -> Result<AudioTags> {
+    let moov = find_top_level_box(reader, b"moov")?.context("No moov box found")?;
+    let find_child = |reader: &mut R, parent, wanted: &[u8; 4]| -> Result<_> {
+        Ok(child_boxes(reader, parent)?
+            .into_iter()
+            .find(|(kind, _)| kind == wanted)
+            .map(|(_, range)| range))
+    };
+    let udta = find_child(reader, moov, b"udta")?.context("No iTunes metadata found")?;
+    let meta = find_child(reader, udta, b"meta")?.context("No iTunes metadata found")?;
+    let meta = read_box(reader, meta)?;
+    // iTunes 的 meta 前面有版本和标志字段
+    let meta = if meta.get(4..8) == Some(b"hdlr") {
+        &meta[..]
+    } else {
+        meta.get(4..).unwrap_or_default()
+    };
+    let ilst = find_box(meta, b"ilst").context("No iTunes metadata found")?;
+
+    let mut tags = AudioTags::default();
+    for (kind, item) in mp4_boxes(ilst) {
+        // data box：类型、语言，之后是值
+        let Some(value) = find_box(item, b"data").and_then(|data| data.get(8..)) else {
+            continue;
+        };
+        let text = || clean_text(&String::from_utf8_lossy(value));
+        match kind {
+            b"\xA9ART" => tags.artist = text(),
+            b"aART" => tags.album_artist = text(),
+            b"\xA9alb" => tags.album = text(),
+            b"\xA9nam" => tags.title = text(),
+            b"\xA9gen" => tags.genre = text(),
+            b"gnre" => {
+                // ID3v1 流派序号加 1
+                let index = number_at(value, 0).and_then(|n| n.checked_sub(1));
+                tags.genre = tags
+                    .genre
+                    .take()
+                    .or(index.and_then(|i| genre_name(i as usize)));
+            },
+            // 曲目和碟号：2 字节保留，之后是序号和总数
+            b"trkn" => tags.track = number_at(value, 2).filter(|&n| n > 0),
+            b"disk" => tags.disc = number_at(value, 2).filter(|&n| n > 0),
+            b"\xA9day" => tags.year = text().and_then(|y| parse_year(&y)),
+            _ => {},
+        }
+    }
+    Ok(tags)
+}
+
+fn number_at(data: &[u8], at: usize) -> Option<u32> {
+    data.get(at..at + 2)
+        .map(|n| u16::from_be_bytes([n[0], n[1]]) as u32)
+}
+
+/// ID3v2 的同步安全整数（每字节 7 位）
+fn syncsafe(bytes: &[u8]) -> u32 {
+    bytes
+        .iter()
+        .take(4)
+        .fold(0, |value, &b| (value << 7) | (b & 0x7F) as u32)
+}
+
+/// 还原非同步化：0xFF 0x00 还原为 0xFF
+fn remove_unsync(data: &[u8]) -> Vec<u8> {
+    let mut result = Vec::with_capacity(data.len());
+    let mut previous = 0u8;
+    for &byte in data {
+        if !(previous == 0xFF && byte == 0) {
+            result.push(byte);
+        }
+        previous = byte;
+    }
+    result
+}
+
+/// 去掉首尾空白和 NUL，空字符串返回 None
+fn clean_text(text: &str) -> Option<String> {
+    let text = text.trim_matches(|c: char| c == '\0' || c.is_whitespace());
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// "5/12" 或 "05" 中的数字部分
+fn parse_number(value: &str) -> Option<u32> {
+    let digits: String = value
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect();
+    digits.parse().ok().filter(|&n| n > 0)
+}
+
+/// "2019"、"2019-05-03" 或 "2019-05-03T12:00" 中的年份
+fn parse_year(value: &str) -> Option<i32> {
+    let year = value.trim().get(..4)?;
+    year.chars()
+        .all(|c| c.is_ascii_digit())
+        .then(|| year.parse().ok())
+        .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn id3_frame(id: &[u8; 4], text: &str) -> Vec<u8> {
+        let mut data = id.to_vec();
+        data.extend_from_slice(&(text.len() as u32 + 1).to_be_bytes());
+        data.extend_from_slice(&[0, 0, 3]);
+        data.extend_from_slice(text.as_bytes());
+        data
+    }
+
+    fn vorbis_comment(entries: &[&str]) -> Vec<u8> {
+        let mut data = 6u32.to_le_bytes().to_vec();
+        data.extend_from_slice(b"vendor");
+        data.extend_from_slice(&(entries.len() as u32).to_le_bytes());
+        for entry in entries {
+            data.extend_from_slice(&(entry.len() as u32).to_le_bytes());
+            data.extend_from_slice(entry.as_bytes());
+        }
+        data
+    }
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn test_read_id3_tags() {
+        let dir = TempDir::new().unwrap();
+
+        // ID3v2.3 标签，流派使用数字形式；末尾的 ID3v1 补充缺少的专辑
+        let frames = [
+            id3_frame(b"TPE1", "Artist"),
+            id3_frame(b"TPE2", "Various"),
+            id3_frame(b"TIT2", "Song"),
+            id3_frame(b"TRCK", "3/12"),
+            id3_frame(b"TPOS", "1/2"),
+            id3_frame(b"TCON", "(17)"),
+            id3_frame(b"TYER", "1999"),
+        ]
+        .concat();
+        let size = frames.len() as u32;
+        let syncsafe_size = [
+            (size >> 21) as u8 & 0x7F,
+            (size >> 14) as u8 & 0x7F,
+            (size >> 7) as u8 & 0x7F,
+            size as u8 & 0x7F,
+        ];
+        let mut v1 = [0u8; 128];
+        v1[..3].copy_from_slice(b"TAG");
+        v1[63..68].copy_from_slice(b"Album");
+        v1[127] = 255;
+        let data = [
+            b"ID3\x03\x00\x00".as_slice(),
+            &syncsafe_size,
+            &frames,
+            &[0xFF, 0xFB, 0x90, 0x00],
+            &v1,
+        ]
+        .concat();
+        let path = dir.path().join("song.mp3");
+        std::fs::write(&path, data).unwrap();
+
+        let tags = read_audio_tags(&path).unwrap();
+        assert_eq!(tags.artist.as_deref(), Some("Artist"));
+        assert_eq!(tags.album_artist.as_deref(), Some("Various"));
+        assert_eq!(tags.album.as_deref(), Some("Album"));
+        assert_eq!(tags.genre.as_deref(), Some("Rock"));
+        assert_eq!(
+            (tags.track, tags.disc, tags.year),
+            (Some(3), Some(1), Some(1999))
+        );
+        assert_eq!(tags.template_values()[3], ("track", Some("03".to_string())));
+
+        assert_eq!(parse_id3_genre("(13)Synth"), Some("Synth".to_string()));
+        assert_eq!(
+            decode_text_frame(b"\x01\xFF\xFEA\x00b\x00"),
+            Some("Ab".to_string())
+        );
+    }
+
+    #[test]
+    fn test_read_vorbis_and_mp4_tags() {
+        let dir = TempDir::new().unwrap();
+        let comment = vorbis_comment(&[
+            "ARTIST=Band",
+            "ALBUMARTIST=Band",
+            "album=Live",
+            "TRACKNUMBER=7",
+            "DATE=2021-06-01",
+        ]);
+
+        // FLAC：STREAMINFO 之后是 VORBIS_COMMENT
+        let mut flac = b"fLaC\x00\x00\x00\x22".to_vec();
+        flac.extend_from_slice(&[0u8; 0x22]);
+        flac.push(0x84);
+        flac.extend_from_slice(&(comment.len() as u32).to_be_bytes()[1..]);
+        flac.extend_from_slice(&comment);
+        let path = dir.path().join("track.flac");
+        std::fs::write(&path, flac).unwrap();
+        let tags = read_audio_tags(&path).unwrap();
+        assert_eq!(tags.album.as_deref(), Some("Live"));
+        assert_eq!((tags.track, tags.year), (Some(7), Some(2021)));
+
+        // Opus：OpusHead 和 OpusTags 各占一页
+        let page = |sequence: u8, packet: &[u8]| {
+            let mut page = b"OggS\x00\x00".to_vec();
+            page.extend_from_slice(&[0u8; 8]);
+            page.extend_from_slice(&[1, 0, 0, 0, sequence, 0, 0, 0, 0, 0, 0, 0]);
+            let mut lacing = vec![255u8; packet.len() / 255];
+            lacing.push((packet.len() % 255) as u8);
+            page.push(lacing.len() as u8);
+            page.extend_from_slice(&lacing);
+            page.extend_from_slice(packet);
+            page
+        };
+        let mut opus_tags = b"OpusTags".to_vec();
+        opus_tags.extend_from_slice(&vorbis_comment(&["TITLE=Intro", "GENRE=Jazz"]));
+        opus_tags.resize(300, 0);
+        let ogg = [page(0, b"OpusHead\x01\x02"), page(1, &opus_tags)].concat();
+        let path = dir.path().join("track.opus");
+        std::fs::write(&path, ogg).unwrap();
+        let tags = read_audio_tags(&path).unwrap();
+        assert_eq!(tags.title.as_deref(), Some("Intro"));
+        assert_eq!(tags.genre.as_deref(), Some("Jazz"));
+
+        // M4A：moov/udta/meta/ilst
+        let item = |kind: &[u8; 4], value: &[u8]| {
+            let data = mp4_box(b"data", &[&[0, 0, 0, 1, 0, 0, 0, 0], value].concat());
+            mp4_box(kind, &data)
+        };
+        let ilst = mp4_box(
+            b"ilst",
+            &[
+                item(b"\xA9ART", b"Singer"),
+                item(b"\xA9alb", b"Record"),
+                item(b"trkn", &[0, 0, 0, 4, 0, 10, 0, 0]),
+                item(b"disk", &[0, 0, 0, 2, 0, 2]),
+                item(b"gnre", &[0, 10]),
+            ]
+            .concat(),
+        );
+        let meta = mp4_box(b"meta", &[&[0u8; 4], ilst.as_slice()].concat());
+        let moov = mp4_box(b"moov", &mp4_box(b"udta", &meta));
+        let m4a = [mp4_box(b"ftyp", b"M4A \x00\x00\x00\x00"), moov].concat();
+        let path = dir.path().join("track.m4a");
+        std::fs::write(&path, m4a).unwrap();
+        let tags = read_audio_tags(&path).unwrap();
+        assert_eq!(tags.artist.as_deref(), Some("Singer"));
+        assert_eq!(tags.album.as_deref(), Some("Record"));
+        assert_eq!(tags.genre.as_deref(), Some("Metal"));
+        assert_eq!((tags.track, tags.disc), (Some(4), Some(2)));
+    }
+}
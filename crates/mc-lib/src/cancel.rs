@@ -0,0 +1,135 @@
+//! 取消和暂停批量运行
+//!
+//! 前端持有 [`CancelToken`] 的克隆，在其他线程（信号处理、界面按钮）中请求
+//! 取消或暂停。批量接口在两个文件之间检查暂停和取消，正在进行的复制在
+//! 数据块之间检查取消，中止时删除不完整的目标文件并保留源文件。
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+
+/// 取消或中止的传输返回的错误
+///
+/// 用 [`Cancelled::is_cause_of`] 判断错误是否由取消引起，外层附加的上下文不影响判断
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cancelled;
+
+impl Cancelled {
+    /// 错误链中是否包含 `Cancelled`
+    pub fn is_cause_of(error: &anyhow::Error) -> bool {
+        error.chain().any(|cause| cause.is::<Cancelled>())
+    }
+}
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Transfer cancelled")
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+/// 可在线程间共享的取消和暂停标志
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    state: Arc<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    cancelled: AtomicBool,
+    paused: Mutex<bool>,
+    changed: Condvar,
+}
+
+impl CancelToken {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 请求取消，同时唤醒暂停中的运行
+    pub fn cancel(&self) {
+        self.state.cancelled.store(true, Ordering::SeqCst);
+        let _paused = self.lock_paused();
+        self.state.changed.notify_all();
+    }
+
+    /// 是否已请求取消
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// 暂停：当前文件完成后不再开始下一个文件
+    pub fn pause(&self) {
+        *self.lock_paused() = true;
+    }
+
+    /// 继续暂停的运行
+    pub fn resume(&self) {
+        *self.lock_paused() = false;
+        self.state.changed.notify_all();
+    }
+
+    /// 是否处于暂停状态
+    pub fn is_paused(&self) -> bool {
+        *self.lock_paused()
+    }
+
+    /// 暂停时阻塞直到继续或取消，返回 false 表示已取消
+    pub fn wait_while_paused(&self) -> bool {
+        let mut paused = self.lock_paused();
+        while *paused && !self.is_cancelled() {
+            paused = self
+                .state
+                .changed
+                .wait(paused)
+                .unwrap_or_else(|e| e.into_inner());
+        }
+        !self.is_cancelled()
+    }
+
+    fn lock_paused(&self) -> std::sync::MutexGuard<'_, bool> {
+        self.state.paused.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+    use std::time::Duration;
+
+    #[test]
+    fn test_pause_resume_and_cancel() {
+        let token = CancelToken::new();
+        assert!(token.wait_while_paused());
+
+        token.pause();
+        let waiter = {
+            let token = token.clone();
+            thread::spawn(move || token.wait_while_paused())
+        };
+        thread::sleep(Duration::from_millis(20));
+        assert!(!waiter.is_finished());
+        token.resume();
+        assert!(waiter.join().unwrap());
+
+        token.pause();
+        let waiter = {
+            let token = token.clone();
+            thread::spawn(move || token.wait_while_paused())
+        };
+        token.cancel();
+        assert!(!waiter.join().unwrap());
+        assert!(token.is_cancelled());
+    }
+
+    #[test]
+    fn test_cancelled_survives_context() {
+        let error = anyhow::Error::new(Cancelled).context("Failed to copy clip.mp4");
+        assert!(Cancelled::is_cause_of(&error));
+        assert!(!Cancelled::is_cause_of(&anyhow::anyhow!(
+            "Transfer cancelled"
+        )));
+    }
+}
@@ -1,117 +1,1402 @@
 use anyhow::{Context, Result};
-use log::{error, info, warn};
+use log::{debug, error, info, warn};
+use std::cell::OnceCell;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
-use crate::config::Config;
-use crate::conflict::{ConflictResolution, resolve_conflict};
+use crate::asset_group::{GroupMember, find_asset_groups};
+use crate::audio_tags::read_audio_tags;
+use crate::cancel::{CancelToken, Cancelled};
+use crate::config::{Config, Rule};
+use crate::conflict::{
+    ConflictPolicy, ConflictResolution, resolve_conflict, resolve_conflict_with_reserved,
+};
+use crate::dedupe::LibraryIndex;
+use crate::event::{EventItem, EventSpan, cluster_events};
+use crate::geocoder::{Geocoder, Location};
+use crate::journal::Journal;
 use crate::media_types::{MediaType, get_media_info};
-use crate::metadata::extract_date;
-use crate::rule_matcher::RuleMatcher;
+use crate::metadata::{
+    GpsPosition, extract_capture_info, extract_content_identifier, extract_date,
+    extract_date_with_source, extract_exif_metadata, extract_gps,
+};
+use crate::observer::{Observer, Progress};
+use crate::pipeline::ordered_parallel;
+use crate::place::find_place;
+use crate::plan::{PlanEntry, PlannedAction, format_modified};
+use crate::rule_matcher::{BatchContext, RuleMatcher};
+use crate::sequence::{SequenceRef, find_sequences};
+use crate::sidecar::{Sidecar, find_primary, find_sidecars, sidecar_target};
+use crate::similar::{ImageFingerprint, SimilarIndex, fingerprint_image, supports_fingerprint};
+use crate::transfer::{TransferMode, transfer_file_with_cancel};
+use crate::utils::{expand_number, has_number_var, relocate, remove_empty_dirs};
 
 /// 文件分类结果
+///
+/// `policy` 为该文件适用的冲突处理策略
 #[derive(Debug, Clone)]
 pub enum ClassifyResult {
-    /// 成功移动文件
-    Success { from: PathBuf, to: PathBuf },
-    /// 跳过文件（已存在且相同）
-    Skipped { path: PathBuf, reason: String },
-    /// 重命名后移动
-    Renamed { from: PathBuf, to: PathBuf },
+    /// 成功传输文件
+    Success {
+        from: PathBuf,
+        to: PathBuf,
+        mode: TransferMode,
+        policy: ConflictPolicy,
+    },
+    /// 跳过文件（已存在且相同，或按策略保留已有文件）
+    Skipped {
+        path: PathBuf,
+        reason: String,
+        policy: ConflictPolicy,
+    },
+    /// 重命名后传输
+    Renamed {
+        from: PathBuf,
+        to: PathBuf,
+        mode: TransferMode,
+        policy: ConflictPolicy,
+    },
+    /// 替换了目标位置上的已有文件
+    Replaced {
+        from: PathBuf,
+        to: PathBuf,
+        mode: TransferMode,
+        policy: ConflictPolicy,
+    },
+    /// 因冲突移到隔离目录
+    Quarantined {
+        from: PathBuf,
+        to: PathBuf,
+        mode: TransferMode,
+        policy: ConflictPolicy,
+    },
     /// 失败
     Failed { path: PathBuf, error: String },
 }
 
-/// 分类单个文件（使用配置）
-pub fn classify_file_with_config(
+/// 分类计划器
+///
+/// 只计算每个文件的去向而不修改磁盘；同一个计划器中先规划的文件
+/// 会占用其目标路径，后续文件据此预测冲突
+pub struct Planner<'a> {
+    config: &'a Config,
+    matcher: RuleMatcher<'a>,
+    target_dir: PathBuf,
+    /// 并行提取元数据的线程数（0 表示自动）
+    jobs: usize,
+    /// 取消或暂停批量处理
+    cancel: CancelToken,
+    /// 反向地理编码器，设置后展开 `{country}` `{region}` `{city}`
+    geocoder: Option<Geocoder>,
+    reservations: Reservations,
+}
+
+/// 批量规划时识别出的资产组
+#[derive(Default)]
+struct AssetGroups {
+    /// 成员 -> 主成员
+    primary_of: HashMap<PathBuf, PathBuf>,
+    /// 主成员 -> 其余成员
+    members: HashMap<PathBuf, Vec<PathBuf>>,
+}
+
+/// 需要按文件顺序串行更新的规划状态
+#[derive(Default)]
+struct Reservations {
+    /// 已被计划占用的目标路径 -> 计划写入该路径的源文件
+    reserved: HashMap<PathBuf, PathBuf>,
+    /// 目标库的内容索引，设置后跳过库中已有的文件
+    library: Option<LibraryIndex>,
+    /// 目标库的图片指纹索引，设置后相似图片移到复查目录
+    similar: Option<SimilarIndex>,
+    /// 文件名模板 `{counter}` 的每个目录已分配的最大编号
+    counters: HashMap<PathBuf, u32>,
+    /// 已随先前条目计划传输的附属文件 -> 其主文件
+    sidecars: HashMap<PathBuf, PathBuf>,
+}
+
+impl<'a> Planner<'a> {
+    pub fn new(config: &'a Config, target_dir: &Path) -> Self {
+        Self {
+            config,
+            matcher: RuleMatcher::new(config),
+            target_dir: target_dir.to_path_buf(),
+            jobs: config.global.jobs,
+            cancel: CancelToken::new(),
+            geocoder: None,
+            reservations: Reservations::default(),
+        }
+    }
+
+    /// 设置批量处理时的线程数（0 表示自动）
+    pub fn with_jobs(mut self, jobs: usize) -> Self {
+        self.jobs = jobs;
+        self
+    }
+
+    /// 使用取消标志，批量处理在文件之间响应暂停和取消，并中止进行中的复制
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 使用目标库的内容索引，内容已存在于库中任意位置的文件会被跳过
+    pub fn with_library_index(mut self, index: LibraryIndex) -> Self {
+        self.reservations.library = Some(index);
+        self
+    }
+
+    /// 使用目标库的图片指纹索引，与库中图片相似的图片会被移到复查目录
+    pub fn with_similar_index(mut self, index: SimilarIndex) -> Self {
+        self.reservations.similar = Some(index);
+        self
+    }
+
+    /// 使用反向地理编码器，按 GPS 坐标确定文件的国家、省/州和城市
+    pub fn with_geocoder(mut self, geocoder: Geocoder) -> Self {
+        self.geocoder = Some(geocoder);
+        self
+    }
+
+    /// 规划单个文件
+    pub fn plan_file(&mut self, source: &Path) -> Result<PlanEntry> {
+        if let Some(entry) = self.reservations.claimed_sidecar(source) {
+            return Ok(entry);
+        }
+        let entry = analyze_file(
+            self.config,
+            &self.matcher,
+            &self.target_dir,
+            source,
+            &BatchContext::default(),
+            self.geocoder.as_ref(),
+        )?;
+        let fingerprint = self
+            .reservations
+            .similar
+            .as_ref()
+            .and_then(|_| fingerprint_entry(&entry));
+        self.reservations
+            .reserve(&self.target_dir, entry, fingerprint)
+    }
+
+    /// 批量规划文件
+    ///
+    /// 元数据在工作线程中并行提取，冲突预测按输入顺序进行，
+    /// 结果与逐个调用 `plan_file` 完全一致（资产组、序列和事件除外，只在批量规划时识别），
+    /// 并按输入顺序交给 `on_entry`。请求取消后不再交出结果
+    pub fn plan_files<F>(&mut self, files: &[PathBuf], mut on_entry: F)
+    where
+        F: FnMut(&Path, Result<PlanEntry>),
+    {
+        // 冲突预测在调用线程中串行更新状态，其余字段在工作线程间共享
+        let mut reservations = std::mem::take(&mut self.reservations);
+        let this = &*self;
+        let similar = reservations.similar.is_some();
+        let groups = this.asset_groups(files);
+        let contexts = this.batch_contexts(files, &groups);
+        let no_context = BatchContext::default();
+        ordered_parallel(
+            files,
+            this.jobs,
+            Some(&this.cancel),
+            |source| {
+                if this.cancel.is_cancelled() {
+                    return (Err(Cancelled.into()), None);
+                }
+                // 资产组的其余成员随主成员传输，不单独分类
+                if let Some(primary) = groups.primary_of.get(source) {
+                    let reason = format!("Grouped with {}", primary.display());
+                    return (Ok(PlanEntry::skipped(source, reason)), None);
+                }
+                let context = contexts.get(source).unwrap_or(&no_context);
+                let entry = analyze_file(
+                    this.config,
+                    &this.matcher,
+                    &this.target_dir,
+                    source,
+                    context,
+                    this.geocoder.as_ref(),
+                )
+                .map(|entry| match groups.members.get(source) {
+                    Some(members) => attach_members(this.config, entry, members),
+                    None => entry,
+                });
+                let fingerprint = match &entry {
+                    Ok(entry) if similar => fingerprint_entry(entry),
+                    _ => None,
+                };
+                (entry, fingerprint)
+            },
+            |source, (entry, fingerprint)| {
+                if this.cancel.is_cancelled() {
+                    return;
+                }
+                // 先前的主文件可能已经带走了该文件，此时分析结果无效
+                let entry = match reservations.claimed_sidecar(source) {
+                    Some(entry) => Ok(entry),
+                    None => {
+                        entry.and_then(|e| reservations.reserve(&this.target_dir, e, fingerprint))
+                    },
+                };
+                on_entry(source, entry)
+            },
+        );
+        self.reservations = reservations;
+    }
+
+    /// 在输入文件中识别资产组，主成员须有匹配的规则
+    fn asset_groups(&self, files: &[PathBuf]) -> AssetGroups {
+        let mut groups = AssetGroups::default();
+        let config = self.config;
+        if !config.asset_groups.enabled {
+            return groups;
+        }
+        let sidecar_extensions: &[String] = if config.sidecars.enabled {
+            &config.sidecars.extensions
+        } else {
+            &[]
+        };
+        // 与逐个分析时相同地考虑规则限定的地点
+        let has_rule = |path: &Path| {
+            let (Some(info), Ok(metadata)) = (get_media_info(path), std::fs::metadata(path)) else {
+                return false;
+            };
+            let is_image = info.media_type == MediaType::Image;
+            let position = || is_image.then(|| read_position(path)).flatten();
+            match_rule(
+                config,
+                &self.matcher,
+                &info.extension,
+                metadata.len(),
+                position,
+            )
+            .is_some()
+        };
+        let groups_found = find_asset_groups(
+            files,
+            &config.asset_groups,
+            sidecar_extensions,
+            extract_content_identifier,
+            has_rule,
+        );
+        for group in groups_found {
+            for member in &group.members {
+                groups
+                    .primary_of
+                    .insert(member.clone(), group.primary.clone());
+            }
+            groups.members.insert(group.primary, group.members);
+        }
+        groups
+    }
+
+    /// 识别输入文件所属的序列和事件，资产组的其余成员跟随主成员，不参与识别
+    fn batch_contexts(
+        &self,
+        files: &[PathBuf],
+        groups: &AssetGroups,
+    ) -> HashMap<PathBuf, BatchContext> {
+        let mut contexts: HashMap<PathBuf, BatchContext> = HashMap::new();
+        let (use_sequences, use_events) = (self.config.uses_sequences(), self.config.uses_events());
+        if !use_sequences && !use_events {
+            return contexts;
+        }
+        let files: Vec<PathBuf> = files
+            .iter()
+            .filter(|file| !groups.primary_of.contains_key(*file))
+            .cloned()
+            .collect();
+
+        if use_sequences {
+            for (file, sequence) in self.sequences(&files) {
+                contexts.entry(file).or_default().sequence = Some(sequence);
+            }
+        }
+        if use_events {
+            for (file, event) in self.events(&files) {
+                contexts.entry(file).or_default().event = Some(event);
+            }
+        }
+        contexts
+    }
+
+    /// 识别连拍、包围曝光和分段视频序列
+    fn sequences(&self, files: &[PathBuf]) -> HashMap<PathBuf, SequenceRef> {
+        // 并行读取图片的拍摄时间和曝光补偿
+        let mut captures = HashMap::new();
+        ordered_parallel(
+            files,
+            self.jobs,
+            Some(&self.cancel),
+            |file| match get_media_info(file) {
+                Some(info) if info.media_type == MediaType::Image => {
+                    extract_capture_info(file).ok()
+                },
+                _ => None,
+            },
+            |file, capture| {
+                if let Some(capture) = capture {
+                    captures.insert(file.clone(), capture);
+                }
+            },
+        );
+
+        let mut result = HashMap::new();
+        for sequence in find_sequences(files, &self.config.sequences, |file| {
+            captures.get(file).copied()
+        }) {
+            let first = &sequence.files[0];
+            let is_image = captures.contains_key(first);
+            let date = extract_date(first, is_image)
+                .inspect_err(|e| warn!("Failed to extract date from {:?}: {:#}", first, e))
+                .ok();
+            info!(
+                "Detected {} sequence {} ({} files)",
+                sequence.kind.as_str(),
+                sequence.name,
+                sequence.files.len()
+            );
+            let sequence_ref = SequenceRef {
+                name: sequence.name,
+                date,
+            };
+            for file in sequence.files {
+                result.insert(file, sequence_ref.clone());
+            }
+        }
+        result
+    }
+
+    /// 按拍摄时间间隔（和地点）把文件聚类为事件
+    fn events(&self, files: &[PathBuf]) -> HashMap<PathBuf, EventSpan> {
+        let config = &self.config.events;
+        let mut items: Vec<EventItem> = Vec::new();
+        ordered_parallel(
+            files,
+            self.jobs,
+            Some(&self.cancel),
+            |file| {
+                let is_image = get_media_info(file)?.media_type == MediaType::Image;
+                let date = extract_date(file, is_image).ok()?;
+                let position = (is_image && config.max_distance_km.is_some())
+                    .then(|| extract_gps(file).ok())
+                    .flatten();
+                Some((date, position))
+            },
+            |file, item| {
+                if let Some((date, position)) = item {
+                    items.push((file.clone(), date, position));
+                }
+            },
+        );
+
+        let mut result = HashMap::new();
+        for event in cluster_events(&items, config) {
+            info!(
+                "Detected event {} ({} files)",
+                event.span.name(),
+                event.files.len()
+            );
+            for file in event.files {
+                result.insert(file, event.span);
+            }
+        }
+        result
+    }
+
+    /// 批量分类文件
+    ///
+    /// 与 `plan_files` 相同地并行提取元数据，之后在调用线程中按输入顺序
+    /// 依次解决冲突并传输文件，因此目标命名和结果顺序与串行执行一致
+    pub fn classify_files<F>(
+        &mut self,
+        files: &[PathBuf],
+        mut journal: Option<&mut Journal>,
+        mut on_result: F,
+    ) where
+        F: FnMut(&Path, Result<ClassifyResult>),
+    {
+        let cancel = self.cancel.clone();
+        self.plan_files(files, |source, entry| {
+            if !cancel.wait_while_paused() {
+                return;
+            }
+            let result = entry.and_then(|entry| execute(&entry, journal.as_deref_mut(), &cancel));
+            // 被中止的传输没有改变任何文件，不作为结果报告
+            if !is_cancelled(&result) {
+                on_result(source, result);
+            }
+        });
+    }
+
+    /// 批量分类文件并通知观察者
+    ///
+    /// 分类完成（或被取消）后清理 `clean_dirs` 下的空目录（记录到运行日志），
+    /// 最后发送运行结束事件；被取消时返回的进度只包含已处理的文件
+    pub fn run(
+        &mut self,
+        files: &[PathBuf],
+        mut journal: Option<&mut Journal>,
+        clean_dirs: &[PathBuf],
+        observer: &mut dyn Observer,
+    ) -> Result<Progress> {
+        let sizes: HashMap<&Path, u64> = files
+            .iter()
+            .map(|file| (file.as_path(), file_size(file)))
+            .collect();
+        let mut progress = Progress::new(files.len(), sizes.values().sum());
+
+        let cancel = self.cancel.clone();
+        self.plan_files(files, |source, entry| {
+            if !cancel.wait_while_paused() {
+                return;
+            }
+            let bytes = entry.as_ref().map_or(0, transfer_size);
+            let result = entry.and_then(|entry| execute(&entry, journal.as_deref_mut(), &cancel));
+            if !is_cancelled(&result) {
+                let size = sizes.get(source).copied().unwrap_or(0);
+                report(observer, &mut progress, source, size, bytes, &result);
+            }
+        });
+
+        progress.cancelled = cancel.is_cancelled();
+        finish_run(clean_dirs, journal, &progress, observer)?;
+        Ok(progress)
+    }
+}
+
+/// 按顺序执行计划条目并通知观察者，跳过自生成计划后已变化的源文件
+///
+/// 失效条目计入已处理的文件，但不计入结果统计，与失效原因一起返回。
+/// 在条目之间响应 `cancel` 的暂停和取消，执行完成（或被取消）后与
+/// [`Planner::run`] 相同地清理空目录并发送运行结束事件
+pub fn run_plan(
+    entries: &[&PlanEntry],
+    mut journal: Option<&mut Journal>,
+    clean_dirs: &[PathBuf],
+    cancel: &CancelToken,
+    observer: &mut dyn Observer,
+) -> Result<(Progress, Vec<(PathBuf, String)>)> {
+    let bytes_total = entries.iter().filter_map(|entry| entry.size).sum();
+    let mut progress = Progress::new(entries.len(), bytes_total);
+    let mut stale = Vec::new();
+
+    for entry in entries {
+        if !cancel.wait_while_paused() {
+            break;
+        }
+        let size = entry.size.unwrap_or(0);
+        if entry.action.transfers()
+            && let Some(reason) = entry.stale_reason()
+        {
+            info!("[STALE] {} | Reason: {}", entry.source.display(), reason);
+            progress.advance(size);
+            stale.push((entry.source.clone(), reason));
+            continue;
+        }
+
+        let bytes = transfer_size(entry);
+        let result = execute(entry, journal.as_deref_mut(), cancel);
+        if is_cancelled(&result) {
+            break;
+        }
+        report(observer, &mut progress, &entry.source, size, bytes, &result);
+    }
+
+    progress.cancelled = cancel.is_cancelled();
+    finish_run(clean_dirs, journal, &progress, observer)?;
+    Ok((progress, stale))
+}
+
+/// 结果是否为被取消而中止的传输
+fn is_cancelled<T>(result: &Result<T>) -> bool {
+    result.as_ref().is_err_and(Cancelled::is_cause_of)
+}
+
+fn file_size(path: &Path) -> u64 {
+    std::fs::metadata(path).map_or(0, |m| m.len())
+}
+
+/// 条目将传输的字节数，包括附属文件和组成员；须在传输前计算
+fn transfer_size(entry: &PlanEntry) -> u64 {
+    if !entry.action.transfers() {
+        return 0;
+    }
+    let sidecars = entry
+        .sidecars
+        .iter()
+        .chain(entry.members.iter().flat_map(|m| &m.sidecars))
+        .map(|sidecar| file_size(&sidecar.source));
+    let members = entry.members.iter().map(|member| file_size(&member.source));
+    entry.size.unwrap_or(0) + sidecars.chain(members).sum::<u64>()
+}
+
+/// 更新进度并发送传输和分类事件
+fn report(
+    observer: &mut dyn Observer,
+    progress: &mut Progress,
+    source: &Path,
+    size: u64,
+    bytes: u64,
+    result: &Result<ClassifyResult>,
+) {
+    progress.record(size, result);
+    let transferred = matches!(
+        result,
+        Ok(ClassifyResult::Success { .. }
+            | ClassifyResult::Renamed { .. }
+            | ClassifyResult::Replaced { .. }
+            | ClassifyResult::Quarantined { .. })
+    );
+    if transferred {
+        progress.bytes_transferred += bytes;
+        observer.bytes_transferred(source, bytes, progress);
+    }
+    observer.file_classified(source, result, progress);
+}
+
+/// 清理空目录并记录到运行日志，然后发送运行结束事件
+fn finish_run(
+    clean_dirs: &[PathBuf],
+    mut journal: Option<&mut Journal>,
+    progress: &Progress,
+    observer: &mut dyn Observer,
+) -> Result<()> {
+    for dir in clean_dirs {
+        let removed = remove_empty_dirs(dir, observer)?;
+        if let Some(journal) = journal.as_deref_mut() {
+            journal.record_removed_dirs(&removed)?;
+        }
+    }
+    observer.run_finished(progress);
+    Ok(())
+}
+
+/// 分析单个文件：匹配规则、提取日期并构建（未解决冲突的）目标路径
+///
+/// 不依赖其他文件的状态，可以在多个线程中并行执行
+fn analyze_file(
     config: &Config,
+    matcher: &RuleMatcher,
     target_dir: &Path,
     source: &Path,
-) -> Result<ClassifyResult> {
-    let matcher = RuleMatcher::new(config);
-
+    context: &BatchContext,
+    geocoder: Option<&Geocoder>,
+) -> Result<PlanEntry> {
     // 1. 获取媒体信息
-    let media_info = match get_media_info(source) {
+    let mut media_info = match get_media_info(source) {
         Some(info) => info,
-        None => {
-            return Ok(ClassifyResult::Failed {
-                path: source.to_path_buf(),
-                error: "Not a media file".to_string(),
-            });
-        },
+        None => return Ok(PlanEntry::failed(source, "Not a media file".to_string())),
     };
+    // 附属文件（如相机的 WAV 语音备注）随主文件传输，不单独分类
+    if config.sidecars.enabled
+        && let Some(primary) = find_primary(source, &config.sidecars.extensions)
+    {
+        return Ok(PlanEntry::skipped(
+            source,
+            format!("Sidecar of {}", primary.display()),
+        ));
+    }
 
-    // 2. 获取文件大小
-    let file_size = std::fs::metadata(source)
-        .context("Failed to get file metadata")?
-        .len();
+    // 2. 获取文件大小和修改时间
+    let metadata = std::fs::metadata(source).context("Failed to get file metadata")?;
+    let file_size = metadata.len();
+    let modified = metadata.modified().ok().map(format_modified);
+
+    // 图片的 GPS 坐标在规则限定了地点或模板使用地点变量时才读取，且只读取一次
+    let is_image = media_info.media_type == MediaType::Image;
+    let gps = OnceCell::new();
+    let position = || *gps.get_or_init(|| is_image.then(|| read_position(source)).flatten());
 
     // 3. 匹配规则
-    let matched_rule = match matcher.match_file(&media_info.extension, file_size) {
+    let matched_rule = match match_rule(config, matcher, &media_info.extension, file_size, position)
+    {
         Some(rule) => rule,
         None => {
             info!("No rule matched for {:?}", source);
-            return Ok(ClassifyResult::Failed {
-                path: source.to_path_buf(),
-                error: "No matching rule found".to_string(),
-            });
+            return Ok(PlanEntry::failed(
+                source,
+                "No matching rule found".to_string(),
+            ));
         },
     };
 
     // 4. 提取日期（如果规则需要）
-    let date = if matched_rule.date_format.is_some() {
-        let is_image = media_info.media_type == MediaType::Image;
-        match extract_date(source, is_image) {
-            Ok(d) => Some(d),
+    let (date, date_source) = if matcher.needs_date(matched_rule) {
+        match extract_date_with_source(source, is_image) {
+            Ok((d, s)) => (Some(d), Some(s)),
             Err(e) => {
                 error!("Failed to extract date from {:?}: {}", source, e);
-                return Ok(ClassifyResult::Failed {
-                    path: source.to_path_buf(),
-                    error: format!("Failed to extract date: {}", e),
-                });
+                let mut entry = PlanEntry::failed(source, format!("Failed to extract date: {}", e));
+                entry.rule = Some(matched_rule.name.clone());
+                return Ok(entry);
             },
         }
     } else {
-        None
+        (None, None)
     };
 
-    // 5. 构建目标路径
-    let target =
-        matcher.build_target_path(target_dir, source, &media_info, date.as_ref(), matched_rule)?;
+    // 5. 读取音频标签、相机信息或拍摄地点（如果规则需要）
+    if matcher.needs_location(matched_rule)
+        && let Some(position) = position()
+    {
+        let mut location = match geocoder {
+            Some(geocoder) => geocoder.locate(position),
+            None => Location {
+                position: Some(position),
+                ..Location::default()
+            },
+        };
+        location.place = find_place(&config.places, &position).map(|place| place.name.clone());
+        media_info.location = Some(location);
+    }
+    match media_info.media_type {
+        MediaType::Audio if matcher.needs_tags(matched_rule) => match read_audio_tags(source) {
+            Ok(tags) => media_info.tags = Some(tags),
+            Err(e) => debug!("No audio tags in {:?}: {}", source, e),
+        },
+        MediaType::Image if matcher.needs_camera(matched_rule) => {
+            match extract_exif_metadata(source) {
+                Ok(exif) => media_info.exif = Some(exif),
+                Err(e) => debug!("No EXIF metadata in {:?}: {}", source, e),
+            }
+        },
+        _ => {},
+    }
 
-    // 6. 解决冲突
-    match resolve_conflict(source, &target)? {
-        ConflictResolution::NoConflict(final_target) => {
-            // 无冲突，直接移动
-            move_file(source, &final_target)?;
-            info!("Successfully moved: {:?} → {:?}", source, final_target);
-            Ok(ClassifyResult::Success {
-                from: source.to_path_buf(),
-                to: final_target,
-            })
+    // 6. 构建目标路径
+    let target = matcher.build_target_path(
+        target_dir,
+        source,
+        &media_info,
+        date.as_ref(),
+        matched_rule,
+        context,
+    )?;
+    let sidecars = sidecar_entries(config, source, &target);
+
+    // 7. 冲突处理策略
+    let conflict = matched_rule.effective_conflict_policy(&config.global);
+    let quarantine = (conflict == ConflictPolicy::Quarantine).then(|| {
+        let dir = target_dir.join(&config.global.quarantine_dir);
+        relocate(target_dir, &dir, &target)
+    });
+
+    Ok(PlanEntry {
+        source: source.to_path_buf(),
+        size: Some(file_size),
+        modified,
+        rule: Some(matched_rule.name.clone()),
+        date: date.map(|d| d.to_rfc3339()),
+        date_source,
+        target: Some(target),
+        action: PlannedAction::Move,
+        transfer_mode: matched_rule.effective_transfer_mode(&config.global),
+        compare: config.global.compare,
+        conflict,
+        quarantine,
+        collision_suffix: config.global.collision_suffix.clone(),
+        sidecars,
+        members: Vec::new(),
+        sequence: context.sequence.as_ref().map(|s| s.name.clone()),
+        event: context.event.map(|e| e.name()),
+    })
+}
+
+/// 按扩展名、大小和拍摄地点匹配规则，遇到限定了地点的规则时才通过 `position` 读取坐标
+fn match_rule<'a>(
+    config: &Config,
+    matcher: &'a RuleMatcher,
+    extension: &str,
+    file_size: u64,
+    position: impl FnOnce() -> Option<GpsPosition>,
+) -> Option<&'a Rule> {
+    let place_of = || {
+        position()
+            .and_then(|p| find_place(&config.places, &p))
+            .map(|place| place.name.as_str())
+    };
+    matcher.match_file_at(extension, file_size, place_of)
+}
+
+/// 读取 EXIF 中的 GPS 坐标，没有时返回 None
+fn read_position(source: &Path) -> Option<GpsPosition> {
+    extract_gps(source)
+        .map_err(|e| debug!("No GPS position in {:?}: {}", source, e))
+        .ok()
+}
+
+/// 查找文件的附属文件，目标路径跟随文件的目标路径 `target`
+fn sidecar_entries(config: &Config, source: &Path, target: &Path) -> Vec<Sidecar> {
+    if !config.sidecars.enabled {
+        return Vec::new();
+    }
+    find_sidecars(source, &config.sidecars.extensions)
+        .into_iter()
+        .map(|path| Sidecar {
+            target: sidecar_target(source, &path, target),
+            source: path,
+        })
+        .collect()
+}
+
+/// 为资产组的主成员附加其余成员及其附属文件，目标路径跟随主成员
+///
+/// 多个成员共有的附属文件（如 RAW+JPEG 共用的 `.xmp`）只随第一个成员传输
+fn attach_members(config: &Config, mut entry: PlanEntry, members: &[PathBuf]) -> PlanEntry {
+    let (PlannedAction::Move, Some(target)) = (&entry.action, entry.target.clone()) else {
+        return entry;
+    };
+    for member in members {
+        let mut member = GroupMember::new(
+            &entry.source,
+            member,
+            &target,
+            &config.asset_groups.edited_suffixes,
+        );
+        member.sidecars = sidecar_entries(config, &member.source, &member.target);
+        member.sidecars.retain(|sidecar| {
+            !entry
+                .sidecars
+                .iter()
+                .chain(entry.members.iter().flat_map(|m| &m.sidecars))
+                .any(|other| other.source == sidecar.source)
+        });
+        entry.members.push(member);
+    }
+    entry
+}
+
+/// 计算待归档图片的感知指纹，不支持或解码失败时返回 None
+fn fingerprint_entry(entry: &PlanEntry) -> Option<ImageFingerprint> {
+    if entry.action != PlannedAction::Move || !supports_fingerprint(&entry.source) {
+        return None;
+    }
+    fingerprint_image(&entry.source)
+        .inspect_err(|e| warn!("Failed to fingerprint {:?}: {:#}", entry.source, e))
+        .ok()
+}
+
+impl Reservations {
+    /// 文件已作为先前条目的附属文件计划传输时返回跳过条目
+    fn claimed_sidecar(&self, source: &Path) -> Option<PlanEntry> {
+        let primary = self.sidecars.get(source)?;
+        Some(PlanEntry::skipped(
+            source,
+            format!("Sidecar of {}", primary.display()),
+        ))
+    }
+
+    /// 预测冲突并占用目标路径，必须按文件顺序串行调用
+    fn reserve(
+        &mut self,
+        target_dir: &Path,
+        mut entry: PlanEntry,
+        fingerprint: Option<ImageFingerprint>,
+    ) -> Result<PlanEntry> {
+        let mut target = match (&entry.action, &entry.target) {
+            (PlannedAction::Move, Some(target)) => target.clone(),
+            _ => return Ok(entry),
+        };
+
+        // 7. 库中已有相同内容的文件时跳过
+        if let Some(library) = self.library.as_mut()
+            && let Some(existing) = library.find_duplicate(&entry.source)?
+        {
+            entry.action = PlannedAction::Skip {
+                reason: format!("Duplicate of library file: {}", existing.display()),
+            };
+            return Ok(entry);
+        }
+
+        // 8. 为文件名模板中的 `{counter}` 分配目录内的下一个编号
+        if target
+            .file_name()
+            .and_then(|name| name.to_str())
+            .is_some_and(|name| has_number_var(name, "counter"))
+        {
+            target = self.next_counter(&target);
+        }
+
+        // 9. 与库中图片相似时改为移到复查目录
+        let mut review = false;
+        if let (Some(similar), Some(fingerprint)) = (&self.similar, &fingerprint)
+            && let Some((existing, similarity)) = similar.find_similar(fingerprint)
+        {
+            info!(
+                "{:?} is {}% similar to {:?}, routing to review",
+                entry.source, similarity, existing
+            );
+            target = similar.review_target(target_dir, &target);
+            review = true;
+        }
+
+        // 10. 预测冲突
+        let resolution = resolve_conflict_with_reserved(
+            &entry.source,
+            &target,
+            &self.reserved,
+            entry.compare,
+            entry.conflict,
+            &entry.collision_suffix,
+        )?;
+        let (target, action) = match resolution {
+            ConflictResolution::NoConflict(final_target) => (final_target, PlannedAction::Move),
+            ConflictResolution::Rename(new_target) => (new_target, PlannedAction::Rename),
+            ConflictResolution::Replace(target) => (target, PlannedAction::Replace),
+            ConflictResolution::Skip(reason) => (target, PlannedAction::Skip { reason }),
+            ConflictResolution::Quarantine(reason) => {
+                info!("Quarantining {:?}: {}", entry.source, reason);
+                match &entry.quarantine {
+                    Some(path) => {
+                        match resolve_conflict_with_reserved(
+                            &entry.source,
+                            path,
+                            &self.reserved,
+                            entry.compare,
+                            ConflictPolicy::KeepBoth,
+                            &entry.collision_suffix,
+                        )? {
+                            ConflictResolution::Skip(reason) => {
+                                (path.clone(), PlannedAction::Skip { reason })
+                            },
+                            resolution => (resolved_target(resolution)?, PlannedAction::Quarantine),
+                        }
+                    },
+                    None => {
+                        let error = "No quarantine path for conflicting file".to_string();
+                        (target, PlannedAction::Fail { error })
+                    },
+                }
+            },
+        };
+
+        if action.transfers() {
+            self.reserved.insert(target.clone(), entry.source.clone());
+            if let Some(library) = self.library.as_mut() {
+                library.add_planned(&target, &entry.source, entry.size.unwrap_or_default());
+            }
+            // 复查和隔离目录中的图片不作为后续比较的原图
+            if let (Some(similar), Some(fingerprint), false) = (
+                self.similar.as_mut(),
+                fingerprint,
+                review || action == PlannedAction::Quarantine,
+            ) {
+                similar.add_planned(&target, fingerprint);
+            }
+        }
+
+        // 附属文件和组成员跟随主文件的最终目标路径，主文件不传输时留在原处；
+        // 已随先前条目计划传输的附属文件不再重复传输
+        if action.transfers() {
+            let claimed = &mut self.sidecars;
+            let mut claim = |owner: &Path, owner_target: &Path, sidecars: &mut Vec<Sidecar>| {
+                sidecars.retain(|sidecar| !claimed.contains_key(&sidecar.source));
+                for sidecar in sidecars {
+                    sidecar.target = sidecar_target(owner, &sidecar.source, owner_target);
+                    claimed.insert(sidecar.source.clone(), entry.source.clone());
+                }
+            };
+            claim(&entry.source, &target, &mut entry.sidecars);
+            // 成员按执行时的策略预测冲突：主成员替换已有文件时成员也替换，否则保留两者
+            let policy = if action == PlannedAction::Replace {
+                ConflictPolicy::Overwrite
+            } else {
+                ConflictPolicy::KeepBoth
+            };
+            for member in &mut entry.members {
+                member.target = member.target_for(&target);
+                match resolve_conflict_with_reserved(
+                    &member.source,
+                    &member.target,
+                    &self.reserved,
+                    entry.compare,
+                    policy,
+                    &entry.collision_suffix,
+                )? {
+                    ConflictResolution::Skip(reason) => {
+                        member.skip = Some(reason);
+                        member.sidecars.clear();
+                        continue;
+                    },
+                    ConflictResolution::Replace(to) => member.target = to,
+                    resolution => member.target = resolved_target(resolution)?,
+                }
+                claim(&member.source, &member.target, &mut member.sidecars);
+                self.reserved
+                    .insert(member.target.clone(), member.source.clone());
+            }
+        } else {
+            entry.sidecars.clear();
+            entry.members.clear();
+        }
+
+        entry.target = Some(target);
+        entry.action = action;
+        Ok(entry)
+    }
+
+    /// 展开目标文件名中的 `{counter}`，跳过磁盘上已存在或已被计划占用的编号
+    fn next_counter(&mut self, target: &Path) -> PathBuf {
+        let parent = target.parent().unwrap_or(Path::new(""));
+        let name = target
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let counter = self.counters.entry(parent.to_path_buf()).or_default();
+        loop {
+            *counter += 1;
+            let candidate = parent.join(expand_number(name, "counter", *counter));
+            if !candidate.exists() && !self.reserved.contains_key(&candidate) {
+                return candidate;
+            }
+        }
+    }
+}
+
+/// 执行计划条目
+///
+/// 执行时会针对磁盘的实际状态重新解决冲突，因此计划生成后出现的
+/// 同名文件不会被覆盖
+pub fn execute_plan_entry(entry: &PlanEntry) -> Result<ClassifyResult> {
+    execute(entry, None, &CancelToken::new())
+}
+
+/// 执行计划条目，每次传输前后写入运行日志
+pub fn execute_plan_entry_with_journal(
+    entry: &PlanEntry,
+    journal: &mut Journal,
+) -> Result<ClassifyResult> {
+    execute(entry, Some(journal), &CancelToken::new())
+}
+
+/// 执行计划条目；`cancel` 只中止主文件的传输，主文件完成后附属文件和组成员总会随之传输
+fn execute(
+    entry: &PlanEntry,
+    mut journal: Option<&mut Journal>,
+    cancel: &CancelToken,
+) -> Result<ClassifyResult> {
+    let source = entry.source.as_path();
+    let policy = entry.conflict;
+    let target = match (&entry.action, &entry.target) {
+        (PlannedAction::Fail { error }, _) => {
+            return Ok(ClassifyResult::Failed {
+                path: source.to_path_buf(),
+                error: error.clone(),
+            });
+        },
+        (PlannedAction::Skip { reason }, _) => {
+            info!("Skipped: {:?} - {}", source, reason);
+            return Ok(ClassifyResult::Skipped {
+                path: source.to_path_buf(),
+                reason: reason.clone(),
+                policy,
+            });
+        },
+        (_, Some(target)) => target,
+        (_, None) => {
+            return Ok(ClassifyResult::Failed {
+                path: source.to_path_buf(),
+                error: "Plan entry has no target".to_string(),
+            });
         },
+    };
+
+    // 已计划隔离的文件在隔离目录中只需避免覆盖
+    let mut quarantined = entry.action == PlannedAction::Quarantine;
+    let target_policy = if quarantined {
+        ConflictPolicy::KeepBoth
+    } else {
+        policy
+    };
+    let suffix = entry.collision_suffix.as_str();
+    let mut resolution = resolve_conflict(source, target, entry.compare, target_policy, suffix)?;
+    if let ConflictResolution::Quarantine(reason) = &resolution {
+        let Some(path) = &entry.quarantine else {
+            return Ok(ClassifyResult::Failed {
+                path: source.to_path_buf(),
+                error: "No quarantine path for conflicting file".to_string(),
+            });
+        };
+        info!("Quarantining {:?}: {}", source, reason);
+        resolution = resolve_conflict(
+            source,
+            path,
+            entry.compare,
+            ConflictPolicy::KeepBoth,
+            suffix,
+        )?;
+        quarantined = true;
+    }
+
+    let mode = entry.transfer_mode;
+    let from = source.to_path_buf();
+    let renamed = entry.action == PlannedAction::Rename;
+    match resolution {
         ConflictResolution::Skip(reason) => {
-            // 文件相同，跳过
+            // 文件相同或按策略保留已有文件，跳过
             info!("Skipped: {:?} - {}", source, reason);
             Ok(ClassifyResult::Skipped {
-                path: source.to_path_buf(),
+                path: from,
                 reason,
+                policy,
             })
         },
-        ConflictResolution::Rename(new_target) => {
-            // 文件不同，重命名后移动
-            move_file(source, &new_target)?;
+        ConflictResolution::Replace(to) => {
+            // 按策略替换已有文件
+            replace_file(source, &to, mode, journal.as_deref_mut(), cancel)?;
+            transfer_companions(entry, &to, true, journal);
             warn!(
-                "File renamed due to conflict: {:?} → {:?}",
-                source, new_target
+                "Replaced existing file ({}, {}): {:?} → {:?}",
+                policy.as_str(),
+                mode.as_str(),
+                source,
+                to
+            );
+            Ok(ClassifyResult::Replaced {
+                from,
+                to,
+                mode,
+                policy,
+            })
+        },
+        ConflictResolution::NoConflict(to) if quarantined => {
+            journaled_transfer_with_cancel(source, &to, mode, journal.as_deref_mut(), cancel)?;
+            transfer_companions(entry, &to, false, journal);
+            warn!(
+                "File quarantined due to conflict ({}): {:?} → {:?}",
+                mode.as_str(),
+                source,
+                to
+            );
+            Ok(ClassifyResult::Quarantined {
+                from,
+                to,
+                mode,
+                policy,
+            })
+        },
+        ConflictResolution::NoConflict(to) if !renamed => {
+            // 无冲突，直接传输
+            journaled_transfer_with_cancel(source, &to, mode, journal.as_deref_mut(), cancel)?;
+            transfer_companions(entry, &to, false, journal);
+            info!(
+                "Successfully transferred ({}): {:?} → {:?}",
+                mode.as_str(),
+                source,
+                to
+            );
+            Ok(ClassifyResult::Success {
+                from,
+                to,
+                mode,
+                policy,
+            })
+        },
+        resolution => {
+            // 文件不同，重命名后传输（隔离目录中的同名文件同样重命名）
+            let to = resolved_target(resolution)?;
+            journaled_transfer_with_cancel(source, &to, mode, journal.as_deref_mut(), cancel)?;
+            transfer_companions(entry, &to, false, journal);
+            if quarantined {
+                warn!(
+                    "File quarantined due to conflict ({}): {:?} → {:?}",
+                    mode.as_str(),
+                    source,
+                    to
+                );
+                return Ok(ClassifyResult::Quarantined {
+                    from,
+                    to,
+                    mode,
+                    policy,
+                });
+            }
+            warn!(
+                "File renamed due to conflict ({}): {:?} → {:?}",
+                mode.as_str(),
+                source,
+                to
             );
             Ok(ClassifyResult::Renamed {
-                from: source.to_path_buf(),
-                to: new_target,
+                from,
+                to,
+                mode,
+                policy,
             })
         },
     }
 }
 
+/// 主文件传输到 `to` 之后，将附属文件和资产组成员传输到主文件旁边
+///
+/// 与目标位置已有的同名文件内容相同时留在原处；内容不同时，主文件替换了
+/// 已有文件则同样替换，否则添加重名后缀。
+/// 这些文件传输失败只记录警告，不影响主文件的结果
+fn transfer_companions(
+    entry: &PlanEntry,
+    to: &Path,
+    replaced: bool,
+    mut journal: Option<&mut Journal>,
+) {
+    transfer_sidecars(
+        entry,
+        &entry.source,
+        &entry.sidecars,
+        to,
+        replaced,
+        journal.as_deref_mut(),
+    );
+    // 主文件落在计划的目标路径时，成员使用计划中已解决冲突的目标路径
+    let planned = entry.target.as_deref() == Some(to);
+    for member in &entry.members {
+        let target = if planned {
+            member.target.clone()
+        } else {
+            member.target_for(to)
+        };
+        match transfer_companion(
+            entry,
+            &member.source,
+            &target,
+            replaced,
+            journal.as_deref_mut(),
+        ) {
+            Ok(Some(member_to)) => transfer_sidecars(
+                entry,
+                &member.source,
+                &member.sidecars,
+                &member_to,
+                replaced,
+                journal.as_deref_mut(),
+            ),
+            Ok(None) => {},
+            Err(e) => warn!(
+                "Failed to transfer group member {:?}: {:#}",
+                member.source, e
+            ),
+        }
+    }
+}
+
+/// 将 `owner` 的附属文件传输到 `owner_to` 旁边
+fn transfer_sidecars(
+    entry: &PlanEntry,
+    owner: &Path,
+    sidecars: &[Sidecar],
+    owner_to: &Path,
+    replaced: bool,
+    mut journal: Option<&mut Journal>,
+) {
+    for sidecar in sidecars {
+        let target = sidecar_target(owner, &sidecar.source, owner_to);
+        if let Err(e) = transfer_companion(
+            entry,
+            &sidecar.source,
+            &target,
+            replaced,
+            journal.as_deref_mut(),
+        ) {
+            warn!("Failed to transfer sidecar {:?}: {:#}", sidecar.source, e);
+        }
+    }
+}
+
+/// 传输随主文件移动的文件，返回最终路径，内容相同而跳过时返回 None
+fn transfer_companion(
+    entry: &PlanEntry,
+    source: &Path,
+    target: &Path,
+    replaced: bool,
+    journal: Option<&mut Journal>,
+) -> Result<Option<PathBuf>> {
+    let policy = if replaced {
+        ConflictPolicy::Overwrite
+    } else {
+        ConflictPolicy::KeepBoth
+    };
+    let mode = entry.transfer_mode;
+    let to = match resolve_conflict(
+        source,
+        target,
+        entry.compare,
+        policy,
+        &entry.collision_suffix,
+    )? {
+        ConflictResolution::Skip(reason) => {
+            info!("Skipped: {:?} - {}", source, reason);
+            return Ok(None);
+        },
+        ConflictResolution::Replace(to) => {
+            replace_file(source, &to, mode, journal, &CancelToken::new())?;
+            info!(
+                "Replaced with {:?} ({}): {:?} → {:?}",
+                entry.source,
+                mode.as_str(),
+                source,
+                to
+            );
+            return Ok(Some(to));
+        },
+        resolution => resolved_target(resolution)?,
+    };
+    journaled_transfer(source, &to, mode, journal)?;
+    info!(
+        "Transferred with {:?} ({}): {:?} → {:?}",
+        entry.source,
+        mode.as_str(),
+        source,
+        to
+    );
+    Ok(Some(to))
+}
+
+/// 取出可以直接写入的目标路径
+fn resolved_target(resolution: ConflictResolution) -> Result<PathBuf> {
+    match resolution {
+        ConflictResolution::NoConflict(target) | ConflictResolution::Rename(target) => Ok(target),
+        other => anyhow::bail!("Unexpected conflict resolution: {:?}", other),
+    }
+}
+
+/// 用 `source` 替换 `target` 处的已有文件
+///
+/// 已有文件先移到一旁，传输失败或被取消时移回原位，不会丢失。
+/// 有运行日志时移到本次运行的备份目录并记录为普通传输，撤销时会被
+/// 移回原位置；没有运行日志时移到同目录下的临时文件，传输成功后才删除
+fn replace_file(
+    source: &Path,
+    target: &Path,
+    mode: TransferMode,
+    journal: Option<&mut Journal>,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let Some(journal) = journal else {
+        let aside = aside_path(target);
+        std::fs::rename(target, &aside)
+            .context(format!("Failed to move aside replaced file: {:?}", target))?;
+        return match transfer_file_with_cancel(source, target, mode, cancel) {
+            Ok(()) => {
+                if let Err(e) = std::fs::remove_file(&aside) {
+                    warn!("Failed to remove replaced file {:?}: {}", aside, e);
+                }
+                Ok(())
+            },
+            Err(e) => {
+                restore_replaced(&aside, target, |from, to| {
+                    std::fs::rename(from, to).map_err(Into::into)
+                });
+                Err(e)
+            },
+        };
+    };
+
+    let backup = journal.backup_path(target);
+    info!("Backing up replaced file: {:?} → {:?}", target, backup);
+    journaled_transfer(target, &backup, TransferMode::Move, Some(&mut *journal))?;
+    match journaled_transfer_with_cancel(source, target, mode, Some(&mut *journal), cancel) {
+        Ok(()) => Ok(()),
+        Err(e) => {
+            restore_replaced(&backup, target, |from, to| {
+                journaled_transfer(from, to, TransferMode::Move, Some(journal))
+            });
+            Err(e)
+        },
+    }
+}
+
+/// 传输失败后把移走的已有文件移回 `target`，先删除可能残留的不完整文件
+fn restore_replaced(aside: &Path, target: &Path, restore: impl FnOnce(&Path, &Path) -> Result<()>) {
+    if target.exists()
+        && let Err(e) = std::fs::remove_file(target)
+    {
+        error!("Failed to remove incomplete file {:?}: {}", target, e);
+    }
+    match restore(aside, target) {
+        Ok(()) => info!("Restored replaced file: {:?}", target),
+        Err(e) => error!(
+            "Failed to restore replaced file {:?} → {:?}: {:#}",
+            aside, target, e
+        ),
+    }
+}
+
+/// 替换期间暂存已有文件的路径：目标旁边的隐藏文件
+fn aside_path(target: &Path) -> PathBuf {
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let mut n = 0;
+    loop {
+        let candidate = match n {
+            0 => target.with_file_name(format!(".{}.replaced", name)),
+            n => target.with_file_name(format!(".{}.replaced{}", name, n)),
+        };
+        if !candidate.exists() {
+            return candidate;
+        }
+        n += 1;
+    }
+}
+
+/// 传输文件；有运行日志时先写入意图，完成或失败后再写入结果
+pub(crate) fn journaled_transfer(
+    source: &Path,
+    target: &Path,
+    mode: TransferMode,
+    journal: Option<&mut Journal>,
+) -> Result<()> {
+    journaled_transfer_with_cancel(source, target, mode, journal, &CancelToken::new())
+}
+
+/// 与 `journaled_transfer` 相同，复制过程中请求取消时中止
+///
+/// 被中止的传输不记录结果，继续运行时按未完成的传输处理，源文件重新排队
+fn journaled_transfer_with_cancel(
+    source: &Path,
+    target: &Path,
+    mode: TransferMode,
+    journal: Option<&mut Journal>,
+    cancel: &CancelToken,
+) -> Result<()> {
+    let Some(journal) = journal else {
+        return transfer_file_with_cancel(source, target, mode, cancel);
+    };
+
+    journal.record_intent(source, target, mode)?;
+    match transfer_file_with_cancel(source, target, mode, cancel) {
+        Ok(()) => journal.record_transferred(source, target, mode),
+        Err(e) if Cancelled::is_cause_of(&e) => Err(e),
+        Err(e) => {
+            journal.record_failed(source, &format!("{:#}", e))?;
+            Err(e)
+        },
+    }
+}
+
+/// 分类单个文件（使用配置）
+pub fn classify_file_with_config(
+    config: &Config,
+    target_dir: &Path,
+    source: &Path,
+) -> Result<ClassifyResult> {
+    let entry = Planner::new(config, target_dir).plan_file(source)?;
+    execute_plan_entry(&entry)
+}
+
+/// 分类单个文件并写入运行日志
+pub fn classify_file_with_journal(
+    config: &Config,
+    target_dir: &Path,
+    source: &Path,
+    journal: &mut Journal,
+) -> Result<ClassifyResult> {
+    let entry = Planner::new(config, target_dir).plan_file(source)?;
+    execute_plan_entry_with_journal(&entry, journal)
+}
+
 /// 分类单个文件（向后兼容，使用默认配置）
 #[allow(dead_code)]
 pub fn classify_file(target_dir: &Path, source: &Path) -> Result<ClassifyResult> {
@@ -120,15 +1405,610 @@ pub fn classify_file(target_dir: &Path, source: &Path) -> Result<ClassifyResult>
     classify_file_with_config(&default_config, target_dir, source)
 }
 
-/// 移动文件到目标位置
-fn move_file(source: &Path, target: &Path) -> Result<()> {
-    // 确保目标目录存在
-    if let Some(parent) = target.parent() {
-        std::fs::create_dir_all(parent).context("Failed to create target directory")?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn music_config() -> Config {
+        let mut config = Config::default();
+        config.rules.retain(|r| r.name == "Music");
+        config
     }
 
-    // 移动文件
-    std::fs::rename(source, target).context("Failed to move file")?;
+    #[test]
+    fn test_plan_does_not_touch_disk() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("song.mp3");
+        fs::write(&source, b"music").unwrap();
 
-    Ok(())
+        let config = music_config();
+        let mut planner = Planner::new(&config, temp_dir.path());
+        let entry = planner.plan_file(&source).unwrap();
+
+        assert_eq!(entry.action, PlannedAction::Move);
+        assert_eq!(entry.rule.as_deref(), Some("Music"));
+        assert_eq!(
+            entry.target.as_deref(),
+            Some(temp_dir.path().join("Music/MP3/song.mp3").as_path())
+        );
+        assert!(source.exists());
+        assert!(!temp_dir.path().join("Music").exists());
+    }
+
+    #[test]
+    fn test_plan_predicts_conflicts_between_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        fs::create_dir_all(temp_dir.path().join("a")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("b")).unwrap();
+        fs::create_dir_all(temp_dir.path().join("c")).unwrap();
+        let first = temp_dir.path().join("a/song.mp3");
+        let same = temp_dir.path().join("b/song.mp3");
+        let different = temp_dir.path().join("c/song.mp3");
+        fs::write(&first, b"music").unwrap();
+        fs::write(&same, b"music").unwrap();
+        fs::write(&different, b"other music").unwrap();
+
+        let config = music_config();
+        let mut planner = Planner::new(&config, temp_dir.path());
+        assert_eq!(
+            planner.plan_file(&first).unwrap().action,
+            PlannedAction::Move
+        );
+        assert!(matches!(
+            planner.plan_file(&same).unwrap().action,
+            PlannedAction::Skip { .. }
+        ));
+        let renamed = planner.plan_file(&different).unwrap();
+        assert_eq!(renamed.action, PlannedAction::Rename);
+        assert_eq!(renamed.target.unwrap().file_name().unwrap(), "song_1.mp3");
+    }
+
+    #[test]
+    fn test_filename_template_counter_and_suffix() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        fs::create_dir_all(library.join("Music/MP3")).unwrap();
+        fs::write(library.join("Music/MP3/track_01.mp3"), b"old").unwrap();
+        let mut files = Vec::new();
+        for i in 0..3 {
+            let file = temp_dir.path().join(format!("song{}.mp3", i));
+            fs::write(&file, vec![b'x'; i + 1]).unwrap();
+            files.push(file);
+        }
+
+        // 编号跳过库中已有的文件名
+        let mut config = music_config();
+        config.global.filename_template = Some("track_{counter:2}".to_string());
+        let mut planner = Planner::new(&config, &library);
+        let names: Vec<String> = files
+            .iter()
+            .map(|f| {
+                let entry = planner.plan_file(f).unwrap();
+                assert_eq!(entry.action, PlannedAction::Move);
+                let target = entry.target.unwrap();
+                target.file_name().unwrap().to_string_lossy().into_owned()
+            })
+            .collect();
+        assert_eq!(names, ["track_02.mp3", "track_03.mp3", "track_04.mp3"]);
+
+        // 规则模板覆盖全局模板，重名时使用配置的后缀
+        config.rules[0].filename_template = Some("track".to_string());
+        config.global.collision_suffix = " ({n})".to_string();
+        let mut planner = Planner::new(&config, &library);
+        let first = planner.plan_file(&files[0]).unwrap();
+        assert_eq!(first.target.unwrap().file_name().unwrap(), "track.mp3");
+        let second = planner.plan_file(&files[1]).unwrap();
+        assert_eq!(second.action, PlannedAction::Rename);
+        assert_eq!(second.target.unwrap().file_name().unwrap(), "track (1).mp3");
+    }
+
+    #[test]
+    fn test_sidecars_follow_renamed_primary() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        fs::create_dir_all(library.join("Videos")).unwrap();
+        fs::write(library.join("Videos/GOPR0001.MP4"), b"other clip").unwrap();
+        let card = temp_dir.path().join("card");
+        fs::create_dir_all(&card).unwrap();
+        for (name, data) in [
+            ("GOPR0001.MP4", "clip"),
+            ("GOPR0001.THM", "thumb"),
+            ("GOPR0001.WAV", "voice"),
+        ] {
+            fs::write(card.join(name), data).unwrap();
+        }
+
+        let mut config = Config::default();
+        config.rules.retain(|r| r.name == "Videos");
+        config.rules[0].directory_template = "Videos".to_string();
+        config.rules[0].date_format = None;
+        let files = [card.join("GOPR0001.MP4"), card.join("GOPR0001.WAV")];
+        let mut journal = Journal::create(&library, std::slice::from_ref(&card), None).unwrap();
+        let mut results = Vec::new();
+        Planner::new(&config, &library).classify_files(&files, Some(&mut journal), |_, result| {
+            results.push(result.unwrap())
+        });
+
+        // 语音备注作为附属文件跟随视频，不单独分类
+        assert!(matches!(results[0], ClassifyResult::Renamed { .. }));
+        assert!(matches!(results[1], ClassifyResult::Skipped { .. }));
+        assert_eq!(
+            fs::read(library.join("Videos/GOPR0001_1.THM")).unwrap(),
+            b"thumb"
+        );
+        assert_eq!(
+            fs::read(library.join("Videos/GOPR0001_1.WAV")).unwrap(),
+            b"voice"
+        );
+        assert!(!card.join("GOPR0001.THM").exists());
+
+        let path = journal.path().to_path_buf();
+        journal.finish().unwrap();
+        crate::journal::undo_run(&path).unwrap();
+        assert_eq!(fs::read(card.join("GOPR0001.THM")).unwrap(), b"thumb");
+        assert_eq!(fs::read(card.join("GOPR0001.WAV")).unwrap(), b"voice");
+    }
+
+    #[test]
+    fn test_raw_jpeg_pair_is_classified_together() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        let card = temp_dir.path().join("card");
+        fs::create_dir_all(&card).unwrap();
+        for name in ["DSC_0001.JPG", "DSC_0001.NEF", "DSC_0001.xmp"] {
+            fs::write(card.join(name), name).unwrap();
+        }
+        let files = [card.join("DSC_0001.JPG"), card.join("DSC_0001.NEF")];
+
+        let plan = |config: &Config| {
+            let mut entries = Vec::new();
+            Planner::new(config, &library).plan_files(&files, |_, entry| {
+                entries.push(entry.unwrap());
+            });
+            entries
+        };
+
+        // 开启资产组时按 RAW 分类，JPEG 和共用的 xmp 跟随 RAW
+        let mut config = Config::default();
+        config.asset_groups.enabled = true;
+        let entries = plan(&config);
+        assert!(matches!(entries[0].action, PlannedAction::Skip { .. }));
+        let raw = &entries[1];
+        assert_eq!(raw.rule.as_deref(), Some("RAW Photos"));
+        let raw_target = raw.target.as_ref().unwrap();
+        assert_eq!(raw.members.len(), 1);
+        assert_eq!(raw.members[0].target, raw_target.with_extension("JPG"));
+        assert_eq!(raw.sidecars.len(), 1);
+        assert!(raw.members[0].sidecars.is_empty());
+
+        // 拆分时 JPEG 单独按自己的规则分类
+        config.asset_groups.enabled = false;
+        let entries = plan(&config);
+        assert_eq!(entries[0].rule.as_deref(), Some("Thumbnails"));
+        assert_eq!(entries[1].rule.as_deref(), Some("RAW Photos"));
+        assert!(entries[1].members.is_empty());
+    }
+
+    #[test]
+    fn test_group_member_targets_resolve_planned_conflicts() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        let loose = temp_dir.path().join("loose");
+        let card = temp_dir.path().join("card");
+        fs::create_dir_all(&loose).unwrap();
+        fs::create_dir_all(&card).unwrap();
+        fs::write(loose.join("DSC_0001.JPG"), b"loose").unwrap();
+        fs::write(card.join("DSC_0001.JPG"), b"pair").unwrap();
+        fs::write(card.join("DSC_0001.NEF"), b"raw").unwrap();
+        let files = [
+            loose.join("DSC_0001.JPG"),
+            card.join("DSC_0001.JPG"),
+            card.join("DSC_0001.NEF"),
+        ];
+
+        // RAW 和 JPEG 都放进同一个目录
+        let mut config = Config::default();
+        config.asset_groups.enabled = true;
+        let mut photos = config.rules[0].clone();
+        photos.name = "Photos".to_string();
+        photos.extensions = vec!["nef".to_string(), "jpg".to_string()];
+        photos.directory_template = "Photos".to_string();
+        photos.date_format = None;
+        photos.file_size = None;
+        config.rules.insert(0, photos);
+
+        let plan = |config: &Config| {
+            let mut entries = Vec::new();
+            Planner::new(config, &library).plan_files(&files, |_, entry| {
+                entries.push(entry.unwrap());
+            });
+            entries
+        };
+
+        // 成员的目标路径已被先前的文件占用时，计划中的成员改用带后缀的路径
+        let entries = plan(&config);
+        assert_eq!(
+            entries[0].target.as_deref(),
+            Some(library.join("Photos/DSC_0001.JPG").as_path())
+        );
+        let raw = &entries[2];
+        assert_eq!(raw.members[0].target, library.join("Photos/DSC_0001_1.JPG"));
+        assert_eq!(raw.members[0].skip, None);
+
+        // 执行结果与计划一致
+        for entry in &entries {
+            execute_plan_entry(entry).unwrap();
+        }
+        assert_eq!(
+            fs::read(library.join("Photos/DSC_0001.JPG")).unwrap(),
+            b"loose"
+        );
+        assert_eq!(
+            fs::read(library.join("Photos/DSC_0001_1.JPG")).unwrap(),
+            b"pair"
+        );
+        assert!(library.join("Photos/DSC_0001.NEF").exists());
+
+        // 成员与已计划的文件内容相同时记录为跳过
+        fs::write(loose.join("DSC_0001.JPG"), b"same").unwrap();
+        fs::write(card.join("DSC_0001.JPG"), b"same").unwrap();
+        fs::write(card.join("DSC_0001.NEF"), b"raw").unwrap();
+        let library = temp_dir.path().join("library2");
+        let mut entries = Vec::new();
+        Planner::new(&config, &library).plan_files(&files, |_, entry| {
+            entries.push(entry.unwrap());
+        });
+        let member = &entries[2].members[0];
+        assert_eq!(member.target, library.join("Photos/DSC_0001.JPG"));
+        assert!(member.skip.is_some());
+    }
+
+    #[test]
+    fn test_asset_group_primary_honors_rule_places() {
+        use exif::experimental::Writer;
+        use exif::{Field, In, Rational, Tag, Value};
+
+        let temp_dir = TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        let card = temp_dir.path().join("card");
+        fs::create_dir_all(&card).unwrap();
+        // 在办公室拍摄的 RAW（NEF 基于 TIFF），JPEG 没有 GPS 信息
+        let gps = |tag, ref_tag, reference: &str, degrees| {
+            [
+                Field {
+                    tag,
+                    ifd_num: In::PRIMARY,
+                    value: Value::Rational(vec![
+                        Rational {
+                            num: degrees,
+                            denom: 1,
+                        },
+                        Rational { num: 0, denom: 1 },
+                        Rational { num: 0, denom: 1 },
+                    ]),
+                },
+                Field {
+                    tag: ref_tag,
+                    ifd_num: In::PRIMARY,
+                    value: Value::Ascii(vec![reference.as_bytes().to_vec()]),
+                },
+            ]
+        };
+        let fields = [
+            gps(Tag::GPSLatitude, Tag::GPSLatitudeRef, "N", 31),
+            gps(Tag::GPSLongitude, Tag::GPSLongitudeRef, "E", 121),
+        ];
+        let mut writer = Writer::new();
+        for field in fields.iter().flatten() {
+            writer.push_field(field);
+        }
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        fs::write(card.join("DSC_0001.NEF"), tiff.get_ref()).unwrap();
+        fs::write(card.join("DSC_0001.JPG"), b"jpeg").unwrap();
+        let files = [card.join("DSC_0001.JPG"), card.join("DSC_0001.NEF")];
+
+        // RAW 规则只用于办公室拍摄的照片，且没有后备规则
+        let mut config = Config::default();
+        config.asset_groups.enabled = true;
+        config.places = vec![crate::config::Place {
+            name: "Office".to_string(),
+            area: crate::config::PlaceArea::Circle {
+                center: [31.0, 121.0],
+                radius_m: 200.0,
+            },
+        }];
+        config.rules.retain(|r| r.name != "Default Global");
+        for rule in config.rules.iter_mut().filter(|r| r.name == "RAW Photos") {
+            rule.places = vec!["Office".to_string()];
+        }
+
+        // RAW 在办公室内，匹配 RAW 规则并作为主成员，JPEG 跟随 RAW
+        let mut entries = Vec::new();
+        Planner::new(&config, &library).plan_files(&files, |_, entry| {
+            entries.push(entry.unwrap());
+        });
+        assert!(matches!(entries[0].action, PlannedAction::Skip { .. }));
+        let raw = &entries[1];
+        assert_eq!(raw.rule.as_deref(), Some("RAW Photos"));
+        let raw_target = raw.target.as_ref().unwrap();
+        assert_eq!(raw.members.len(), 1);
+        assert_eq!(raw.members[0].target, raw_target.with_extension("JPG"));
+    }
+
+    #[test]
+    fn test_chaptered_video_is_kept_together() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        let card = temp_dir.path().join("card");
+        fs::create_dir_all(&card).unwrap();
+        let names = ["GH010042.MP4", "GH020042.MP4", "GH010043.MP4"];
+        for name in names {
+            fs::write(card.join(name), name).unwrap();
+        }
+        let files: Vec<PathBuf> = names.iter().map(|name| card.join(name)).collect();
+
+        let plan = |config: &Config| {
+            let mut targets = Vec::new();
+            Planner::new(config, &library).plan_files(&files, |_, entry| {
+                let entry = entry.unwrap();
+                let target = entry.target.unwrap();
+                targets.push((
+                    target.strip_prefix(&library).unwrap().to_path_buf(),
+                    entry.sequence,
+                ));
+            });
+            targets
+        };
+
+        let mut config = Config::default();
+        config.rules.retain(|r| r.name == "Videos");
+        config.rules[0].directory_template = "Videos".to_string();
+        config.rules[0].date_format = None;
+        config.sequences.keep_together = true;
+        let targets = plan(&config);
+        assert_eq!(targets[0].0, Path::new("Videos/GH010042/GH010042.MP4"));
+        assert_eq!(targets[1].0, Path::new("Videos/GH010042/GH020042.MP4"));
+        assert_eq!(targets[1].1.as_deref(), Some("GH010042"));
+        assert_eq!(targets[2], (PathBuf::from("Videos/GH010043.MP4"), None));
+
+        // 不属于序列的文件省略空的 `{sequence}` 目录层级
+        config.sequences.keep_together = false;
+        config.rules[0].directory_template = "Videos/{sequence}".to_string();
+        config.rules[0].filename_template = Some("{sequence}-{stem}".to_string());
+        let targets = plan(&config);
+        assert_eq!(
+            targets[1].0,
+            Path::new("Videos/GH010042/GH010042-GH020042.MP4")
+        );
+        // 不属于序列的文件名省略 `{sequence}` 和相邻的分隔符
+        assert_eq!(targets[2].0, Path::new("Videos/GH010043.MP4"));
+    }
+
+    #[test]
+    fn test_rule_conflict_policy_overwrite_is_undoable() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        let existing = library.join("Music/MP3/song.mp3");
+        fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        fs::write(&existing, b"old mix").unwrap();
+        let source = temp_dir.path().join("song.mp3");
+        fs::write(&source, b"new master").unwrap();
+
+        let mut config = music_config();
+        config.rules[0].conflict = Some(ConflictPolicy::Overwrite);
+        let entry = Planner::new(&config, &library).plan_file(&source).unwrap();
+        assert_eq!(entry.action, PlannedAction::Replace);
+
+        let mut journal = Journal::create(&library, &[], None).unwrap();
+        let result = execute_plan_entry_with_journal(&entry, &mut journal).unwrap();
+        assert!(matches!(
+            result,
+            ClassifyResult::Replaced {
+                policy: ConflictPolicy::Overwrite,
+                ..
+            }
+        ));
+        assert_eq!(fs::read(&existing).unwrap(), b"new master");
+        let path = journal.path().to_path_buf();
+        journal.finish().unwrap();
+
+        // 撤销后新文件回到源位置，被替换的文件恢复
+        crate::journal::undo_run(&path).unwrap();
+        assert_eq!(fs::read(&source).unwrap(), b"new master");
+        assert_eq!(fs::read(&existing).unwrap(), b"old mix");
+    }
+
+    #[test]
+    fn test_failed_replace_keeps_existing_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        let existing = library.join("Music/MP3/song.mp3");
+        fs::create_dir_all(existing.parent().unwrap()).unwrap();
+        fs::write(&existing, b"old mix").unwrap();
+        let source = temp_dir.path().join("song.mp3");
+        fs::write(&source, b"new master").unwrap();
+
+        let mut config = music_config();
+        config.rules[0].conflict = Some(ConflictPolicy::Overwrite);
+        config.rules[0].transfer_mode = Some(TransferMode::Copy);
+        let entry = Planner::new(&config, &library).plan_file(&source).unwrap();
+        assert_eq!(entry.action, PlannedAction::Replace);
+
+        // 复制在第一个数据块之前被取消，传输失败
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        assert!(execute(&entry, None, &cancel).is_err());
+        assert_eq!(fs::read(&existing).unwrap(), b"old mix");
+        assert_eq!(fs::read_dir(existing.parent().unwrap()).unwrap().count(), 1);
+
+        let mut journal = Journal::create(&library, &[], None).unwrap();
+        assert!(execute(&entry, Some(&mut journal), &cancel).is_err());
+        assert_eq!(fs::read(&existing).unwrap(), b"old mix");
+        assert_eq!(fs::read(&source).unwrap(), b"new master");
+    }
+
+    #[test]
+    fn test_execute_plan_entry_moves_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("song.mp3");
+        fs::write(&source, b"music").unwrap();
+
+        let config = music_config();
+        let entry = Planner::new(&config, temp_dir.path())
+            .plan_file(&source)
+            .unwrap();
+        let result = execute_plan_entry(&entry).unwrap();
+
+        assert!(matches!(result, ClassifyResult::Success { .. }));
+        assert!(!source.exists());
+        assert!(temp_dir.path().join("Music/MP3/song.mp3").exists());
+    }
+
+    #[test]
+    fn test_rule_transfer_mode_is_applied() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("song.mp3");
+        fs::write(&source, b"music").unwrap();
+
+        let mut config = music_config();
+        config.rules[0].transfer_mode = Some(TransferMode::Copy);
+        let result = classify_file_with_config(&config, temp_dir.path(), &source).unwrap();
+
+        assert!(matches!(
+            result,
+            ClassifyResult::Success {
+                mode: TransferMode::Copy,
+                ..
+            }
+        ));
+        assert!(source.exists());
+        assert!(temp_dir.path().join("Music/MP3/song.mp3").exists());
+    }
+
+    #[test]
+    fn test_parallel_classify_is_deterministic() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        let mut files = Vec::new();
+        for i in 0..12 {
+            let dir = temp_dir.path().join(format!("card{}", i));
+            fs::create_dir_all(&dir).unwrap();
+            let file = dir.join("song.mp3");
+            fs::write(&file, vec![b'x'; i + 1]).unwrap();
+            files.push(file);
+        }
+
+        let config = music_config();
+        let mut targets = Vec::new();
+        Planner::new(&config, &library)
+            .with_jobs(4)
+            .classify_files(&files, None, |_, result| match result.unwrap() {
+                ClassifyResult::Success { to, .. } | ClassifyResult::Renamed { to, .. } => {
+                    targets.push(to)
+                },
+                other => panic!("unexpected result: {:?}", other),
+            });
+
+        // 按输入顺序处理：第一个文件保留原名，其余依次编号
+        assert_eq!(targets[0], library.join("Music/MP3/song.mp3"));
+        for (i, target) in targets.iter().enumerate().skip(1) {
+            assert_eq!(*target, library.join(format!("Music/MP3/song_{}.mp3", i)));
+            assert_eq!(fs::metadata(target).unwrap().len(), i as u64 + 1);
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingObserver {
+        classified: Vec<PathBuf>,
+        bytes: u64,
+        cleaned: Vec<PathBuf>,
+        finished: Option<Progress>,
+        /// 处理完第一个文件后请求取消
+        cancel: Option<CancelToken>,
+    }
+
+    impl Observer for RecordingObserver {
+        fn file_classified(
+            &mut self,
+            source: &Path,
+            _result: &Result<ClassifyResult>,
+            _progress: &Progress,
+        ) {
+            self.classified.push(source.to_path_buf());
+            if let Some(cancel) = &self.cancel {
+                cancel.cancel();
+            }
+        }
+
+        fn bytes_transferred(&mut self, _source: &Path, bytes: u64, _progress: &Progress) {
+            self.bytes += bytes;
+        }
+
+        fn dir_cleaned(&mut self, dir: &Path) {
+            self.cleaned.push(dir.to_path_buf());
+        }
+
+        fn run_finished(&mut self, progress: &Progress) {
+            self.finished = Some(progress.clone());
+        }
+    }
+
+    #[test]
+    fn test_run_reports_progress_to_observer() {
+        let temp_dir = TempDir::new().unwrap();
+        let card = temp_dir.path().join("card");
+        let album = card.join("album");
+        fs::create_dir_all(&album).unwrap();
+        let files = vec![album.join("a.mp3"), album.join("b.mp3")];
+        fs::write(&files[0], b"aaaa").unwrap();
+        fs::write(&files[1], b"bbbbbb").unwrap();
+
+        let config = music_config();
+        let mut observer = RecordingObserver::default();
+        let progress = Planner::new(&config, &temp_dir.path().join("library"))
+            .run(&files, None, &[card], &mut observer)
+            .unwrap();
+
+        assert_eq!(observer.classified, files);
+        assert_eq!(observer.bytes, 10);
+        assert_eq!(observer.cleaned, [album]);
+        let finished = observer.finished.unwrap();
+        assert_eq!((finished.files_done, finished.success), (2, 2));
+        assert_eq!(progress.bytes_transferred, 10);
+        assert_eq!(progress.bytes_total, 10);
+    }
+
+    #[test]
+    fn test_run_stops_when_cancelled() {
+        let temp_dir = TempDir::new().unwrap();
+        let files: Vec<PathBuf> = (0..5)
+            .map(|i| temp_dir.path().join(format!("card/{}.mp3", i)))
+            .collect();
+        fs::create_dir_all(temp_dir.path().join("card")).unwrap();
+        for file in &files {
+            fs::write(file, b"music").unwrap();
+        }
+
+        let config = music_config();
+        let cancel = CancelToken::new();
+        let mut observer = RecordingObserver {
+            cancel: Some(cancel.clone()),
+            ..RecordingObserver::default()
+        };
+        let progress = Planner::new(&config, &temp_dir.path().join("library"))
+            .with_jobs(2)
+            .with_cancel_token(cancel)
+            .run(&files, None, &[], &mut observer)
+            .unwrap();
+
+        // 只处理了第一个文件，其余文件留在原处
+        assert_eq!(observer.classified, &files[..1]);
+        assert!(progress.cancelled);
+        assert_eq!((progress.files_done, progress.success), (1, 1));
+        assert!(files[1..].iter().all(|file| file.exists()));
+        assert!(observer.finished.unwrap().cancelled);
+    }
 }
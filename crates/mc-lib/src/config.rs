@@ -4,6 +4,10 @@ use std::collections::HashMap;
 use std::fs;
 use std::path::{Path, PathBuf};
 
+use crate::asset_group::AssetKind;
+use crate::conflict::{CompareStrategy, ConflictPolicy, DEFAULT_COLLISION_SUFFIX};
+use crate::transfer::TransferMode;
+
 /// 配置文件根结构
 #[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Config {
@@ -13,6 +17,25 @@ pub struct Config {
     pub extension_aliases: HashMap<String, Vec<String>>,
     #[serde(default)]
     pub exclude: ExcludeConfig,
+    #[serde(default)]
+    pub scan: ScanConfig,
+    #[serde(default)]
+    pub dedupe: DedupeConfig,
+    #[serde(default)]
+    pub similar: SimilarConfig,
+    #[serde(default)]
+    pub sidecars: SidecarConfig,
+    #[serde(default)]
+    pub asset_groups: AssetGroupConfig,
+    #[serde(default)]
+    pub sequences: SequenceConfig,
+    #[serde(default)]
+    pub events: EventConfig,
+    #[serde(default)]
+    pub geocoding: GeocodingConfig,
+    /// 用户定义的地点，按顺序匹配
+    #[serde(default)]
+    pub places: Vec<Place>,
 }
 
 /// 全局配置
@@ -23,6 +46,30 @@ pub struct GlobalConfig {
     pub clean_empty_dirs: bool,
     #[serde(default)]
     pub file_size: Option<FileSizeFilter>,
+    /// 默认传输方式（move / copy / copy_then_delete）
+    #[serde(default)]
+    pub transfer_mode: TransferMode,
+    /// 并行提取元数据的线程数，0 表示使用 CPU 核心数
+    #[serde(default)]
+    pub jobs: usize,
+    /// 目标文件已存在时判断是否为同一文件的方式
+    #[serde(default)]
+    pub compare: CompareStrategy,
+    /// 目标位置已有不同文件时的默认处理策略
+    #[serde(default)]
+    pub conflict: ConflictPolicy,
+    /// `quarantine` 策略使用的隔离目录，相对于目标目录
+    #[serde(default = "default_quarantine_dir")]
+    pub quarantine_dir: String,
+    /// 默认文件名模板（不含扩展名），未设置时保留原文件名
+    #[serde(default)]
+    pub filename_template: Option<String>,
+    /// 重名时追加在文件名后的后缀模板，`{n}` 为递增编号
+    #[serde(default = "default_collision_suffix")]
+    pub collision_suffix: String,
+    /// 文件中没有相机、镜头、音频标签等信息时模板变量使用的值
+    #[serde(default = "default_missing_placeholder")]
+    pub missing_placeholder: String,
 }
 
 /// 文件分类规则
@@ -36,6 +83,18 @@ pub struct Rule {
     pub directory_template: String,
     pub date_format: Option<String>,
     pub enabled: bool,
+    /// 规则级传输方式，未设置时使用全局设置
+    #[serde(default)]
+    pub transfer_mode: Option<TransferMode>,
+    /// 规则级冲突处理策略，未设置时使用全局设置
+    #[serde(default)]
+    pub conflict: Option<ConflictPolicy>,
+    /// 规则级文件名模板，未设置时使用全局设置
+    #[serde(default)]
+    pub filename_template: Option<String>,
+    /// 只匹配在这些地点（`places` 中的名称）拍摄的文件，为空时不限制
+    #[serde(default)]
+    pub places: Vec<String>,
 }
 
 /// 文件大小过滤器
@@ -60,6 +119,317 @@ pub struct ExcludeConfig {
     pub patterns: Vec<String>,
 }
 
+/// 扫描源目录的配置
+///
+/// 隐藏文件和目录的处理见 `exclude.hidden_files`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct ScanConfig {
+    /// 最大扫描深度（源目录中的文件为 1），None 表示不限制
+    #[serde(default)]
+    pub max_depth: Option<usize>,
+    /// 跟随指向目录和文件的符号链接（循环链接会被跳过）
+    #[serde(default)]
+    pub follow_symlinks: bool,
+    /// 不进入其他文件系统上的目录（如挂载的网络盘）
+    #[serde(default)]
+    pub same_filesystem: bool,
+}
+
+/// 重复文件检测配置
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+pub struct DedupeConfig {
+    /// 分类前索引目标目录，跳过内容已存在于库中任意位置的文件
+    #[serde(default)]
+    pub skip_existing: bool,
+}
+
+/// 相似图片检测配置
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SimilarConfig {
+    /// 与库中图片相似（重新压缩、缩放）的新图片移到复查目录
+    #[serde(default)]
+    pub enabled: bool,
+    /// 相似度阈值（百分比），感知哈希相似度不低于该值视为相似
+    #[serde(default = "default_similar_threshold")]
+    pub threshold: u8,
+    /// 复查目录，相对于目标目录
+    #[serde(default = "default_review_dir")]
+    pub review_dir: String,
+}
+
+impl Default for SimilarConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: default_similar_threshold(),
+            review_dir: default_review_dir(),
+        }
+    }
+}
+
+/// 附属文件配置
+///
+/// 与图片或视频同名（或以其完整文件名为前缀）的附属文件随主文件一起传输
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SidecarConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 附属文件扩展名（不区分大小写）
+    #[serde(default = "default_sidecar_extensions")]
+    pub extensions: Vec<String>,
+}
+
+impl Default for SidecarConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            extensions: default_sidecar_extensions(),
+        }
+    }
+}
+
+/// 多文件资产组配置
+///
+/// 同一目录下去掉编辑后缀后文件名相同的图片和视频（RAW+JPEG、实况照片、
+/// 编辑版本）作为一组，按主成员分类并放在一起
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AssetGroupConfig {
+    /// 为 false 时拆分资产组，每个文件单独匹配规则；默认关闭，
+    /// 以免升级后 RAW+JPEG 中的 JPEG 改为跟随 RAW 的规则
+    #[serde(default)]
+    pub enabled: bool,
+    /// 选择主成员时各种类的优先顺序
+    #[serde(default = "default_asset_primary")]
+    pub primary: Vec<AssetKind>,
+    /// 编辑版本的文件名后缀（不区分大小写）
+    #[serde(default = "default_edited_suffixes")]
+    pub edited_suffixes: Vec<String>,
+}
+
+impl Default for AssetGroupConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            primary: default_asset_primary(),
+            edited_suffixes: default_edited_suffixes(),
+        }
+    }
+}
+
+/// 连拍、包围曝光和分段视频序列配置
+///
+/// 序列可通过模板变量 `{sequence}` 引用，也可以整体放进以第一个成员日期
+/// 归档的公共子目录
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct SequenceConfig {
+    /// 为 false 时不识别序列，`{sequence}` 展开为空
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 整个序列按第一个成员的日期放进同一个子目录
+    #[serde(default)]
+    pub keep_together: bool,
+    /// `keep_together` 时追加在目标目录后的子目录模板
+    #[serde(default = "default_sequence_subfolder")]
+    pub subfolder: String,
+    /// 连拍中相邻两张的最大间隔（毫秒）
+    #[serde(default = "default_burst_gap_ms")]
+    pub burst_gap_ms: u64,
+    /// 包围曝光中相邻两张（曝光补偿不同）的最大间隔（毫秒）
+    #[serde(default = "default_bracket_gap_ms")]
+    pub bracket_gap_ms: u64,
+    /// 连拍和包围曝光的最少张数，分段视频只需两段
+    #[serde(default = "default_sequence_min_length")]
+    pub min_length: usize,
+}
+
+impl Default for SequenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            keep_together: false,
+            subfolder: default_sequence_subfolder(),
+            burst_gap_ms: default_burst_gap_ms(),
+            bracket_gap_ms: default_bracket_gap_ms(),
+            min_length: default_sequence_min_length(),
+        }
+    }
+}
+
+/// 事件聚类配置
+///
+/// 所有文件按拍摄时间排序，相邻间隔超过阈值时开始新的事件
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct EventConfig {
+    /// 为 false 时每个文件单独作为一个事件
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// 同一事件中相邻两个文件的最大间隔（分钟）
+    #[serde(default = "default_event_gap_minutes")]
+    pub max_gap_minutes: u64,
+    /// 同一事件中相邻拍摄地点的最大距离（公里），null 表示不按地点拆分
+    #[serde(default)]
+    pub max_distance_km: Option<f64>,
+}
+
+impl Default for EventConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            max_gap_minutes: default_event_gap_minutes(),
+            max_distance_km: None,
+        }
+    }
+}
+
+fn default_event_gap_minutes() -> u64 {
+    8 * 60
+}
+
+/// 反向地理编码配置
+///
+/// 模板中使用 `{country}` `{region}` `{city}` 时，按 GPS 坐标查找最近的城市
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GeocodingConfig {
+    /// GeoNames 格式的城市数据（如 `cities500.txt`），null 表示使用内置的主要城市列表
+    ///
+    /// 内置列表只有约 150 个大城市，小城镇的照片会归到附近的大城市或使用占位名称
+    #[serde(default)]
+    pub cities: Option<PathBuf>,
+    /// GeoNames 的省/州名称表（`admin1CodesASCII.txt`），null 表示使用原始代码
+    #[serde(default)]
+    pub admin1_codes: Option<PathBuf>,
+    /// 坐标到最近城市的最大距离（公里），超过时地点变量使用占位名称
+    #[serde(default = "default_geocoding_distance_km")]
+    pub max_distance_km: f64,
+}
+
+impl Default for GeocodingConfig {
+    fn default() -> Self {
+        Self {
+            cities: None,
+            admin1_codes: None,
+            max_distance_km: default_geocoding_distance_km(),
+        }
+    }
+}
+
+fn default_geocoding_distance_km() -> f64 {
+    30.0
+}
+
+/// 用户定义的地点
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Place {
+    /// 名称，用于 `{place}` 和规则的 `places` 条件
+    pub name: String,
+    #[serde(flatten)]
+    pub area: PlaceArea,
+}
+
+/// 地点范围，坐标为 `[纬度, 经度]`
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(untagged, try_from = "RawPlaceArea")]
+pub enum PlaceArea {
+    /// 以 `center` 为中心、半径 `radius_m` 米的圆
+    Circle { center: [f64; 2], radius_m: f64 },
+    /// 依次连接各顶点围成的多边形
+    Polygon { polygon: Vec<[f64; 2]> },
+}
+
+/// 配置中的地点范围字段，按给出的字段确定形状并报告缺少的字段
+#[derive(Deserialize)]
+struct RawPlaceArea {
+    center: Option<[f64; 2]>,
+    radius_m: Option<f64>,
+    polygon: Option<Vec<[f64; 2]>>,
+}
+
+impl TryFrom<RawPlaceArea> for PlaceArea {
+    type Error = String;
+
+    fn try_from(raw: RawPlaceArea) -> std::result::Result<Self, Self::Error> {
+        match (raw.center, raw.radius_m, raw.polygon) {
+            (Some(center), Some(radius_m), None) => Ok(PlaceArea::Circle { center, radius_m }),
+            (None, None, Some(polygon)) => Ok(PlaceArea::Polygon { polygon }),
+            (Some(_), None, None) => Err("place with `center` requires `radius_m`".to_string()),
+            (None, Some(_), None) => Err("place with `radius_m` requires `center`".to_string()),
+            (None, None, None) => {
+                Err("place requires either `center` and `radius_m`, or `polygon`".to_string())
+            },
+            _ => Err("place cannot have both `polygon` and `center`/`radius_m`".to_string()),
+        }
+    }
+}
+
+impl Place {
+    /// 检查地点范围是否有效：多边形至少 3 个顶点，半径为正数
+    fn validate(&self) -> Result<()> {
+        match &self.area {
+            PlaceArea::Circle { radius_m, .. } if radius_m.is_nan() || *radius_m <= 0.0 => {
+                anyhow::bail!("Place {:?} must have a positive radius_m", self.name)
+            },
+            PlaceArea::Polygon { polygon } if polygon.len() < 3 => {
+                anyhow::bail!("Place {:?} needs at least 3 polygon vertices", self.name)
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+fn default_sequence_subfolder() -> String {
+    "{sequence}".to_string()
+}
+
+fn default_burst_gap_ms() -> u64 {
+    1000
+}
+
+fn default_bracket_gap_ms() -> u64 {
+    3000
+}
+
+fn default_sequence_min_length() -> usize {
+    3
+}
+
+fn default_asset_primary() -> Vec<AssetKind> {
+    vec![AssetKind::Raw, AssetKind::Image, AssetKind::Video]
+}
+
+fn default_edited_suffixes() -> Vec<String> {
+    ["-edited", "_edited", " (edited)"]
+        .iter()
+        .map(|suffix| suffix.to_string())
+        .collect()
+}
+
+fn default_sidecar_extensions() -> Vec<String> {
+    ["xmp", "aae", "thm", "lrv", "wav"]
+        .iter()
+        .map(|ext| ext.to_string())
+        .collect()
+}
+
+fn default_quarantine_dir() -> String {
+    "_Quarantine".to_string()
+}
+
+fn default_collision_suffix() -> String {
+    DEFAULT_COLLISION_SUFFIX.to_string()
+}
+
+fn default_missing_placeholder() -> String {
+    "Unknown".to_string()
+}
+
+fn default_similar_threshold() -> u8 {
+    90
+}
+
+fn default_review_dir() -> String {
+    "_Review/Similar".to_string()
+}
+
 fn default_true() -> bool {
     true
 }
@@ -148,6 +518,25 @@ impl FileSize {
     }
 }
 
+impl Rule {
+    /// 该规则实际使用的传输方式
+    pub fn effective_transfer_mode(&self, global: &GlobalConfig) -> TransferMode {
+        self.transfer_mode.unwrap_or(global.transfer_mode)
+    }
+
+    /// 该规则实际使用的冲突处理策略
+    pub fn effective_conflict_policy(&self, global: &GlobalConfig) -> ConflictPolicy {
+        self.conflict.unwrap_or(global.conflict)
+    }
+
+    /// 该规则实际使用的文件名模板，None 表示保留原文件名
+    pub fn effective_filename_template<'a>(&'a self, global: &'a GlobalConfig) -> Option<&'a str> {
+        self.filename_template
+            .as_deref()
+            .or(global.filename_template.as_deref())
+    }
+}
+
 impl Config {
     /// 创建默认配置
     #[allow(dead_code)]
@@ -160,6 +549,18 @@ impl Config {
             fs::read_to_string(path).context(format!("Failed to read config file: {:?}", path))?;
         let config: Config =
             serde_yaml_bw::from_str(&content).context("Failed to parse YAML config")?;
+        for place in &config.places {
+            place.validate()?;
+        }
+        for rule in &config.rules {
+            if let Some(place) = rule
+                .places
+                .iter()
+                .find(|name| !config.places.iter().any(|p| &p.name == *name))
+            {
+                anyhow::bail!("Rule {:?} refers to unknown place {:?}", rule.name, place);
+            }
+        }
         Ok(config)
     }
 
@@ -173,6 +574,50 @@ impl Config {
         fs::write(path, yaml)?;
         Ok(())
     }
+
+    /// 是否需要识别序列：启用了 `keep_together`，或有模板使用了 `{sequence}`
+    pub fn uses_sequences(&self) -> bool {
+        self.sequences.enabled
+            && (self.sequences.keep_together || self.uses_template_var("{sequence}"))
+    }
+
+    /// 是否需要聚类事件：有模板使用了 `{event}`、`{event_start}` 或 `{event_end}`
+    pub fn uses_events(&self) -> bool {
+        self.events.enabled
+            && ["{event}", "{event_start}", "{event_end}"]
+                .iter()
+                .any(|var| self.uses_template_var(var))
+    }
+
+    /// 模板是否使用了需要反向地理编码的地点变量
+    pub fn uses_geocoding(&self) -> bool {
+        ["{country}", "{region}", "{city}"]
+            .iter()
+            .any(|var| self.uses_template_var(var))
+    }
+
+    /// 全局设置或任一启用的规则的目录、文件名模板是否包含变量 `var`
+    fn uses_template_var(&self, var: &str) -> bool {
+        let global = [
+            Some(self.global.directory_template.as_str()),
+            self.global.filename_template.as_deref(),
+        ];
+        let rules = self
+            .rules
+            .iter()
+            .filter(|rule| rule.enabled)
+            .flat_map(|rule| {
+                [
+                    Some(rule.directory_template.as_str()),
+                    rule.filename_template.as_deref(),
+                ]
+            });
+        global
+            .into_iter()
+            .chain(rules)
+            .flatten()
+            .any(|template| template.contains(var))
+    }
 }
 
 impl Default for Config {
@@ -183,6 +628,14 @@ impl Default for Config {
                 directory_template: "{ext}/{date}".to_string(),
                 clean_empty_dirs: true,
                 file_size: None,
+                transfer_mode: TransferMode::Move,
+                jobs: 0,
+                compare: CompareStrategy::FullHash,
+                conflict: ConflictPolicy::KeepBoth,
+                quarantine_dir: default_quarantine_dir(),
+                filename_template: None,
+                collision_suffix: default_collision_suffix(),
+                missing_placeholder: default_missing_placeholder(),
             },
             rules: vec![
                 Rule {
@@ -196,6 +649,10 @@ impl Default for Config {
                     directory_template: "Photos/{year}/{month}".to_string(),
                     date_format: Some("YYYY/MM".to_string()),
                     enabled: true,
+                    transfer_mode: None,
+                    conflict: None,
+                    filename_template: None,
+                    places: Vec::new(),
                 },
                 Rule {
                     name: "RAW Photos".to_string(),
@@ -214,6 +671,10 @@ impl Default for Config {
                     directory_template: "RAW/{year}/{month}/{day}".to_string(),
                     date_format: Some("YYYY/MM/DD".to_string()),
                     enabled: true,
+                    transfer_mode: None,
+                    conflict: None,
+                    filename_template: None,
+                    places: Vec::new(),
                 },
                 Rule {
                     name: "Thumbnails".to_string(),
@@ -226,6 +687,10 @@ impl Default for Config {
                     directory_template: "Thumbnails/{date}".to_string(),
                     date_format: Some("YYYYMMDD".to_string()),
                     enabled: true,
+                    transfer_mode: None,
+                    conflict: None,
+                    filename_template: None,
+                    places: Vec::new(),
                 },
                 Rule {
                     name: "Videos".to_string(),
@@ -243,6 +708,10 @@ impl Default for Config {
                     directory_template: "Videos/{year}".to_string(),
                     date_format: Some("YYYY".to_string()),
                     enabled: true,
+                    transfer_mode: None,
+                    conflict: None,
+                    filename_template: None,
+                    places: Vec::new(),
                 },
                 Rule {
                     name: "Music".to_string(),
@@ -258,6 +727,10 @@ impl Default for Config {
                     directory_template: "Music/{ext}".to_string(),
                     date_format: None,
                     enabled: true,
+                    transfer_mode: None,
+                    conflict: None,
+                    filename_template: None,
+                    places: Vec::new(),
                 },
                 Rule {
                     name: "Default Global".to_string(),
@@ -267,6 +740,10 @@ impl Default for Config {
                     directory_template: "{ext}/{date}".to_string(),
                     date_format: Some("YYYYMMDD".to_string()),
                     enabled: true,
+                    transfer_mode: None,
+                    conflict: None,
+                    filename_template: None,
+                    places: Vec::new(),
                 },
             ],
             extension_aliases: {
@@ -303,6 +780,15 @@ impl Default for Config {
                     ".DS_Store".to_string(),
                 ],
             },
+            scan: ScanConfig::default(),
+            dedupe: DedupeConfig::default(),
+            similar: SimilarConfig::default(),
+            sidecars: SidecarConfig::default(),
+            asset_groups: AssetGroupConfig::default(),
+            sequences: SequenceConfig::default(),
+            events: EventConfig::default(),
+            geocoding: GeocodingConfig::default(),
+            places: Vec::new(),
         }
     }
 }
@@ -1,23 +1,206 @@
 use anyhow::{Context, Result};
 use log::debug;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
+use crate::hash::{files_equal, hash_file, quick_hash};
+use crate::utils::{expand_number, has_number_var};
+
+/// 默认的重名后缀模板，如 photo.jpg -> photo_1.jpg
+pub const DEFAULT_COLLISION_SUFFIX: &str = "_{n}";
+
+/// 判断两个文件是否相同的方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareStrategy {
+    /// 仅比较文件大小（最快，大小相同的不同文件会被误判为相同）
+    Size,
+    /// 比较大小和快速哈希（开头、中间、结尾各 64KB）
+    QuickHash,
+    /// 比较完整内容哈希，先用大小和快速哈希排除不同的文件
+    #[default]
+    FullHash,
+    /// 逐字节比较，先用大小和快速哈希排除不同的文件
+    ByteByByte,
+}
+
+impl CompareStrategy {
+    /// 用于显示和配置的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            CompareStrategy::Size => "size",
+            CompareStrategy::QuickHash => "quick_hash",
+            CompareStrategy::FullHash => "full_hash",
+            CompareStrategy::ByteByByte => "byte_by_byte",
+        }
+    }
+}
+
+impl std::str::FromStr for CompareStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "size" => Ok(CompareStrategy::Size),
+            "quick_hash" => Ok(CompareStrategy::QuickHash),
+            "full_hash" => Ok(CompareStrategy::FullHash),
+            "byte_by_byte" => Ok(CompareStrategy::ByteByByte),
+            other => anyhow::bail!("Unknown compare strategy: {}", other),
+        }
+    }
+}
+
+/// 目标位置已有内容不同的文件时的处理策略
+///
+/// 内容相同的文件总是被跳过，策略只决定内容不同时如何处理
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConflictPolicy {
+    /// 保留两者，新文件添加数字后缀
+    #[default]
+    KeepBoth,
+    /// 跳过新文件
+    Skip,
+    /// 用新文件替换已有文件
+    Overwrite,
+    /// 保留修改时间较新的文件
+    KeepNewer,
+    /// 保留较大的文件
+    KeepLarger,
+    /// 新文件移到隔离目录
+    Quarantine,
+}
+
+impl ConflictPolicy {
+    /// 用于显示和配置的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ConflictPolicy::KeepBoth => "keep_both",
+            ConflictPolicy::Skip => "skip",
+            ConflictPolicy::Overwrite => "overwrite",
+            ConflictPolicy::KeepNewer => "keep_newer",
+            ConflictPolicy::KeepLarger => "keep_larger",
+            ConflictPolicy::Quarantine => "quarantine",
+        }
+    }
+}
+
+impl std::str::FromStr for ConflictPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "keep_both" | "rename" => Ok(ConflictPolicy::KeepBoth),
+            "skip" => Ok(ConflictPolicy::Skip),
+            "overwrite" => Ok(ConflictPolicy::Overwrite),
+            "keep_newer" => Ok(ConflictPolicy::KeepNewer),
+            "keep_larger" => Ok(ConflictPolicy::KeepLarger),
+            "quarantine" => Ok(ConflictPolicy::Quarantine),
+            other => anyhow::bail!("Unknown conflict policy: {}", other),
+        }
+    }
+}
+
+/// 按指定方式判断两个文件内容是否相同
+pub(crate) fn files_identical(a: &Path, b: &Path, strategy: CompareStrategy) -> Result<bool> {
+    let size_a = std::fs::metadata(a)
+        .context(format!("Failed to get file metadata: {:?}", a))?
+        .len();
+    let size_b = std::fs::metadata(b)
+        .context(format!("Failed to get file metadata: {:?}", b))?
+        .len();
+    if size_a != size_b {
+        return Ok(false);
+    }
+    if strategy == CompareStrategy::Size {
+        return Ok(true);
+    }
+
+    // 快速预筛：大部分不同的文件在这里就能区分
+    if quick_hash(a)? != quick_hash(b)? {
+        return Ok(false);
+    }
+
+    match strategy {
+        CompareStrategy::Size | CompareStrategy::QuickHash => Ok(true),
+        CompareStrategy::FullHash => Ok(hash_file(a)? == hash_file(b)?),
+        CompareStrategy::ByteByByte => files_equal(a, b),
+    }
+}
+
 /// 文件冲突解决结果
 #[derive(Debug)]
 pub enum ConflictResolution {
     /// 无冲突，可以直接使用目标路径
     NoConflict(PathBuf),
-    /// 文件相同（大小一致），应该跳过
+    /// 文件相同或按策略保留已有文件，应该跳过
     Skip(String),
     /// 文件不同，需要重命名
     Rename(PathBuf),
+    /// 按策略替换目标路径上的已有文件
+    Replace(PathBuf),
+    /// 按策略移到隔离目录
+    Quarantine(String),
 }
 
 /// 解决文件冲突
 /// 如果目标文件不存在，返回 NoConflict
-/// 如果目标文件存在且大小相同，返回 Skip
-/// 如果目标文件存在但大小不同，返回 Rename（带新文件名）
-pub fn resolve_conflict(source: &Path, target: &Path) -> Result<ConflictResolution> {
+/// 如果目标文件存在且内容相同（按 `compare` 判断），返回 Skip
+/// 如果目标文件存在但内容不同，按 `policy` 决定；重命名时使用 `suffix` 模板
+pub fn resolve_conflict(
+    source: &Path,
+    target: &Path,
+    compare: CompareStrategy,
+    policy: ConflictPolicy,
+    suffix: &str,
+) -> Result<ConflictResolution> {
+    resolve_conflict_with_reserved(source, target, &HashMap::new(), compare, policy, suffix)
+}
+
+/// 解决文件冲突，同时考虑已被计划占用（尚未写入磁盘）的目标路径
+/// `reserved` 记录目标路径到计划写入该路径的源文件
+pub fn resolve_conflict_with_reserved(
+    source: &Path,
+    target: &Path,
+    reserved: &HashMap<PathBuf, PathBuf>,
+    compare: CompareStrategy,
+    policy: ConflictPolicy,
+    suffix: &str,
+) -> Result<ConflictResolution> {
+    // 目标路径已被本次计划中的其他文件占用
+    if let Some(planned_source) = reserved.get(target) {
+        // 边规划边执行时，计划的源文件可能已经被移动到目标路径
+        let planned = if planned_source.exists() {
+            planned_source.as_path()
+        } else {
+            target
+        };
+        if planned.exists() && files_identical(source, planned, compare)? {
+            let reason = format!(
+                "Identical file ({}) planned earlier: {:?}",
+                compare.as_str(),
+                target
+            );
+            debug!("{}", reason);
+            return Ok(ConflictResolution::Skip(reason));
+        }
+        if !planned.exists() {
+            return Ok(ConflictResolution::Rename(generate_unique_name(
+                target, reserved, suffix,
+            )?));
+        }
+        debug!("Target {:?} reserved by plan ({})", target, policy.as_str());
+        // 替换类策略只作用于运行前已在磁盘上的文件，本次计划中的文件之间
+        // 互相替换会丢失先规划的文件，改为重命名
+        return match apply_policy(source, planned, target, reserved, policy, suffix)? {
+            ConflictResolution::Replace(_) => Ok(ConflictResolution::Rename(generate_unique_name(
+                target, reserved, suffix,
+            )?)),
+            resolution => Ok(resolution),
+        };
+    }
+
     // 如果目标文件不存在，无冲突
     if !target.exists() {
         debug!("No conflict for {:?}", target);
@@ -32,37 +215,87 @@ pub fn resolve_conflict(source: &Path, target: &Path) -> Result<ConflictResoluti
         return Ok(ConflictResolution::Skip(reason));
     }
 
-    // 比较文件大小
-    let source_size = std::fs::metadata(source)
-        .context("Failed to get source file metadata")?
-        .len();
-
-    let target_size = std::fs::metadata(target)
-        .context("Failed to get target file metadata")?
-        .len();
-
-    if source_size == target_size {
-        // 文件大小相同，跳过
+    // 比较文件内容
+    if files_identical(source, target, compare)? {
+        // 文件相同，跳过
         let reason = format!(
-            "File already exists with same size ({} bytes): {:?}",
-            source_size, target
+            "Identical file ({}) already exists: {:?}",
+            compare.as_str(),
+            target
         );
         debug!("{}", reason);
         Ok(ConflictResolution::Skip(reason))
     } else {
-        // 文件大小不同，生成新文件名
-        let new_target = generate_unique_name(target)?;
+        // 文件不同，按策略处理
         debug!(
-            "File exists with different size (source: {} bytes, target: {} bytes), renaming to {:?}",
-            source_size, target_size, new_target
+            "Different file exists at {:?} ({})",
+            target,
+            policy.as_str()
         );
-        Ok(ConflictResolution::Rename(new_target))
+        apply_policy(source, target, target, reserved, policy, suffix)
+    }
+}
+
+/// 目标位置已有内容不同的文件 `existing` 时按策略决定如何处理
+fn apply_policy(
+    source: &Path,
+    existing: &Path,
+    target: &Path,
+    reserved: &HashMap<PathBuf, PathBuf>,
+    policy: ConflictPolicy,
+    suffix: &str,
+) -> Result<ConflictResolution> {
+    let source_meta = std::fs::metadata(source).context("Failed to get source file metadata")?;
+    let existing_meta = std::fs::metadata(existing)
+        .context(format!("Failed to get file metadata: {:?}", existing))?;
+
+    let keep_existing = |reason: &str| {
+        Ok(ConflictResolution::Skip(format!(
+            "{} ({}): {:?}",
+            reason,
+            policy.as_str(),
+            target
+        )))
+    };
+
+    match policy {
+        ConflictPolicy::KeepBoth => Ok(ConflictResolution::Rename(generate_unique_name(
+            target, reserved, suffix,
+        )?)),
+        ConflictPolicy::Skip => keep_existing("Different file already exists"),
+        ConflictPolicy::Overwrite => Ok(ConflictResolution::Replace(target.to_path_buf())),
+        ConflictPolicy::KeepNewer => {
+            if source_meta.modified()? > existing_meta.modified()? {
+                Ok(ConflictResolution::Replace(target.to_path_buf()))
+            } else {
+                keep_existing("Existing file is not older")
+            }
+        },
+        ConflictPolicy::KeepLarger => {
+            if source_meta.len() > existing_meta.len() {
+                Ok(ConflictResolution::Replace(target.to_path_buf()))
+            } else {
+                keep_existing("Existing file is not smaller")
+            }
+        },
+        ConflictPolicy::Quarantine => Ok(ConflictResolution::Quarantine(format!(
+            "Conflicts with {:?}",
+            target
+        ))),
     }
 }
 
 /// 生成唯一的文件名
-/// 在文件名后添加数字后缀，如 photo.jpg -> photo_1.jpg
-fn generate_unique_name(target: &Path) -> Result<PathBuf> {
+/// 在文件名后添加按 `suffix` 模板展开的后缀，如 `_{n}`: photo.jpg -> photo_1.jpg
+/// 已存在于磁盘或已被计划占用的文件名都会被跳过
+fn generate_unique_name(
+    target: &Path,
+    reserved: &HashMap<PathBuf, PathBuf>,
+    suffix: &str,
+) -> Result<PathBuf> {
+    if !has_number_var(suffix, "n") {
+        anyhow::bail!("Collision suffix must contain {{n}}: {:?}", suffix);
+    }
     let parent = target.parent().context("Failed to get parent directory")?;
     let file_stem = target
         .file_stem()
@@ -70,16 +303,17 @@ fn generate_unique_name(target: &Path) -> Result<PathBuf> {
         .context("Failed to get file stem")?;
     let extension = target.extension().and_then(|s| s.to_str()).unwrap_or("");
 
-    // 尝试添加数字后缀，直到找到不存在的文件名
+    // 尝试递增编号，直到找到不存在的文件名
     for i in 1..1000 {
+        let stem = format!("{}{}", file_stem, expand_number(suffix, "n", i));
         let new_name = if extension.is_empty() {
-            format!("{}_{}", file_stem, i)
+            stem
         } else {
-            format!("{}_{}.{}", file_stem, i, extension)
+            format!("{}.{}", stem, extension)
         };
 
         let new_path = parent.join(new_name);
-        if !new_path.exists() {
+        if !new_path.exists() && !reserved.contains_key(&new_path) {
             return Ok(new_path);
         }
     }
@@ -102,14 +336,193 @@ mod tests {
         fs::write(&base_path, b"test").unwrap();
 
         // 生成唯一名称
-        let unique = generate_unique_name(&base_path).unwrap();
+        let unique =
+            generate_unique_name(&base_path, &HashMap::new(), DEFAULT_COLLISION_SUFFIX).unwrap();
         assert_eq!(unique.file_name().unwrap(), "test_1.jpg");
 
         // 创建 test_1.jpg
         fs::write(&unique, b"test").unwrap();
 
         // 再次生成应该得到 test_2.jpg
-        let unique2 = generate_unique_name(&base_path).unwrap();
+        let unique2 =
+            generate_unique_name(&base_path, &HashMap::new(), DEFAULT_COLLISION_SUFFIX).unwrap();
         assert_eq!(unique2.file_name().unwrap(), "test_2.jpg");
+
+        // 自定义后缀模板
+        let custom = generate_unique_name(&base_path, &HashMap::new(), " ({n:2})").unwrap();
+        assert_eq!(custom.file_name().unwrap(), "test (01).jpg");
+        assert!(generate_unique_name(&base_path, &HashMap::new(), "_copy").is_err());
+    }
+
+    #[test]
+    fn test_resolve_conflict_with_reserved() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("source.jpg");
+        let same = temp_dir.path().join("same.jpg");
+        let other = temp_dir.path().join("other.jpg");
+        fs::write(&source, b"data").unwrap();
+        fs::write(&same, b"data").unwrap();
+        fs::write(&other, b"more data").unwrap();
+        let target = temp_dir.path().join("out").join("photo.jpg");
+
+        let mut reserved = HashMap::new();
+        reserved.insert(target.clone(), same);
+        assert!(matches!(
+            resolve_conflict_with_reserved(
+                &source,
+                &target,
+                &reserved,
+                CompareStrategy::FullHash,
+                ConflictPolicy::KeepBoth,
+                DEFAULT_COLLISION_SUFFIX,
+            )
+            .unwrap(),
+            ConflictResolution::Skip(_)
+        ));
+
+        reserved.insert(target.clone(), other.clone());
+        reserved.insert(temp_dir.path().join("out").join("photo_1.jpg"), other);
+        match resolve_conflict_with_reserved(
+            &source,
+            &target,
+            &reserved,
+            CompareStrategy::FullHash,
+            ConflictPolicy::KeepBoth,
+            DEFAULT_COLLISION_SUFFIX,
+        )
+        .unwrap()
+        {
+            ConflictResolution::Rename(p) => assert_eq!(p.file_name().unwrap(), "photo_2.jpg"),
+            other => panic!("unexpected resolution: {:?}", other),
+        }
+
+        // 本次计划中的文件不会被替换
+        match resolve_conflict_with_reserved(
+            &source,
+            &target,
+            &reserved,
+            CompareStrategy::FullHash,
+            ConflictPolicy::Overwrite,
+            DEFAULT_COLLISION_SUFFIX,
+        )
+        .unwrap()
+        {
+            ConflictResolution::Rename(p) => assert_eq!(p.file_name().unwrap(), "photo_2.jpg"),
+            other => panic!("unexpected resolution: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_same_size_different_content_is_renamed() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("DSC_0001.NEF");
+        let target = temp_dir.path().join("NEF").join("DSC_0001.NEF");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        fs::write(&source, b"raw frame A").unwrap();
+        fs::write(&target, b"raw frame B").unwrap();
+
+        // 仅比较大小会误判为相同
+        assert!(matches!(
+            resolve_conflict(
+                &source,
+                &target,
+                CompareStrategy::Size,
+                ConflictPolicy::KeepBoth,
+                DEFAULT_COLLISION_SUFFIX,
+            )
+            .unwrap(),
+            ConflictResolution::Skip(_)
+        ));
+        for strategy in [
+            CompareStrategy::QuickHash,
+            CompareStrategy::FullHash,
+            CompareStrategy::ByteByByte,
+        ] {
+            assert!(matches!(
+                resolve_conflict(
+                    &source,
+                    &target,
+                    strategy,
+                    ConflictPolicy::KeepBoth,
+                    DEFAULT_COLLISION_SUFFIX,
+                )
+                .unwrap(),
+                ConflictResolution::Rename(_)
+            ));
+        }
+
+        fs::write(&target, b"raw frame A").unwrap();
+        assert!(matches!(
+            resolve_conflict(
+                &source,
+                &target,
+                CompareStrategy::ByteByByte,
+                ConflictPolicy::KeepBoth,
+                DEFAULT_COLLISION_SUFFIX,
+            )
+            .unwrap(),
+            ConflictResolution::Skip(_)
+        ));
+    }
+
+    #[test]
+    fn test_conflict_policies() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("photo.jpg");
+        let target = temp_dir.path().join("JPG").join("photo.jpg");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        fs::write(&target, b"old photo").unwrap();
+        fs::write(&source, b"new, larger photo").unwrap();
+
+        let resolve = |policy| {
+            resolve_conflict(
+                &source,
+                &target,
+                CompareStrategy::FullHash,
+                policy,
+                DEFAULT_COLLISION_SUFFIX,
+            )
+            .unwrap()
+        };
+        assert!(matches!(
+            resolve(ConflictPolicy::KeepBoth),
+            ConflictResolution::Rename(_)
+        ));
+        assert!(matches!(
+            resolve(ConflictPolicy::Skip),
+            ConflictResolution::Skip(_)
+        ));
+        assert!(matches!(
+            resolve(ConflictPolicy::Overwrite),
+            ConflictResolution::Replace(_)
+        ));
+        assert!(matches!(
+            resolve(ConflictPolicy::KeepLarger),
+            ConflictResolution::Replace(_)
+        ));
+        assert!(matches!(
+            resolve(ConflictPolicy::Quarantine),
+            ConflictResolution::Quarantine(_)
+        ));
+
+        // 源文件更旧时保留已有文件
+        let old = std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_600_000_000);
+        fs::File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(old)
+            .unwrap();
+        assert!(matches!(
+            resolve(ConflictPolicy::KeepNewer),
+            ConflictResolution::Skip(_)
+        ));
+
+        // 内容相同时任何策略都跳过
+        fs::write(&source, b"old photo").unwrap();
+        assert!(matches!(
+            resolve(ConflictPolicy::Overwrite),
+            ConflictResolution::Skip(_)
+        ));
     }
 }
@@ -0,0 +1,358 @@
+//! 媒体库范围的重复文件检测
+//!
+//! `LibraryIndex` 按文件大小索引目标目录中的所有媒体文件，分类时用来发现
+//! 换了文件名但内容已经在库中的文件；`find_duplicate_groups` 扫描整个库，
+//! 依次按大小、快速哈希、完整哈希分组找出重复文件。
+
+use anyhow::{Context, Result};
+use log::{debug, info};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+use crate::hash::{hash_file, quick_hash};
+use crate::journal::{DATA_DIR, Journal};
+use crate::media_types::get_media_info;
+use crate::pipeline::ordered_parallel;
+use crate::transfer::TransferMode;
+
+/// 目标目录中媒体文件的内容索引
+///
+/// 只在构建时读取文件大小，内容哈希在需要比较时才计算并缓存
+#[derive(Debug, Default)]
+pub struct LibraryIndex {
+    /// 文件大小 -> 该大小的文件
+    by_size: HashMap<u64, Vec<PathBuf>>,
+    /// 已计算的完整哈希
+    hashes: HashMap<PathBuf, String>,
+    /// 本次运行计划写入的目标路径 -> 源文件
+    planned: HashMap<PathBuf, PathBuf>,
+}
+
+impl LibraryIndex {
+    /// 扫描目标目录构建索引
+    pub fn build(root: &Path) -> Result<Self> {
+        let mut index = LibraryIndex::default();
+        for path in library_media_files(root) {
+            let size = fs::metadata(&path)
+                .context(format!("Failed to get file metadata: {:?}", path))?
+                .len();
+            index.by_size.entry(size).or_default().push(path);
+        }
+        info!(
+            "Indexed {} library files in {:?}",
+            index.by_size.values().map(Vec::len).sum::<usize>(),
+            root
+        );
+        Ok(index)
+    }
+
+    /// 查找库中与 `source` 内容相同的文件
+    pub fn find_duplicate(&mut self, source: &Path) -> Result<Option<PathBuf>> {
+        let size = fs::metadata(source)
+            .context("Failed to get source file metadata")?
+            .len();
+        let candidates = match self.by_size.get(&size) {
+            Some(candidates) => candidates.clone(),
+            None => return Ok(None),
+        };
+
+        let mut source_hash = None;
+        for candidate in candidates {
+            if candidate == source {
+                continue;
+            }
+            // 本次运行计划写入、尚未写入磁盘的文件用其源文件比较
+            let actual = match self.planned.get(&candidate) {
+                Some(planned) if planned.exists() => planned.clone(),
+                _ => candidate.clone(),
+            };
+            if actual == source || !actual.exists() {
+                continue;
+            }
+
+            if source_hash.is_none() {
+                source_hash = Some(hash_file(source)?);
+            }
+            if self.content_hash(&candidate, &actual)? == *source_hash.as_ref().unwrap() {
+                debug!("{:?} duplicates library file {:?}", source, candidate);
+                return Ok(Some(candidate));
+            }
+        }
+        Ok(None)
+    }
+
+    /// 记录本次运行计划写入的文件，后续文件也会与之比较
+    pub fn add_planned(&mut self, target: &Path, source: &Path, size: u64) {
+        self.by_size
+            .entry(size)
+            .or_default()
+            .push(target.to_path_buf());
+        self.planned
+            .insert(target.to_path_buf(), source.to_path_buf());
+    }
+
+    fn content_hash(&mut self, key: &Path, actual: &Path) -> Result<String> {
+        if let Some(hash) = self.hashes.get(key) {
+            return Ok(hash.clone());
+        }
+        let hash = hash_file(actual)?;
+        self.hashes.insert(key.to_path_buf(), hash.clone());
+        Ok(hash)
+    }
+}
+
+/// 一组内容相同的文件
+#[derive(Debug, Clone)]
+pub struct DuplicateGroup {
+    pub size: u64,
+    pub hash: String,
+    /// 按路径排序
+    pub files: Vec<PathBuf>,
+}
+
+/// 选择保留哪一个副本
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum KeepStrategy {
+    /// 保留修改时间最早的
+    #[default]
+    Oldest,
+    /// 保留修改时间最新的
+    Newest,
+    /// 保留路径最短的
+    ShortestPath,
+    /// 保留按路径排序的第一个
+    First,
+}
+
+impl KeepStrategy {
+    /// 用于显示的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            KeepStrategy::Oldest => "oldest",
+            KeepStrategy::Newest => "newest",
+            KeepStrategy::ShortestPath => "shortest_path",
+            KeepStrategy::First => "first",
+        }
+    }
+}
+
+impl std::str::FromStr for KeepStrategy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "oldest" => Ok(KeepStrategy::Oldest),
+            "newest" => Ok(KeepStrategy::Newest),
+            "shortest_path" => Ok(KeepStrategy::ShortestPath),
+            "first" => Ok(KeepStrategy::First),
+            other => anyhow::bail!("Unknown keep strategy: {}", other),
+        }
+    }
+}
+
+impl DuplicateGroup {
+    /// 按策略选出要保留的文件
+    pub fn keeper(&self, keep: KeepStrategy) -> &Path {
+        let modified = |p: &PathBuf| {
+            fs::metadata(p)
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH)
+        };
+        // files 已按路径排序，min_by_key/max_by_key 在相等时分别取第一个/最后一个
+        let keeper = match keep {
+            KeepStrategy::Oldest => self.files.iter().min_by_key(|p| modified(p)),
+            KeepStrategy::Newest => self.files.iter().rev().max_by_key(|p| modified(p)),
+            KeepStrategy::ShortestPath => self.files.iter().min_by_key(|p| p.as_os_str().len()),
+            KeepStrategy::First => self.files.first(),
+        };
+        keeper.expect("duplicate group is never empty")
+    }
+
+    /// 除保留文件之外的副本
+    pub fn duplicates(&self, keep: KeepStrategy) -> Vec<&Path> {
+        let keeper = self.keeper(keep);
+        self.files
+            .iter()
+            .map(PathBuf::as_path)
+            .filter(|p| *p != keeper)
+            .collect()
+    }
+}
+
+/// 扫描目录，找出内容相同的媒体文件组
+///
+/// 先按大小分组，再用快速哈希和完整哈希逐步细分，只有大小相同的文件
+/// 才会被读取；哈希计算使用 `jobs` 个线程并行进行
+pub fn find_duplicate_groups(root: &Path, jobs: usize) -> Result<Vec<DuplicateGroup>> {
+    let mut by_size: HashMap<u64, Vec<PathBuf>> = HashMap::new();
+    for path in library_media_files(root) {
+        let size = fs::metadata(&path)
+            .context(format!("Failed to get file metadata: {:?}", path))?
+            .len();
+        by_size.entry(size).or_default().push(path);
+    }
+
+    let candidates: Vec<(u64, PathBuf)> = by_size
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .flat_map(|(size, files)| files.into_iter().map(move |f| (size, f)))
+        .collect();
+
+    // 快速哈希细分
+    let by_quick = group_by_hash(&candidates, jobs, quick_hash)?;
+    let candidates: Vec<(u64, PathBuf)> = by_quick
+        .into_values()
+        .filter(|files| files.len() > 1)
+        .flatten()
+        .collect();
+
+    // 完整哈希确认
+    let mut groups: Vec<DuplicateGroup> = group_by_hash(&candidates, jobs, hash_file)?
+        .into_iter()
+        .filter(|(_, files)| files.len() > 1)
+        .map(|((size, hash), files)| {
+            let mut files: Vec<PathBuf> = files.into_iter().map(|(_, f)| f).collect();
+            files.sort();
+            DuplicateGroup { size, hash, files }
+        })
+        .collect();
+
+    groups.sort_by(|a, b| a.files[0].cmp(&b.files[0]));
+    Ok(groups)
+}
+
+type HashGroups = HashMap<(u64, String), Vec<(u64, PathBuf)>>;
+
+fn group_by_hash(
+    files: &[(u64, PathBuf)],
+    jobs: usize,
+    hash: fn(&Path) -> Result<String>,
+) -> Result<HashGroups> {
+    let mut groups: HashGroups = HashMap::new();
+    let mut error = None;
+    ordered_parallel(
+        files,
+        jobs,
+        None,
+        |(_, path)| hash(path),
+        |(size, path), result| match result {
+            Ok(hash) => groups
+                .entry((*size, hash))
+                .or_default()
+                .push((*size, path.clone())),
+            Err(e) => {
+                error.get_or_insert(e);
+            },
+        },
+    );
+    match error {
+        Some(e) => Err(e),
+        None => Ok(groups),
+    }
+}
+
+/// 处理重复副本的方式
+#[derive(Debug, Clone)]
+pub enum DedupeAction {
+    /// 删除
+    Delete,
+    /// 移动到指定目录（保留相对路径），可以通过运行日志撤销
+    MoveTo(PathBuf),
+}
+
+/// 按指定方式处理一个重复副本，返回移动后的路径（删除时为 None）
+pub fn remove_duplicate(
+    path: &Path,
+    root: &Path,
+    action: &DedupeAction,
+    journal: Option<&mut Journal>,
+) -> Result<Option<PathBuf>> {
+    match action {
+        DedupeAction::Delete => {
+            fs::remove_file(path).context(format!("Failed to delete {:?}", path))?;
+            info!("Deleted duplicate: {:?}", path);
+            Ok(None)
+        },
+        DedupeAction::MoveTo(dir) => {
+            let relative = path.strip_prefix(root).unwrap_or(path);
+            let relative = relative.strip_prefix("/").unwrap_or(relative);
+            let target = dir.join(relative);
+            if target.exists() {
+                anyhow::bail!("Target already exists: {:?}", target);
+            }
+            crate::classifier::journaled_transfer(path, &target, TransferMode::Move, journal)?;
+            info!("Moved duplicate: {:?} → {:?}", path, target);
+            Ok(Some(target))
+        },
+    }
+}
+
+/// 库中的媒体文件（跳过程序数据目录）
+fn library_media_files(root: &Path) -> impl Iterator<Item = PathBuf> {
+    let data_dir = root.join(DATA_DIR);
+    WalkDir::new(root)
+        .into_iter()
+        .filter_entry(move |e| !e.path().starts_with(&data_dir))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter(|e| get_media_info(e.path()).is_some())
+        .map(|e| e.into_path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_duplicate_groups() {
+        let temp_dir = TempDir::new().unwrap();
+        let root = temp_dir.path();
+        fs::create_dir_all(root.join("JPG/2024")).unwrap();
+        fs::write(root.join("JPG/2024/IMG_1.jpg"), b"same photo").unwrap();
+        fs::write(root.join("JPG/copy of IMG_1.jpg"), b"same photo").unwrap();
+        // 大小相同、内容不同
+        fs::write(root.join("JPG/IMG_2.jpg"), b"diff photo").unwrap();
+
+        let groups = find_duplicate_groups(root, 2).unwrap();
+        assert_eq!(groups.len(), 1);
+        assert_eq!(groups[0].files.len(), 2);
+
+        let keeper = groups[0].keeper(KeepStrategy::ShortestPath);
+        assert_eq!(keeper, root.join("JPG/2024/IMG_1.jpg"));
+        assert_eq!(
+            groups[0].duplicates(KeepStrategy::ShortestPath),
+            vec![root.join("JPG/copy of IMG_1.jpg").as_path()]
+        );
+    }
+
+    #[test]
+    fn test_library_index_finds_renamed_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        fs::create_dir_all(library.join("JPG")).unwrap();
+        fs::write(library.join("JPG/IMG_1.jpg"), b"photo").unwrap();
+
+        let incoming = temp_dir.path().join("renamed.jpg");
+        let different = temp_dir.path().join("other.jpg");
+        fs::write(&incoming, b"photo").unwrap();
+        fs::write(&different, b"other").unwrap();
+
+        let mut index = LibraryIndex::build(&library).unwrap();
+        assert_eq!(
+            index.find_duplicate(&incoming).unwrap(),
+            Some(library.join("JPG/IMG_1.jpg"))
+        );
+        assert_eq!(index.find_duplicate(&different).unwrap(), None);
+
+        // 本次运行中计划写入的文件也会参与比较
+        let planned = library.join("JPG/other.jpg");
+        index.add_planned(&planned, &different, 5);
+        let another = temp_dir.path().join("another.jpg");
+        fs::write(&another, b"other").unwrap();
+        assert_eq!(index.find_duplicate(&another).unwrap(), Some(planned));
+    }
+}
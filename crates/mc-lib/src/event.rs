@@ -0,0 +1,142 @@
+//! 按拍摄时间间隔聚类的事件
+//!
+//! 按日期分目录会把一次周末出行拆成好几个目录。事件聚类把所有文件按拍摄时间
+//! 排序，相邻两个文件间隔超过阈值（或拍摄地点相距太远）时开始新的事件，
+//! 目录模板可以通过 `{event}` / `{event_start}` / `{event_end}` 引用事件。
+
+use chrono::{DateTime, Datelike, Local};
+use std::path::PathBuf;
+
+use crate::config::EventConfig;
+use crate::metadata::GpsPosition;
+
+/// 事件的时间范围
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct EventSpan {
+    /// 第一个文件的拍摄时间
+    pub start: DateTime<Local>,
+    /// 最后一个文件的拍摄时间
+    pub end: DateTime<Local>,
+}
+
+impl EventSpan {
+    /// 事件名称，即 `{event}` 的值
+    ///
+    /// 同一天为 `2025-11-15`，同月为 `2025-11-15_to_17`，同年为 `2025-11-30_to_12-02`，
+    /// 跨年为 `2025-12-30_to_2026-01-02`
+    pub fn name(&self) -> String {
+        let (start, end) = (self.start.date_naive(), self.end.date_naive());
+        let first = start.format("%Y-%m-%d");
+        if start == end {
+            first.to_string()
+        } else if (start.year(), start.month()) == (end.year(), end.month()) {
+            format!("{}_to_{}", first, end.format("%d"))
+        } else if start.year() == end.year() {
+            format!("{}_to_{}", first, end.format("%m-%d"))
+        } else {
+            format!("{}_to_{}", first, end.format("%Y-%m-%d"))
+        }
+    }
+}
+
+/// 一个事件
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct Event {
+    pub span: EventSpan,
+    /// 按拍摄时间排列的文件
+    pub files: Vec<PathBuf>,
+}
+
+/// 待聚类的文件：路径、拍摄时间和可选的拍摄地点
+pub(crate) type EventItem = (PathBuf, DateTime<Local>, Option<GpsPosition>);
+
+/// 按拍摄时间间隔把文件聚类为事件
+///
+/// 相邻两个文件的间隔超过 `max_gap_minutes` 时开始新的事件；设置了
+/// `max_distance_km` 时，与事件中最近一个有地点的文件相距更远也开始新的事件
+pub(crate) fn cluster_events(items: &[EventItem], config: &EventConfig) -> Vec<Event> {
+    let mut sorted: Vec<&EventItem> = items.iter().collect();
+    sorted.sort_by(|a, b| a.1.cmp(&b.1).then_with(|| a.0.cmp(&b.0)));
+
+    let max_gap = chrono::TimeDelta::minutes(config.max_gap_minutes as i64);
+    let mut events: Vec<Event> = Vec::new();
+    let mut last_position: Option<GpsPosition> = None;
+    for (path, time, position) in sorted {
+        let moved = match (config.max_distance_km, &last_position, position) {
+            (Some(max), Some(last), Some(position)) => last.distance_km(position) > max,
+            _ => false,
+        };
+        match events.last_mut() {
+            Some(event) if *time - event.span.end <= max_gap && !moved => {
+                event.span.end = *time;
+                event.files.push(path.clone());
+            },
+            _ => {
+                events.push(Event {
+                    span: EventSpan {
+                        start: *time,
+                        end: *time,
+                    },
+                    files: vec![path.clone()],
+                });
+                last_position = None;
+            },
+        }
+        if position.is_some() {
+            last_position = *position;
+        }
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(month: u32, day: u32, hour: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2025, month, day, hour, 0, 0)
+            .unwrap()
+    }
+
+    #[test]
+    fn test_cluster_events() {
+        let home = GpsPosition {
+            latitude: 48.8566,
+            longitude: 2.3522,
+        };
+        let away = GpsPosition {
+            latitude: 51.5074,
+            longitude: -0.1278,
+        };
+        let items: Vec<EventItem> = vec![
+            (PathBuf::from("b.jpg"), at(11, 16, 10), None),
+            (PathBuf::from("a.jpg"), at(11, 15, 18), Some(home)),
+            (PathBuf::from("c.jpg"), at(11, 17, 2), Some(home)),
+            (PathBuf::from("d.jpg"), at(11, 17, 5), Some(away)),
+            (PathBuf::from("e.jpg"), at(11, 30, 12), None),
+        ];
+
+        let mut config = EventConfig {
+            max_gap_minutes: 24 * 60,
+            ..EventConfig::default()
+        };
+        let events = cluster_events(&items, &config);
+        let names: Vec<String> = events.iter().map(|e| e.span.name()).collect();
+        assert_eq!(names, ["2025-11-15_to_17", "2025-11-30"]);
+        assert_eq!(events[0].files[0], PathBuf::from("a.jpg"));
+
+        // 地点相距太远时开始新的事件
+        config.max_distance_km = Some(50.0);
+        let events = cluster_events(&items, &config);
+        assert_eq!(events.len(), 3);
+        assert_eq!(events[1].files, [PathBuf::from("d.jpg")]);
+
+        let span = EventSpan {
+            start: at(12, 30, 0),
+            end: Local.with_ymd_and_hms(2026, 1, 2, 0, 0, 0).unwrap(),
+        };
+        assert_eq!(span.name(), "2025-12-30_to_2026-01-02");
+    }
+}
@@ -3,7 +3,8 @@
 /// 负责根据配置规则过滤不需要处理的文件和目录
 /// 遵循单一职责原则：只处理过滤逻辑
 use crate::config::ExcludeConfig;
-use std::path::Path;
+use crate::scanner::SkipReason;
+use std::path::{Path, PathBuf};
 use walkdir::DirEntry;
 
 /// 文件过滤器
@@ -11,27 +12,52 @@ use walkdir::DirEntry;
 /// 封装所有过滤逻辑，提供统一的过滤接口
 pub struct FileFilter<'a> {
     config: &'a ExcludeConfig,
+    /// 整体跳过的目录树（如与源目录分离的目标目录）
+    excluded_dirs: Vec<PathBuf>,
 }
 
 impl<'a> FileFilter<'a> {
     /// 创建新的过滤器实例
     pub fn new(config: &'a ExcludeConfig) -> Self {
-        Self { config }
+        Self {
+            config,
+            excluded_dirs: Vec::new(),
+        }
+    }
+
+    /// 额外排除指定的目录树
+    pub fn with_excluded_dirs(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.excluded_dirs = dirs;
+        self
     }
 
     /// 检查目录是否应该被排除
     ///
     /// 返回 true 表示应该跳过这个目录
     pub fn should_exclude_dir(&self, entry: &DirEntry) -> bool {
+        self.dir_exclusion(entry).is_some()
+    }
+
+    /// 返回目录被排除的原因，不排除时返回 None
+    pub fn dir_exclusion(&self, entry: &DirEntry) -> Option<SkipReason> {
         if !entry.file_type().is_dir() {
-            return false;
+            return None;
+        }
+
+        // 0. 检查显式排除的目录树
+        if self
+            .excluded_dirs
+            .iter()
+            .any(|excluded| entry.path().starts_with(excluded))
+        {
+            return Some(SkipReason::Destination);
         }
 
         let dir_name = entry.file_name().to_string_lossy();
 
         // 1. 检查隐藏目录
         if self.config.hidden_files && dir_name.starts_with('.') {
-            return true;
+            return Some(SkipReason::Hidden);
         }
 
         // 2. 检查配置的排除目录列表
@@ -41,10 +67,10 @@ impl<'a> FileFilter<'a> {
             .iter()
             .any(|excluded| excluded.eq_ignore_ascii_case(&dir_name))
         {
-            return true;
+            return Some(SkipReason::Excluded);
         }
 
-        false
+        None
     }
 
     /// 检查文件是否应该被排除
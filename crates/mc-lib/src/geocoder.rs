@@ -0,0 +1,278 @@
+//! 离线反向地理编码
+//!
+//! [`Geocoder`] 把 GPS 坐标映射到最近的城市，得到国家、省/州和城市名称。
+//! 城市数据使用 GeoNames 的 `cities*.txt` 格式（制表符分隔），可以在配置中
+//! 指定完整的数据集，未指定时使用内置的主要城市列表（约 150 个大城市，
+//! 只适合按国家或大城市粗略归档）。
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+
+use crate::config::GeocodingConfig;
+use crate::metadata::GpsPosition;
+
+/// 内置的主要城市数据，省/州列直接写名称
+const BUNDLED_CITIES: &str = include_str!("../cities.tsv");
+
+/// GeoNames 城市数据的列
+const NAME_COLUMN: usize = 1;
+const LATITUDE_COLUMN: usize = 4;
+const LONGITUDE_COLUMN: usize = 5;
+const COUNTRY_COLUMN: usize = 8;
+const ADMIN1_COLUMN: usize = 10;
+
+/// 每度纬度对应的距离（公里）
+const KM_PER_DEGREE: f64 = 111.2;
+
+/// 拍摄地点
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct Location {
+    /// GPS 坐标
+    pub position: Option<GpsPosition>,
+    /// 国家代码（ISO 3166-1，如 `CN`）
+    pub country: Option<String>,
+    /// 省/州
+    pub region: Option<String>,
+    /// 最近的城市
+    pub city: Option<String>,
+    /// 配置中定义的地点
+    pub place: Option<String>,
+}
+
+impl Location {
+    /// 模板变量名和值，坐标保留两位小数；没有的值为 None
+    pub fn template_values(&self) -> [(&'static str, Option<String>); 6] {
+        [
+            ("country", self.country.clone()),
+            ("region", self.region.clone()),
+            ("city", self.city.clone()),
+            ("lat", self.position.map(|p| format!("{:.2}", p.latitude))),
+            ("lon", self.position.map(|p| format!("{:.2}", p.longitude))),
+            ("place", self.place.clone()),
+        ]
+    }
+}
+
+#[derive(Debug, Clone)]
+struct City {
+    name: String,
+    position: GpsPosition,
+    country: String,
+    region: Option<String>,
+}
+
+/// 离线反向地理编码器
+#[derive(Debug, Clone)]
+pub struct Geocoder {
+    /// 按纬度排序的城市
+    cities: Vec<City>,
+    /// 最近的城市超过此距离（公里）时不认为坐标属于该城市
+    max_distance_km: f64,
+}
+
+impl Geocoder {
+    /// 使用内置的主要城市数据
+    pub fn bundled() -> Self {
+        Self::from_cities(parse_cities(BUNDLED_CITIES.lines(), &HashMap::new()))
+    }
+
+    /// 读取 GeoNames 格式的城市数据
+    ///
+    /// `admin1_codes` 为 `admin1CodesASCII.txt` 格式的省/州名称表，
+    /// 未指定时省/州使用城市数据中的原始代码
+    pub fn load(cities: &Path, admin1_codes: Option<&Path>) -> Result<Self> {
+        let regions = match admin1_codes {
+            Some(path) => read_admin1_codes(path)?,
+            None => HashMap::new(),
+        };
+        let file = File::open(cities)
+            .with_context(|| format!("Failed to open city dataset {:?}", cities))?;
+        let lines = BufReader::new(file)
+            .lines()
+            .collect::<std::io::Result<Vec<_>>>()
+            .with_context(|| format!("Failed to read city dataset {:?}", cities))?;
+        let cities_found = parse_cities(lines.iter().map(String::as_str), &regions);
+        if cities_found.is_empty() {
+            anyhow::bail!("No cities found in {:?}", cities);
+        }
+        Ok(Self::from_cities(cities_found))
+    }
+
+    /// 按配置中的 `geocoding` 设置创建
+    pub fn from_config(config: &GeocodingConfig) -> Result<Self> {
+        let geocoder = match &config.cities {
+            Some(path) => Self::load(path, config.admin1_codes.as_deref())?,
+            None => Self::bundled(),
+        };
+        Ok(geocoder.with_max_distance(config.max_distance_km))
+    }
+
+    fn from_cities(mut cities: Vec<City>) -> Self {
+        cities.sort_by(|a, b| a.position.latitude.total_cmp(&b.position.latitude));
+        Geocoder {
+            cities,
+            max_distance_km: GeocodingConfig::default().max_distance_km,
+        }
+    }
+
+    /// 设置坐标到最近城市的最大距离（公里）
+    pub fn with_max_distance(mut self, km: f64) -> Self {
+        self.max_distance_km = km;
+        self
+    }
+
+    /// 城市数量
+    pub fn len(&self) -> usize {
+        self.cities.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.cities.is_empty()
+    }
+
+    /// 查找坐标所在的地点，附近没有城市时只包含坐标
+    pub fn locate(&self, position: GpsPosition) -> Location {
+        let mut location = Location {
+            position: Some(position),
+            ..Location::default()
+        };
+        if let Some(city) = self.nearest(&position) {
+            location.country = Some(city.country.clone());
+            location.region = city.region.clone();
+            location.city = Some(city.name.clone());
+        }
+        location
+    }
+
+    /// 最大距离内最近的城市，只检查纬度差在范围内的城市
+    fn nearest(&self, position: &GpsPosition) -> Option<&City> {
+        let band = self.max_distance_km / KM_PER_DEGREE;
+        let start = self
+            .cities
+            .partition_point(|c| c.position.latitude < position.latitude - band);
+        self.cities[start..]
+            .iter()
+            .take_while(|c| c.position.latitude <= position.latitude + band)
+            .map(|c| (c, c.position.distance_km(position)))
+            .filter(|(_, distance)| *distance <= self.max_distance_km)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(city, _)| city)
+    }
+}
+
+/// 解析城市数据，跳过格式不正确的行
+fn parse_cities<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    regions: &HashMap<String, String>,
+) -> Vec<City> {
+    lines
+        .filter_map(|line| {
+            let columns: Vec<&str> = line.split('\t').collect();
+            let column = |i: usize| columns.get(i).map(|c| c.trim()).filter(|c| !c.is_empty());
+            let name = column(NAME_COLUMN)?;
+            let country = column(COUNTRY_COLUMN)?;
+            let position = GpsPosition {
+                latitude: column(LATITUDE_COLUMN)?.parse().ok()?,
+                longitude: column(LONGITUDE_COLUMN)?.parse().ok()?,
+            };
+            let region = column(ADMIN1_COLUMN).map(|code| {
+                regions
+                    .get(&format!("{}.{}", country, code))
+                    .cloned()
+                    .unwrap_or_else(|| code.to_string())
+            });
+            Some(City {
+                name: name.to_string(),
+                position,
+                country: country.to_string(),
+                region,
+            })
+        })
+        .collect()
+}
+
+/// 读取省/州名称表：`国家代码.省州代码` -> 名称
+fn read_admin1_codes(path: &Path) -> Result<HashMap<String, String>> {
+    let content = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read admin1 codes {:?}", path))?;
+    Ok(content
+        .lines()
+        .filter_map(|line| {
+            let mut columns = line.split('\t');
+            let code = columns.next()?.trim();
+            let name = columns.next()?.trim();
+            (!code.is_empty() && !name.is_empty()).then(|| (code.to_string(), name.to_string()))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn position(latitude: f64, longitude: f64) -> GpsPosition {
+        GpsPosition {
+            latitude,
+            longitude,
+        }
+    }
+
+    #[test]
+    fn test_bundled_geocoder_finds_nearest_city() {
+        let geocoder = Geocoder::bundled();
+        assert!(!geocoder.is_empty());
+
+        // 埃菲尔铁塔
+        let location = geocoder.locate(position(48.8584, 2.2945));
+        assert_eq!(location.city.as_deref(), Some("Paris"));
+        assert_eq!(location.country.as_deref(), Some("FR"));
+        assert_eq!(location.region.as_deref(), Some("Île-de-France"));
+
+        // 太平洋中部，附近没有城市
+        let location = geocoder.locate(position(0.0, -140.0));
+        assert_eq!(location.city, None);
+        let values = location.template_values();
+        assert_eq!(values[3], ("lat", Some("0.00".to_string())));
+        assert_eq!(values[4], ("lon", Some("-140.00".to_string())));
+    }
+
+    #[test]
+    fn test_load_geonames_dataset() {
+        let temp_dir = TempDir::new().unwrap();
+        let cities = temp_dir.path().join("cities500.txt");
+        let admin1 = temp_dir.path().join("admin1CodesASCII.txt");
+        let row = |id, name: &str, lat, lon, cc, admin1: &str| {
+            format!(
+                "{id}\t{name}\t{name}\t\t{lat}\t{lon}\tP\tPPL\t{cc}\t\t{admin1}\t\t\t\t1000\t\t10\tEurope/Berlin\t2024-01-01\n"
+            )
+        };
+        fs::write(
+            &cities,
+            row(1, "Garmisch-Partenkirchen", 47.49, 11.10, "DE", "02")
+                + &row(2, "Mittenwald", 47.44, 11.26, "DE", "02")
+                + "broken line\n",
+        )
+        .unwrap();
+        fs::write(&admin1, "DE.02\tBavaria\tBavaria\t2951839\n").unwrap();
+
+        let geocoder = Geocoder::load(&cities, Some(&admin1))
+            .unwrap()
+            .with_max_distance(20.0);
+        assert_eq!(geocoder.len(), 2);
+        let location = geocoder.locate(position(47.45, 11.25));
+        assert_eq!(location.city.as_deref(), Some("Mittenwald"));
+        assert_eq!(location.region.as_deref(), Some("Bavaria"));
+
+        // 没有名称表时使用原始代码
+        let geocoder = Geocoder::load(&cities, None).unwrap();
+        let location = geocoder.locate(position(47.49, 11.10));
+        assert_eq!(location.region.as_deref(), Some("02"));
+
+        assert!(Geocoder::load(&admin1, None).is_err());
+    }
+}
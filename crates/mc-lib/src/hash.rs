@@ -0,0 +1,115 @@
+use anyhow::{Context, Result};
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::Path;
+
+/// 读取文件时使用的缓冲区大小
+pub(crate) const BUFFER_SIZE: usize = 256 * 1024;
+
+/// 计算文件内容的 BLAKE3 哈希（十六进制字符串）
+pub(crate) fn hash_file(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).context(format!("Failed to open file for hashing: {:?}", path))?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+
+    loop {
+        let read = file
+            .read(&mut buffer)
+            .context(format!("Failed to read file for hashing: {:?}", path))?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// 快速哈希每段读取的字节数
+const QUICK_HASH_CHUNK: u64 = 64 * 1024;
+
+/// 计算文件的快速哈希：文件大小加上开头、中间、结尾各一段内容
+///
+/// 只用于快速排除不同的文件，哈希相同并不代表内容完全相同
+pub(crate) fn quick_hash(path: &Path) -> Result<String> {
+    let mut file =
+        File::open(path).context(format!("Failed to open file for hashing: {:?}", path))?;
+    let size = file.metadata()?.len();
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(&size.to_le_bytes());
+
+    let mut buffer = vec![0u8; QUICK_HASH_CHUNK as usize];
+    let middle = size.saturating_sub(QUICK_HASH_CHUNK) / 2;
+    let end = size.saturating_sub(QUICK_HASH_CHUNK);
+    for offset in [0, middle, end] {
+        file.seek(SeekFrom::Start(offset))?;
+        let read = read_full(&mut file, &mut buffer)
+            .context(format!("Failed to read file for hashing: {:?}", path))?;
+        hasher.update(&buffer[..read]);
+    }
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// 逐字节比较两个文件的内容
+pub(crate) fn files_equal(a: &Path, b: &Path) -> Result<bool> {
+    let mut file_a = File::open(a).context(format!("Failed to open file: {:?}", a))?;
+    let mut file_b = File::open(b).context(format!("Failed to open file: {:?}", b))?;
+    if file_a.metadata()?.len() != file_b.metadata()?.len() {
+        return Ok(false);
+    }
+
+    let mut buffer_a = vec![0u8; BUFFER_SIZE];
+    let mut buffer_b = vec![0u8; BUFFER_SIZE];
+    loop {
+        let read_a = read_full(&mut file_a, &mut buffer_a)?;
+        let read_b = read_full(&mut file_b, &mut buffer_b)?;
+        if buffer_a[..read_a] != buffer_b[..read_b] {
+            return Ok(false);
+        }
+        if read_a == 0 {
+            return Ok(true);
+        }
+    }
+}
+
+/// 尽量读满缓冲区，返回实际读取的字节数（小于缓冲区长度表示到达文件末尾）
+fn read_full(file: &mut File, buffer: &mut [u8]) -> std::io::Result<usize> {
+    let mut total = 0;
+    while total < buffer.len() {
+        match file.read(&mut buffer[total..])? {
+            0 => break,
+            n => total += n,
+        }
+    }
+    Ok(total)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_quick_hash_and_files_equal() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = temp_dir.path().join("a.nef");
+        let b = temp_dir.path().join("b.nef");
+        let c = temp_dir.path().join("c.nef");
+
+        // 同样大小、只有中间某个字节不同的文件
+        let data = vec![7u8; 1024 * 1024];
+        let mut other = data.clone();
+        other[300 * 1024] = 8;
+        fs::write(&a, &data).unwrap();
+        fs::write(&b, &data).unwrap();
+        fs::write(&c, &other).unwrap();
+
+        assert_eq!(quick_hash(&a).unwrap(), quick_hash(&b).unwrap());
+        assert!(files_equal(&a, &b).unwrap());
+        assert!(!files_equal(&a, &c).unwrap());
+        assert_ne!(hash_file(&a).unwrap(), hash_file(&c).unwrap());
+    }
+}
@@ -0,0 +1,874 @@
+//! 运行日志（journal）、崩溃恢复与撤销
+//!
+//! 每次分类运行都会在目标目录的 `.media-classifier/runs/` 下写入一个
+//! JSON Lines 文件，记录每一次移动/复制以及被清理的空目录，
+//! `undo_run` 可据此将一次运行完整还原。
+//!
+//! 每次传输前都会先写入并同步一条 `intent` 事件（预写日志）。运行被中断时，
+//! 日志中没有 `run_finished`，`recover_run` 根据未完成的 `intent` 判断哪些
+//! 传输正在进行，清理不完整的目标文件，之后可以继续运行或整体回滚。
+
+use anyhow::{Context, Result};
+use chrono::{Local, Utc};
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::hash::hash_file;
+use crate::plan::format_modified;
+use crate::transfer::{TransferMode, transfer_file};
+use crate::utils::relocate;
+
+/// 目标目录下存放程序数据的目录，扫描媒体库时跳过
+pub const DATA_DIR: &str = ".media-classifier";
+
+/// 目标目录下存放运行日志的目录
+pub const JOURNAL_DIR: &str = ".media-classifier/runs";
+
+/// 目标目录下存放被替换文件的目录，按运行 ID 分子目录
+const REPLACED_DIR: &str = ".media-classifier/replaced";
+
+/// 运行日志中的单条事件
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum JournalEvent {
+    /// 运行开始
+    RunStarted {
+        run_id: String,
+        timestamp: String,
+        sources: Vec<PathBuf>,
+        destination: PathBuf,
+        /// 执行的计划文件（`apply` 运行）
+        #[serde(default)]
+        plan: Option<PathBuf>,
+    },
+    /// 本次运行待处理的源文件
+    Queued { files: Vec<PathBuf> },
+    /// 即将传输文件（在传输开始前写入并同步到磁盘）
+    Intent {
+        timestamp: String,
+        source: PathBuf,
+        target: PathBuf,
+        mode: TransferMode,
+        size: u64,
+    },
+    /// 文件已传输到目标位置
+    Transferred {
+        timestamp: String,
+        source: PathBuf,
+        target: PathBuf,
+        mode: TransferMode,
+        size: u64,
+        /// 传输完成后目标文件的修改时间（RFC 3339）
+        modified: Option<String>,
+    },
+    /// 传输失败（不完整的目标文件已被清理）
+    Failed {
+        timestamp: String,
+        source: PathBuf,
+        error: String,
+    },
+    /// 空目录被清理
+    DirRemoved { timestamp: String, path: PathBuf },
+    /// 中断的运行被继续
+    RunResumed { timestamp: String },
+    /// 运行结束，`cancelled` 表示在两个文件之间被取消（剩余文件留在原处，无需恢复）
+    RunFinished {
+        timestamp: String,
+        #[serde(default)]
+        cancelled: bool,
+    },
+    /// 运行已被撤销
+    RunUndone { timestamp: String },
+}
+
+/// 运行日志写入器
+pub struct Journal {
+    run_id: String,
+    path: PathBuf,
+    file: File,
+}
+
+impl Journal {
+    /// 在目标目录下创建新的运行日志，`plan` 为执行的计划文件
+    pub fn create(destination: &Path, sources: &[PathBuf], plan: Option<&Path>) -> Result<Self> {
+        let dir = destination.join(JOURNAL_DIR);
+        fs::create_dir_all(&dir).context("Failed to create journal directory")?;
+
+        // 同一进程在同一毫秒内的多次运行追加序号区分
+        let base = format!(
+            "{}-{}",
+            Local::now().format("%Y%m%d-%H%M%S-%3f"),
+            std::process::id()
+        );
+        let mut attempt = 0;
+        let (run_id, path, file) = loop {
+            let run_id = match attempt {
+                0 => base.clone(),
+                n => format!("{}-{}", base, n),
+            };
+            let path = dir.join(format!("{}.jsonl", run_id));
+            match OpenOptions::new().create_new(true).append(true).open(&path) {
+                Ok(file) => break (run_id, path, file),
+                Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists && attempt < 1000 => {
+                    attempt += 1;
+                },
+                Err(e) => {
+                    return Err(e).context(format!("Failed to create journal file: {:?}", path));
+                },
+            }
+        };
+
+        let mut journal = Journal { run_id, path, file };
+        journal.append(&JournalEvent::RunStarted {
+            run_id: journal.run_id.clone(),
+            timestamp: now(),
+            sources: sources.to_vec(),
+            destination: destination.to_path_buf(),
+            plan: plan.map(Path::to_path_buf),
+        })?;
+        Ok(journal)
+    }
+
+    /// 打开已有的运行日志以继续中断的运行
+    pub fn open(path: &Path) -> Result<Self> {
+        let run_id = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| anyhow::anyhow!("Invalid journal path: {:?}", path))?
+            .to_string();
+        let file = open_for_append(path)?;
+
+        let mut journal = Journal {
+            run_id,
+            path: path.to_path_buf(),
+            file,
+        };
+        journal.append(&JournalEvent::RunResumed { timestamp: now() })?;
+        Ok(journal)
+    }
+
+    /// 运行 ID
+    pub fn run_id(&self) -> &str {
+        &self.run_id
+    }
+
+    /// 日志文件路径
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 被替换的文件在本次运行中的备份位置，保留其在目标目录中的相对路径
+    ///
+    /// 备份通过普通的日志传输完成，撤销时会被移回原位置
+    pub(crate) fn backup_path(&self, target: &Path) -> PathBuf {
+        // 日志位于 <目标目录>/.media-classifier/runs/<run_id>.jsonl
+        let destination = self.path.ancestors().nth(3).unwrap_or(Path::new(""));
+        let backup_dir = destination.join(REPLACED_DIR).join(&self.run_id);
+        relocate(destination, &backup_dir, target)
+    }
+
+    /// 记录本次运行待处理的源文件，中断后据此继续
+    pub fn record_queue(&mut self, files: &[PathBuf]) -> Result<()> {
+        self.append(&JournalEvent::Queued {
+            files: files.to_vec(),
+        })
+    }
+
+    /// 在传输前记录意图，并同步到磁盘
+    pub(crate) fn record_intent(
+        &mut self,
+        source: &Path,
+        target: &Path,
+        mode: TransferMode,
+    ) -> Result<()> {
+        let size = fs::metadata(source)
+            .context("Failed to get source file metadata")?
+            .len();
+        self.append(&JournalEvent::Intent {
+            timestamp: now(),
+            source: source.to_path_buf(),
+            target: target.to_path_buf(),
+            mode,
+            size,
+        })?;
+        self.file.sync_data().context("Failed to sync journal")
+    }
+
+    /// 记录传输完成
+    pub(crate) fn record_transferred(
+        &mut self,
+        source: &Path,
+        target: &Path,
+        mode: TransferMode,
+    ) -> Result<()> {
+        let event = transferred_event(source, target, mode)?;
+        self.append(&event)
+    }
+
+    /// 记录传输失败
+    pub(crate) fn record_failed(&mut self, source: &Path, error: &str) -> Result<()> {
+        self.append(&JournalEvent::Failed {
+            timestamp: now(),
+            source: source.to_path_buf(),
+            error: error.to_string(),
+        })
+    }
+
+    /// 记录被清理的空目录
+    pub fn record_removed_dirs(&mut self, dirs: &[PathBuf]) -> Result<()> {
+        for dir in dirs {
+            self.append(&JournalEvent::DirRemoved {
+                timestamp: now(),
+                path: dir.clone(),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// 标记运行结束
+    pub fn finish(mut self) -> Result<()> {
+        self.append(&JournalEvent::RunFinished {
+            timestamp: now(),
+            cancelled: false,
+        })
+    }
+
+    /// 记录运行已被取消而结束
+    ///
+    /// 被取消的传输已清理不完整的目标文件，日志不需要 `recover_run`，
+    /// 剩余的源文件在下一次运行时重新扫描
+    pub fn finish_cancelled(mut self) -> Result<()> {
+        self.append(&JournalEvent::RunFinished {
+            timestamp: now(),
+            cancelled: true,
+        })
+    }
+
+    fn append(&mut self, event: &JournalEvent) -> Result<()> {
+        append_event(&mut self.file, event)
+    }
+}
+
+fn transferred_event(source: &Path, target: &Path, mode: TransferMode) -> Result<JournalEvent> {
+    let metadata = fs::metadata(target).context("Failed to read transferred file metadata")?;
+    Ok(JournalEvent::Transferred {
+        timestamp: now(),
+        source: source.to_path_buf(),
+        target: target.to_path_buf(),
+        mode,
+        size: metadata.len(),
+        modified: metadata.modified().ok().map(format_modified),
+    })
+}
+
+/// 以追加方式打开日志；中断时最后一行可能不完整，先补上换行
+fn open_for_append(path: &Path) -> Result<File> {
+    let mut file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .open(path)
+        .context(format!("Failed to open journal for writing: {:?}", path))?;
+
+    if file.metadata()?.len() > 0 {
+        let mut last = [0u8; 1];
+        file.seek(SeekFrom::End(-1))?;
+        file.read_exact(&mut last)?;
+        if last[0] != b'\n' {
+            file.write_all(b"\n")?;
+        }
+    }
+    Ok(file)
+}
+
+fn append_event(file: &mut File, event: &JournalEvent) -> Result<()> {
+    let mut line = serde_json::to_string(event)?;
+    line.push('\n');
+    file.write_all(line.as_bytes())
+        .context("Failed to write journal")?;
+    file.flush().context("Failed to flush journal")?;
+    Ok(())
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339()
+}
+
+/// 读取运行日志中的所有事件
+pub fn read_journal(path: &Path) -> Result<Vec<JournalEvent>> {
+    let file = File::open(path).context(format!("Failed to open journal: {:?}", path))?;
+    let mut events = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line.context("Failed to read journal")?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(event) => events.push(event),
+            // 最后一行可能因中断而不完整
+            Err(e) => warn!(
+                "Ignoring invalid journal line {} in {:?}: {}",
+                index + 1,
+                path,
+                e
+            ),
+        }
+    }
+    Ok(events)
+}
+
+/// 运行摘要
+#[derive(Debug, Clone)]
+pub struct RunSummary {
+    pub run_id: String,
+    pub path: PathBuf,
+    pub started_at: Option<String>,
+    pub transferred: usize,
+    pub finished: bool,
+    /// 运行被取消（同时视为已结束）
+    pub cancelled: bool,
+    pub undone: bool,
+}
+
+impl RunSummary {
+    /// 运行是否被中断（既未结束也未撤销）
+    pub fn is_incomplete(&self) -> bool {
+        !self.finished && !self.undone
+    }
+}
+
+/// 列出目标目录下的所有运行（按时间先后排序）
+pub fn list_runs(destination: &Path) -> Result<Vec<RunSummary>> {
+    let dir = destination.join(JOURNAL_DIR);
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+
+    let mut runs = Vec::new();
+    for entry in fs::read_dir(&dir).context("Failed to read journal directory")? {
+        let path = entry?.path();
+        if path.extension().and_then(|e| e.to_str()) != Some("jsonl") {
+            continue;
+        }
+        let run_id = match path.file_stem().and_then(|s| s.to_str()) {
+            Some(stem) => stem.to_string(),
+            None => continue,
+        };
+
+        let mut summary = RunSummary {
+            run_id,
+            path: path.clone(),
+            started_at: None,
+            transferred: 0,
+            finished: false,
+            cancelled: false,
+            undone: false,
+        };
+        for event in read_journal(&path)? {
+            match event {
+                JournalEvent::RunStarted { timestamp, .. } => summary.started_at = Some(timestamp),
+                JournalEvent::Transferred { .. } => summary.transferred += 1,
+                JournalEvent::RunFinished { cancelled, .. } => {
+                    summary.finished = true;
+                    summary.cancelled = cancelled;
+                },
+                JournalEvent::RunUndone { .. } => summary.undone = true,
+                _ => {},
+            }
+        }
+        runs.push(summary);
+    }
+
+    runs.sort_by(|a, b| a.run_id.cmp(&b.run_id));
+    Ok(runs)
+}
+
+/// 根据运行 ID 查找日志文件，`last` 表示最近一次未撤销的运行
+pub fn find_run(destination: &Path, run_id: &str) -> Result<PathBuf> {
+    let runs = list_runs(destination)?;
+    let run = if run_id == "last" {
+        runs.iter().rev().find(|r| !r.undone)
+    } else {
+        runs.iter().find(|r| r.run_id == run_id)
+    };
+    run.map(|r| r.path.clone())
+        .ok_or_else(|| anyhow::anyhow!("Run not found: {}", run_id))
+}
+
+/// 列出目标目录下被中断的运行
+pub fn incomplete_runs(destination: &Path) -> Result<Vec<RunSummary>> {
+    Ok(list_runs(destination)?
+        .into_iter()
+        .filter(RunSummary::is_incomplete)
+        .collect())
+}
+
+/// 中断运行的恢复结果
+#[derive(Debug, Default)]
+pub struct Recovery {
+    /// 运行的源目录
+    pub sources: Vec<PathBuf>,
+    /// 运行的目标目录
+    pub destination: PathBuf,
+    /// 运行执行的计划文件
+    pub plan: Option<PathBuf>,
+    /// 尚未处理的源文件（包括被回退的进行中传输）
+    pub pending: Vec<PathBuf>,
+    /// 中断时实际已完成、补记到日志中的目标文件
+    pub completed: Vec<PathBuf>,
+    /// 已删除的不完整目标文件
+    pub cleaned: Vec<PathBuf>,
+    /// 无法自动判断、需要人工检查的文件
+    pub unresolved: Vec<(PathBuf, String)>,
+}
+
+/// 恢复被中断的运行
+///
+/// 对每个只有 `intent` 没有结果的传输：
+/// - 源文件已不在而目标文件完整：传输已完成，补记 `transferred`
+/// - 源文件和目标文件都在：删除不完整的目标文件，源文件重新排队
+/// - 目标文件不存在：传输尚未开始，源文件重新排队
+pub fn recover_run(journal_path: &Path) -> Result<Recovery> {
+    let events = read_journal(journal_path)?;
+    let mut recovery = Recovery::default();
+    let mut queued = Vec::new();
+    let mut attempted = HashSet::new();
+    let mut in_flight = HashMap::new();
+
+    for event in events {
+        match event {
+            JournalEvent::RunStarted {
+                sources,
+                destination,
+                plan,
+                ..
+            } => {
+                recovery.sources = sources;
+                recovery.destination = destination;
+                recovery.plan = plan;
+            },
+            JournalEvent::Queued { files } => queued.extend(files),
+            JournalEvent::Intent {
+                source,
+                target,
+                mode,
+                size,
+                ..
+            } => {
+                attempted.insert(source.clone());
+                in_flight.insert(source, (target, mode, size));
+            },
+            JournalEvent::Transferred { source, .. } | JournalEvent::Failed { source, .. } => {
+                in_flight.remove(&source);
+            },
+            _ => {},
+        }
+    }
+
+    let mut file = open_for_append(journal_path)?;
+    for (source, (target, mode, size)) in in_flight {
+        match recover_transfer(&source, &target, mode, size) {
+            RecoveredTransfer::Completed => {
+                info!(
+                    "Interrupted transfer had completed: {:?} → {:?}",
+                    source, target
+                );
+                append_event(&mut file, &transferred_event(&source, &target, mode)?)?;
+                recovery.completed.push(target);
+            },
+            RecoveredTransfer::Requeue { cleaned } => {
+                if cleaned {
+                    info!("Removed partial file: {:?}", target);
+                    recovery.cleaned.push(target);
+                }
+                attempted.remove(&source);
+            },
+            RecoveredTransfer::Unresolved(reason) => {
+                warn!(
+                    "Cannot recover transfer {:?} → {:?}: {}",
+                    source, target, reason
+                );
+                recovery.unresolved.push((target, reason));
+            },
+        }
+    }
+
+    recovery.pending = queued
+        .into_iter()
+        .filter(|file| !attempted.contains(file))
+        .collect();
+    Ok(recovery)
+}
+
+enum RecoveredTransfer {
+    Completed,
+    Requeue { cleaned: bool },
+    Unresolved(String),
+}
+
+fn recover_transfer(
+    source: &Path,
+    target: &Path,
+    mode: TransferMode,
+    size: u64,
+) -> RecoveredTransfer {
+    let target_size = fs::metadata(target).ok().map(|m| m.len());
+    match (source.exists(), target_size) {
+        (true, None) => RecoveredTransfer::Requeue { cleaned: false },
+        (false, None) => {
+            RecoveredTransfer::Unresolved("Source and target are both missing".to_string())
+        },
+        (false, Some(len)) if len == size => RecoveredTransfer::Completed,
+        (false, Some(_)) => {
+            RecoveredTransfer::Unresolved("Source is gone and target is incomplete".to_string())
+        },
+        (true, Some(len)) => {
+            // 复制模式下目标完整且内容一致，视为已完成
+            if mode == TransferMode::Copy
+                && len == size
+                && let (Ok(a), Ok(b)) = (hash_file(source), hash_file(target))
+                && a == b
+            {
+                return RecoveredTransfer::Completed;
+            }
+            if len > size {
+                return RecoveredTransfer::Unresolved(
+                    "Target is larger than the source, not removing".to_string(),
+                );
+            }
+            match fs::remove_file(target) {
+                Ok(()) => RecoveredTransfer::Requeue { cleaned: true },
+                Err(e) => {
+                    RecoveredTransfer::Unresolved(format!("Failed to remove partial file: {}", e))
+                },
+            }
+        },
+    }
+}
+
+/// 撤销单个文件的结果
+#[derive(Debug, Clone)]
+pub enum UndoOutcome {
+    /// 文件已移回原位置
+    Restored { from: PathBuf, to: PathBuf },
+    /// 复制产生的副本已删除（源文件仍在原位置）
+    CopyRemoved { path: PathBuf },
+    /// 未撤销
+    Skipped { path: PathBuf, reason: String },
+}
+
+/// 撤销报告
+#[derive(Debug, Default)]
+pub struct UndoReport {
+    pub outcomes: Vec<UndoOutcome>,
+    /// 重新创建的源目录
+    pub recreated_dirs: Vec<PathBuf>,
+    /// 回滚中断运行时删除的不完整目标文件
+    pub cleaned: Vec<PathBuf>,
+}
+
+/// 撤销一次运行
+///
+/// 按相反顺序还原每一次传输；运行之后被修改、删除的目标文件以及
+/// 原位置已被占用的文件不会被处理，而是在报告中列出。
+/// 被中断的运行会先经过 `recover_run` 清理不完整的文件，再整体回滚
+pub fn undo_run(journal_path: &Path) -> Result<UndoReport> {
+    let mut events = read_journal(journal_path)?;
+    if events
+        .iter()
+        .any(|e| matches!(e, JournalEvent::RunUndone { .. }))
+    {
+        anyhow::bail!("Run has already been undone: {:?}", journal_path);
+    }
+
+    let mut report = UndoReport::default();
+    if !events
+        .iter()
+        .any(|e| matches!(e, JournalEvent::RunFinished { .. }))
+    {
+        let recovery = recover_run(journal_path)?;
+        report.cleaned = recovery.cleaned;
+        for (path, reason) in recovery.unresolved {
+            report.outcomes.push(UndoOutcome::Skipped { path, reason });
+        }
+        events = read_journal(journal_path)?;
+    }
+
+    let destination = events.iter().find_map(|e| match e {
+        JournalEvent::RunStarted { destination, .. } => Some(destination.clone()),
+        _ => None,
+    });
+
+    // 先重建被清理的空目录
+    for event in &events {
+        if let JournalEvent::DirRemoved { path, .. } = event
+            && !path.exists()
+        {
+            fs::create_dir_all(path)
+                .context(format!("Failed to recreate directory: {:?}", path))?;
+            info!("Recreated directory: {:?}", path);
+            report.recreated_dirs.push(path.clone());
+        }
+    }
+
+    for event in events.iter().rev() {
+        if let JournalEvent::Transferred {
+            source,
+            target,
+            mode,
+            size,
+            modified,
+            ..
+        } = event
+        {
+            let outcome = undo_transfer(source, target, *mode, *size, modified.as_deref());
+            match &outcome {
+                UndoOutcome::Skipped { path, reason } => {
+                    warn!("Undo skipped {:?}: {}", path, reason)
+                },
+                UndoOutcome::Restored { from, to } => info!("Restored: {:?} → {:?}", from, to),
+                UndoOutcome::CopyRemoved { path } => info!("Removed copy: {:?}", path),
+            }
+            if !matches!(outcome, UndoOutcome::Skipped { .. })
+                && let Some(destination) = &destination
+            {
+                remove_empty_parents(target, destination);
+            }
+            report.outcomes.push(outcome);
+        }
+    }
+
+    let mut file = open_for_append(journal_path)?;
+    append_event(&mut file, &JournalEvent::RunUndone { timestamp: now() })?;
+
+    Ok(report)
+}
+
+/// 删除运行中在目标目录下创建、撤销后变空的父目录
+fn remove_empty_parents(target: &Path, destination: &Path) {
+    let mut dir = target.parent();
+    while let Some(current) = dir {
+        if current == destination || !current.starts_with(destination) {
+            break;
+        }
+        if fs::remove_dir(current).is_err() {
+            break;
+        }
+        dir = current.parent();
+    }
+}
+
+fn undo_transfer(
+    source: &Path,
+    target: &Path,
+    mode: TransferMode,
+    size: u64,
+    modified: Option<&str>,
+) -> UndoOutcome {
+    let skipped = |reason: &str| UndoOutcome::Skipped {
+        path: target.to_path_buf(),
+        reason: reason.to_string(),
+    };
+
+    let metadata = match fs::metadata(target) {
+        Ok(m) => m,
+        Err(_) => return skipped("File no longer exists at target"),
+    };
+    if metadata.len() != size
+        || (modified.is_some()
+            && metadata.modified().ok().map(format_modified).as_deref() != modified)
+    {
+        return skipped("File was changed after the run");
+    }
+
+    if mode == TransferMode::Copy {
+        if !source.exists() {
+            return skipped("Original source is missing, keeping the copy");
+        }
+        return match fs::remove_file(target) {
+            Ok(()) => UndoOutcome::CopyRemoved {
+                path: target.to_path_buf(),
+            },
+            Err(e) => skipped(&format!("Failed to remove copy: {}", e)),
+        };
+    }
+
+    if source.exists() {
+        return skipped("Original location is occupied");
+    }
+    match transfer_file(target, source, TransferMode::Move) {
+        Ok(()) => UndoOutcome::Restored {
+            from: target.to_path_buf(),
+            to: source.to_path_buf(),
+        },
+        Err(e) => skipped(&format!("Failed to move back: {}", e)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_undo_restores_moves_and_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let source_dir = temp_dir.path().join("card/DCIM");
+        let library = temp_dir.path().join("library");
+        fs::create_dir_all(&source_dir).unwrap();
+        let source = source_dir.join("a.jpg");
+        let changed_source = source_dir.join("b.jpg");
+        fs::write(&source, b"a").unwrap();
+        fs::write(&changed_source, b"b").unwrap();
+
+        let mut journal = Journal::create(&library, &[temp_dir.path().join("card")], None).unwrap();
+        for (from, name) in [(&source, "a.jpg"), (&changed_source, "b.jpg")] {
+            let to = library.join("Photos").join(name);
+            journal
+                .record_intent(from, &to, TransferMode::Move)
+                .unwrap();
+            transfer_file(from, &to, TransferMode::Move).unwrap();
+            journal
+                .record_transferred(from, &to, TransferMode::Move)
+                .unwrap();
+        }
+        let removed = crate::utils::remove_empty_dirs(
+            temp_dir.path().join("card"),
+            &mut crate::observer::NoopObserver,
+        )
+        .unwrap();
+        assert_eq!(removed, vec![source_dir.clone()]);
+        journal.record_removed_dirs(&removed).unwrap();
+        let journal_path = journal.path().to_path_buf();
+        journal.finish().unwrap();
+
+        // 运行后修改的文件不会被还原
+        fs::write(library.join("Photos/b.jpg"), b"edited").unwrap();
+
+        let report = undo_run(&journal_path).unwrap();
+        assert_eq!(report.recreated_dirs, vec![source_dir.clone()]);
+        assert!(source.exists());
+        assert!(!changed_source.exists());
+        assert!(library.join("Photos/b.jpg").exists());
+        assert!(report.outcomes.iter().any(
+            |o| matches!(o, UndoOutcome::Skipped { reason, .. } if reason.contains("changed"))
+        ));
+
+        // 同一运行不能撤销两次
+        assert!(undo_run(&journal_path).is_err());
+        let runs = list_runs(&library).unwrap();
+        assert_eq!(runs.len(), 1);
+        assert!(runs[0].undone);
+    }
+
+    #[test]
+    fn test_runs_created_back_to_back_get_distinct_ids() {
+        let temp_dir = TempDir::new().unwrap();
+        let sources = [temp_dir.path().to_path_buf()];
+        let ids: HashSet<_> = (0..5)
+            .map(|_| {
+                Journal::create(temp_dir.path(), &sources, None)
+                    .unwrap()
+                    .run_id()
+                    .to_string()
+            })
+            .collect();
+        assert_eq!(ids.len(), 5);
+        assert_eq!(list_runs(temp_dir.path()).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn test_cancelled_run_is_not_incomplete() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("card/a.jpg");
+        fs::create_dir_all(source.parent().unwrap()).unwrap();
+        fs::write(&source, b"photo").unwrap();
+        let target = temp_dir.path().join("JPG/a.jpg");
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+
+        let mut journal =
+            Journal::create(temp_dir.path(), &[temp_dir.path().to_path_buf()], None).unwrap();
+        journal
+            .record_intent(&source, &target, TransferMode::Move)
+            .unwrap();
+        transfer_file(&source, &target, TransferMode::Move).unwrap();
+        journal
+            .record_transferred(&source, &target, TransferMode::Move)
+            .unwrap();
+        // 被取消的传输只有 intent，不完整的目标文件已由传输本身清理
+        let pending = temp_dir.path().join("card/b.jpg");
+        fs::write(&pending, b"next").unwrap();
+        journal
+            .record_intent(
+                &pending,
+                &temp_dir.path().join("JPG/b.jpg"),
+                TransferMode::Copy,
+            )
+            .unwrap();
+        let path = journal.path().to_path_buf();
+        journal.finish_cancelled().unwrap();
+
+        let runs = list_runs(temp_dir.path()).unwrap();
+        assert!(runs[0].finished && runs[0].cancelled);
+        assert!(incomplete_runs(temp_dir.path()).unwrap().is_empty());
+
+        // 已取消的运行可以直接撤销
+        undo_run(&path).unwrap();
+        assert_eq!(fs::read(&source).unwrap(), b"photo");
+        assert_eq!(fs::read(&pending).unwrap(), b"next");
+    }
+
+    #[test]
+    fn test_recover_interrupted_run() {
+        let temp_dir = TempDir::new().unwrap();
+        let card = temp_dir.path().join("card");
+        let library = temp_dir.path().join("library");
+        fs::create_dir_all(&card).unwrap();
+        let files: Vec<PathBuf> = ["done.jpg", "partial.jpg", "todo.jpg"]
+            .iter()
+            .map(|name| card.join(name))
+            .collect();
+        for file in &files {
+            fs::write(file, b"image data").unwrap();
+        }
+
+        let mut journal = Journal::create(&library, std::slice::from_ref(&card), None).unwrap();
+        journal.record_queue(&files).unwrap();
+
+        let done = library.join("done.jpg");
+        journal
+            .record_intent(&files[0], &done, TransferMode::Move)
+            .unwrap();
+        transfer_file(&files[0], &done, TransferMode::Move).unwrap();
+
+        // 模拟复制到一半时进程被杀
+        let partial = library.join("partial.jpg");
+        journal
+            .record_intent(&files[1], &partial, TransferMode::Move)
+            .unwrap();
+        fs::write(&partial, b"image").unwrap();
+        let journal_path = journal.path().to_path_buf();
+        drop(journal);
+
+        assert_eq!(incomplete_runs(&library).unwrap().len(), 1);
+
+        let recovery = recover_run(&journal_path).unwrap();
+        assert_eq!(recovery.completed, vec![done.clone()]);
+        assert_eq!(recovery.cleaned, vec![partial.clone()]);
+        assert!(!partial.exists());
+        assert_eq!(recovery.pending, files[1..].to_vec());
+
+        // 继续运行后可以正常结束
+        let journal = Journal::open(&journal_path).unwrap();
+        journal.finish().unwrap();
+        assert!(incomplete_runs(&library).unwrap().is_empty());
+
+        // 补记的传输也能被撤销
+        undo_run(&journal_path).unwrap();
+        assert!(files[0].exists());
+        assert!(!done.exists());
+    }
+}
@@ -3,9 +3,12 @@
 //! 这个库提供了媒体文件分类的核心功能，包括：
 //! - 配置管理
 //! - 媒体类型识别
-//! - 元数据提取
+//! - 元数据提取（EXIF、视频容器、音频标签）
 //! - 规则匹配
-//! - 文件分类
+//! - 文件分类、冲突处理和执行计划
+//! - 运行日志、撤销和中断运行的恢复
+//! - 重复文件和相似图片查找
+//! - 离线反向地理编码和自定义地点
 //!
 //! # 使用示例
 //!
@@ -20,23 +23,74 @@
 //! let result = classify_file_with_config(&config, &target_dir, &source);
 //! ```
 
+mod asset_group;
+mod audio_tags;
+mod cancel;
 mod classifier;
 mod config;
 mod conflict;
+mod dedupe;
+mod event;
 mod filter;
+mod geocoder;
+mod hash;
+mod journal;
 mod media_types;
 mod metadata;
+mod observer;
+mod pipeline;
+mod place;
+mod plan;
+mod roots;
 mod rule_matcher;
+mod scanner;
+mod sequence;
+mod sidecar;
+mod similar;
+mod transfer;
 mod utils;
 
 // Re-export public items
-pub use classifier::{ClassifyResult, classify_file, classify_file_with_config};
-pub use config::{Config, ExcludeConfig, FileSize, FileSizeFilter, GlobalConfig, Rule};
+pub use asset_group::{AssetKind, GroupMember};
+pub use audio_tags::{AudioTags, read_audio_tags};
+pub use cancel::{CancelToken, Cancelled};
+pub use classifier::{
+    ClassifyResult, Planner, classify_file, classify_file_with_config, classify_file_with_journal,
+    execute_plan_entry, execute_plan_entry_with_journal, run_plan,
+};
+pub use config::{
+    AssetGroupConfig, Config, DedupeConfig, EventConfig, ExcludeConfig, FileSize, FileSizeFilter,
+    GeocodingConfig, GlobalConfig, Place, PlaceArea, Rule, ScanConfig, SequenceConfig,
+    SidecarConfig, SimilarConfig,
+};
+pub use conflict::{CompareStrategy, ConflictPolicy};
+pub use dedupe::{
+    DedupeAction, DuplicateGroup, KeepStrategy, LibraryIndex, find_duplicate_groups,
+    remove_duplicate,
+};
+pub use event::EventSpan;
 pub use filter::FileFilter;
+pub use geocoder::{Geocoder, Location};
+pub use journal::{
+    DATA_DIR, JOURNAL_DIR, Journal, JournalEvent, Recovery, RunSummary, UndoOutcome, UndoReport,
+    find_run, incomplete_runs, list_runs, read_journal, recover_run, undo_run,
+};
 pub use media_types::{
-    MediaInfo, MediaType, get_media_info, is_audio_extension, is_image_extension,
+    MediaInfo, MediaType, get_media_info, is_audio_extension, is_image_extension, is_raw_extension,
     is_video_extension,
 };
-pub use metadata::{extract_date, format_date};
-pub use rule_matcher::RuleMatcher;
+pub use metadata::{
+    DateSource, ExifMetadata, GpsPosition, extract_date, extract_date_with_source,
+    extract_exif_metadata, extract_gps, format_date,
+};
+pub use observer::{NoopObserver, Observer, Progress};
+pub use pipeline::default_jobs;
+pub use plan::{PLAN_VERSION, Plan, PlanEntry, PlannedAction};
+pub use roots::Roots;
+pub use rule_matcher::{BatchContext, RuleMatcher};
+pub use scanner::{ScanResult, Scanner, SkipReason, SkippedDir};
+pub use sequence::SequenceRef;
+pub use sidecar::Sidecar;
+pub use similar::{SimilarImage, SimilarIndex, find_similar_clusters};
+pub use transfer::{TransferMode, transfer_file, transfer_file_with_cancel};
 pub use utils::remove_empty_dirs;
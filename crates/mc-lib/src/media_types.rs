@@ -1,5 +1,9 @@
 use std::path::Path;
 
+use crate::audio_tags::AudioTags;
+use crate::geocoder::Location;
+use crate::metadata::ExifMetadata;
+
 /// 媒体文件类型
 #[derive(Debug, Clone, PartialEq)]
 pub enum MediaType {
@@ -13,6 +17,12 @@ pub enum MediaType {
 pub struct MediaInfo {
     pub media_type: MediaType,
     pub extension: String, // 大写形式，如 "JPG"
+    /// 音频标签，只在规则需要时读取
+    pub tags: Option<AudioTags>,
+    /// 图片的 EXIF 相机和镜头信息，只在规则需要时读取
+    pub exif: Option<ExifMetadata>,
+    /// 图片的拍摄地点，只在规则需要时读取
+    pub location: Option<Location>,
 }
 
 /// 根据文件路径获取媒体信息
@@ -23,16 +33,25 @@ pub fn get_media_info(path: &Path) -> Option<MediaInfo> {
         Some(MediaInfo {
             media_type: MediaType::Image,
             extension: extension.to_uppercase(),
+            tags: None,
+            exif: None,
+            location: None,
         })
     } else if is_video_extension(&extension) {
         Some(MediaInfo {
             media_type: MediaType::Video,
             extension: extension.to_uppercase(),
+            tags: None,
+            exif: None,
+            location: None,
         })
     } else if is_audio_extension(&extension) {
         Some(MediaInfo {
             media_type: MediaType::Audio,
             extension: extension.to_uppercase(),
+            tags: None,
+            exif: None,
+            location: None,
         })
     } else {
         None
@@ -44,8 +63,14 @@ pub fn is_image_extension(ext: &str) -> bool {
     matches!(
         ext,
         // 常规图片格式
-        "jpg" | "jpeg" | "png" | "gif" | "tiff" | "tif" | "bmp" | "webp" | "heic" | "heif" |
-        // RAW 格式
+        "jpg" | "jpeg" | "png" | "gif" | "tiff" | "tif" | "bmp" | "webp" | "heic" | "heif"
+    ) || is_raw_extension(ext)
+}
+
+/// 检查是否为 RAW 图片文件扩展名
+pub fn is_raw_extension(ext: &str) -> bool {
+    matches!(
+        ext,
         "nef" | "nrw" |  // Nikon
         "cr2" | "cr3" | "crw" |  // Canon
         "arw" | "srf" | "sr2" |  // Sony
@@ -113,6 +138,8 @@ mod tests {
         assert!(is_image_extension("nef"));
         assert!(is_image_extension("cr2"));
         assert!(!is_image_extension("mp4"));
+        assert!(is_raw_extension("nef"));
+        assert!(!is_raw_extension("jpg"));
     }
 
     #[test]
@@ -1,19 +1,56 @@
 use anyhow::{Context, Result};
-use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use chrono::{DateTime, Local, NaiveDateTime, TimeDelta, TimeZone};
 use log::{debug, warn};
+use serde::{Deserialize, Serialize};
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Cursor, Read, Seek, SeekFrom};
 use std::path::Path;
 
+use crate::media_types::{MediaType, get_media_info};
+
+/// 日期来源
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateSource {
+    /// EXIF 拍摄时间
+    Exif,
+    /// 视频容器中的创建时间（MP4/MOV、Matroska、AVI）
+    Container,
+    /// 文件系统创建时间
+    FileCreated,
+    /// 文件系统修改时间
+    FileModified,
+}
+
+impl DateSource {
+    /// 用于显示的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            DateSource::Exif => "EXIF",
+            DateSource::Container => "container",
+            DateSource::FileCreated => "file created",
+            DateSource::FileModified => "file modified",
+        }
+    }
+}
+
 /// 从文件中提取日期
-/// 图片文件优先使用 EXIF 数据，其他文件使用文件系统时间
+/// 图片文件优先使用 EXIF 数据，视频优先使用容器中的创建时间，其他文件使用文件系统时间
 pub fn extract_date(path: &Path, is_image: bool) -> Result<DateTime<Local>> {
+    extract_date_with_source(path, is_image).map(|(date, _)| date)
+}
+
+/// 从文件中提取日期，同时返回日期来源
+pub fn extract_date_with_source(
+    path: &Path,
+    is_image: bool,
+) -> Result<(DateTime<Local>, DateSource)> {
     if is_image {
         // 尝试从 EXIF 提取日期
         match extract_exif_date(path) {
             Ok(date) => {
                 debug!("Extracted EXIF date for {:?}: {}", path, date);
-                return Ok(date);
+                return Ok((date, DateSource::Exif));
             },
             Err(e) => {
                 warn!(
@@ -22,6 +59,17 @@ pub fn extract_date(path: &Path, is_image: bool) -> Result<DateTime<Local>> {
                 );
             },
         }
+    } else {
+        // 尝试从视频容器提取日期
+        match extract_container_date(path) {
+            Ok(date) => {
+                debug!("Extracted container date for {:?}: {}", path, date);
+                return Ok((date, DateSource::Container));
+            },
+            Err(e) => {
+                debug!("No container date for {:?}: {}", path, e);
+            },
+        }
     }
 
     // 回退到文件系统时间
@@ -30,60 +78,691 @@ pub fn extract_date(path: &Path, is_image: bool) -> Result<DateTime<Local>> {
 
 /// 从 EXIF 数据中提取日期
 fn extract_exif_date(path: &Path) -> Result<DateTime<Local>> {
-    let file = File::open(path).context("Failed to open file for EXIF reading")?;
-    let mut bufreader = BufReader::new(&file);
+    extract_exif_metadata(path)?
+        .date
+        .context("No valid date found in EXIF data")
+}
 
-    let exifreader = exif::Reader::new();
-    let exif = exifreader
-        .read_from_container(&mut bufreader)
-        .context("Failed to read EXIF data")?;
+/// EXIF 中的拍摄时间和拍摄设备信息
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ExifMetadata {
+    /// 拍摄时间（`DateTimeOriginal`，没有时为 `DateTime`）
+    pub date: Option<DateTime<Local>>,
+    /// 相机厂商
+    pub make: Option<String>,
+    /// 相机型号
+    pub model: Option<String>,
+    /// 镜头型号
+    pub lens: Option<String>,
+    /// 机身序列号
+    pub serial: Option<String>,
+    /// 感光度
+    pub iso: Option<u32>,
+    /// 焦距（毫米）
+    pub focal_length: Option<f64>,
+    /// 处理软件或相机固件
+    pub software: Option<String>,
+}
 
-    // 优先使用 DateTimeOriginal（拍摄时间）
-    if let Some(field) = exif.get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)
-        && let Some(date) = parse_exif_datetime(&field.display_value().to_string())
-    {
-        return Ok(date);
+impl ExifMetadata {
+    /// 模板变量名和值，焦距不带单位；文件中没有的值为 None
+    pub fn template_values(&self) -> [(&'static str, Option<String>); 7] {
+        let focal_length = self.focal_length.map(|mm| {
+            if mm.fract() == 0.0 {
+                format!("{:.0}", mm)
+            } else {
+                format!("{:.1}", mm)
+            }
+        });
+        [
+            ("make", self.make.clone()),
+            ("model", self.model.clone()),
+            ("lens", self.lens.clone()),
+            ("serial", self.serial.clone()),
+            ("iso", self.iso.map(|iso| iso.to_string())),
+            ("focal_length", focal_length),
+            ("software", self.software.clone()),
+        ]
     }
+}
 
-    // 其次使用 DateTime（修改时间）
-    if let Some(field) = exif.get_field(exif::Tag::DateTime, exif::In::PRIMARY)
-        && let Some(date) = parse_exif_datetime(&field.display_value().to_string())
-    {
-        return Ok(date);
-    }
+/// 读取 EXIF 中的拍摄时间、相机、镜头等信息
+pub fn extract_exif_metadata(path: &Path) -> Result<ExifMetadata> {
+    let file = File::open(path).context("Failed to open file for EXIF reading")?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(&file))
+        .context("Failed to read EXIF data")?;
+
+    let field = |tag| exif.get_field(tag, exif::In::PRIMARY);
+    let text = |tag| match &field(tag)?.value {
+        exif::Value::Ascii(values) => values
+            .iter()
+            .map(|v| {
+                String::from_utf8_lossy(v)
+                    .trim_matches(char::from(0))
+                    .trim()
+                    .to_string()
+            })
+            .find(|v| !v.is_empty()),
+        _ => None,
+    };
+    // 优先使用 DateTimeOriginal（拍摄时间），其次使用 DateTime（修改时间）
+    let date = [exif::Tag::DateTimeOriginal, exif::Tag::DateTime]
+        .into_iter()
+        .find_map(|tag| parse_exif_datetime(&field(tag)?.display_value().to_string()));
+    let focal_length = field(exif::Tag::FocalLength).and_then(|f| match &f.value {
+        exif::Value::Rational(values) => values.first().map(|v| v.to_f64()),
+        _ => None,
+    });
 
-    anyhow::bail!("No valid date found in EXIF data")
+    Ok(ExifMetadata {
+        date,
+        make: text(exif::Tag::Make),
+        model: text(exif::Tag::Model),
+        lens: text(exif::Tag::LensModel),
+        serial: text(exif::Tag::BodySerialNumber),
+        iso: field(exif::Tag::PhotographicSensitivity).and_then(|f| f.value.get_uint(0)),
+        focal_length: focal_length.filter(|mm| mm.is_finite() && *mm > 0.0),
+        software: text(exif::Tag::Software),
+    })
 }
 
 /// 解析 EXIF 日期时间字符串
 /// EXIF 格式: "2025:11:18 14:30:45" 或 "2025-11-18 14:30:45"
 fn parse_exif_datetime(datetime_str: &str) -> Option<DateTime<Local>> {
+    let naive = parse_exif_naive(datetime_str)?;
+    Local.from_local_datetime(&naive).single()
+}
+
+fn parse_exif_naive(datetime_str: &str) -> Option<NaiveDateTime> {
     // 移除可能的引号
     let datetime_str = datetime_str.trim_matches('"').trim();
 
     // 尝试解析 "YYYY:MM:DD HH:MM:SS" 格式
     if let Ok(naive) = NaiveDateTime::parse_from_str(datetime_str, "%Y:%m:%d %H:%M:%S") {
-        return Local.from_local_datetime(&naive).single();
+        return Some(naive);
     }
 
     // 尝试解析 "YYYY-MM-DD HH:MM:SS" 格式
-    if let Ok(naive) = NaiveDateTime::parse_from_str(datetime_str, "%Y-%m-%d %H:%M:%S") {
-        return Local.from_local_datetime(&naive).single();
+    NaiveDateTime::parse_from_str(datetime_str, "%Y-%m-%d %H:%M:%S").ok()
+}
+
+/// 图片的拍摄信息，用于识别连拍和包围曝光
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct CaptureInfo {
+    /// 拍摄时间，包含 `SubSecTimeOriginal` 中的亚秒部分
+    pub time: NaiveDateTime,
+    /// 曝光补偿（EV）
+    pub exposure_bias: Option<f64>,
+}
+
+/// 从 EXIF 中读取拍摄时间（精确到毫秒）和曝光补偿
+pub(crate) fn extract_capture_info(path: &Path) -> Result<CaptureInfo> {
+    let file = File::open(path).context("Failed to open file for EXIF reading")?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(&file))
+        .context("Failed to read EXIF data")?;
+
+    let time = exif
+        .get_field(exif::Tag::DateTimeOriginal, exif::In::PRIMARY)
+        .and_then(|field| parse_exif_naive(&field.display_value().to_string()))
+        .context("No DateTimeOriginal in EXIF data")?;
+    let millis = exif
+        .get_field(exif::Tag::SubSecTimeOriginal, exif::In::PRIMARY)
+        .map(|field| parse_subsec_millis(&field.display_value().to_string()))
+        .unwrap_or(0);
+    let exposure_bias = exif
+        .get_field(exif::Tag::ExposureBiasValue, exif::In::PRIMARY)
+        .and_then(|field| match &field.value {
+            exif::Value::SRational(values) => values.first().map(|v| v.to_f64()),
+            exif::Value::Rational(values) => values.first().map(|v| v.to_f64()),
+            _ => None,
+        });
+
+    Ok(CaptureInfo {
+        time: time + TimeDelta::milliseconds(millis),
+        exposure_bias,
+    })
+}
+
+/// GPS 坐标（十进制度数，南纬和西经为负）
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize, Serialize)]
+pub struct GpsPosition {
+    pub latitude: f64,
+    pub longitude: f64,
+}
+
+impl GpsPosition {
+    /// 两点间的大圆距离（公里）
+    pub fn distance_km(&self, other: &GpsPosition) -> f64 {
+        const EARTH_RADIUS_KM: f64 = 6371.0;
+        let (lat1, lat2) = (self.latitude.to_radians(), other.latitude.to_radians());
+        let d_lat = lat2 - lat1;
+        let d_lon = (other.longitude - self.longitude).to_radians();
+        let a = (d_lat / 2.0).sin().powi(2) + lat1.cos() * lat2.cos() * (d_lon / 2.0).sin().powi(2);
+        2.0 * EARTH_RADIUS_KM * a.sqrt().asin()
+    }
+}
+
+/// 从 EXIF 中读取 GPS 坐标
+pub fn extract_gps(path: &Path) -> Result<GpsPosition> {
+    let file = File::open(path).context("Failed to open file for EXIF reading")?;
+    let exif = exif::Reader::new()
+        .read_from_container(&mut BufReader::new(&file))
+        .context("Failed to read EXIF data")?;
+
+    let coordinate = |tag, ref_tag, negative: &str| {
+        let degrees = match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+            exif::Value::Rational(v) if v.len() >= 3 => {
+                v[0].to_f64() + v[1].to_f64() / 60.0 + v[2].to_f64() / 3600.0
+            },
+            _ => return None,
+        };
+        let reference = exif
+            .get_field(ref_tag, exif::In::PRIMARY)
+            .map(|field| field.display_value().to_string())
+            .unwrap_or_default();
+        let sign = if reference.contains(negative) {
+            -1.0
+        } else {
+            1.0
+        };
+        Some(sign * degrees).filter(|value| value.is_finite())
+    };
+    let latitude = coordinate(exif::Tag::GPSLatitude, exif::Tag::GPSLatitudeRef, "S")
+        .context("No GPS latitude in EXIF data")?;
+    let longitude = coordinate(exif::Tag::GPSLongitude, exif::Tag::GPSLongitudeRef, "W")
+        .context("No GPS longitude in EXIF data")?;
+    Ok(GpsPosition {
+        latitude,
+        longitude,
+    })
+}
+
+/// 读取实况照片的 Apple 内容标识符，同一张实况照片的图片和视频相同
+///
+/// 图片（HEIC/JPEG）从 Apple MakerNote 的 0x0011 标签读取，
+/// 视频（MOV）从 QuickTime 元数据的 `content.identifier` 读取；没有时返回 None
+pub(crate) fn extract_content_identifier(path: &Path) -> Option<String> {
+    let mut file = File::open(path).ok()?;
+    let id = match get_media_info(path)?.media_type {
+        MediaType::Image => {
+            let exif = exif::Reader::new()
+                .read_from_container(&mut BufReader::new(&file))
+                .ok()?;
+            match &exif
+                .get_field(exif::Tag::MakerNote, exif::In::PRIMARY)?
+                .value
+            {
+                exif::Value::Undefined(maker_note, _) => apple_content_identifier(maker_note),
+                _ => None,
+            }
+        },
+        MediaType::Video => {
+            let mut magic = [0u8; 8];
+            file.read_exact(&mut magic).ok()?;
+            if !matches!(
+                &magic[4..8],
+                b"ftyp" | b"moov" | b"mdat" | b"wide" | b"free" | b"skip"
+            ) {
+                return None;
+            }
+            quicktime_content_identifier(&mut file)
+        },
+        MediaType::Audio => None,
+    };
+    id.filter(|id| !id.is_empty())
+}
+
+/// Apple MakerNote：`Apple iOS` 头之后是大端序 IFD，偏移相对于 MakerNote 开头
+fn apple_content_identifier(maker_note: &[u8]) -> Option<String> {
+    const HEADER: &[u8] = b"Apple iOS\0";
+    const IFD_START: usize = 14;
+    const CONTENT_IDENTIFIER: u16 = 0x0011;
+    if !maker_note.starts_with(HEADER) || maker_note.get(12..14)? != b"MM" {
+        return None;
+    }
+
+    let be_u16 = |at: usize| {
+        Some(u16::from_be_bytes(
+            maker_note.get(at..at + 2)?.try_into().ok()?,
+        ))
+    };
+    let count = be_u16(IFD_START)? as usize;
+    (0..count).find_map(|i| {
+        let entry = IFD_START + 2 + i * 12;
+        // 只接受 ASCII（类型 2），不超过 4 字节时值直接存放在条目中
+        if be_u16(entry)? != CONTENT_IDENTIFIER || be_u16(entry + 2)? != 2 {
+            return None;
+        }
+        let len = be_u32(maker_note, entry + 4)? as usize;
+        let start = if len <= 4 {
+            entry + 8
+        } else {
+            be_u32(maker_note, entry + 8)? as usize
+        };
+        let value = maker_note.get(start..start.checked_add(len)?)?;
+        let value = std::str::from_utf8(value).ok()?;
+        Some(value.trim_matches(char::from(0)).trim().to_string())
+    })
+}
+
+/// MOV：`moov/meta` 中的 `com.apple.quicktime.content.identifier`
+fn quicktime_content_identifier<R: Read + Seek>(reader: &mut R) -> Option<String> {
+    let moov = find_top_level_box(reader, b"moov").ok()??;
+    let (_, meta) = child_boxes(reader, moov)
+        .ok()?
+        .into_iter()
+        .find(|(kind, _)| kind == b"meta")?;
+    let meta = read_box(reader, meta).ok()?;
+    let value = quicktime_value(&meta, QUICKTIME_CONTENT_IDENTIFIER)?;
+    let value = std::str::from_utf8(value).ok()?;
+    Some(value.trim_matches(char::from(0)).trim().to_string())
+}
+
+/// MP4 时间戳起点（1904-01-01）到 Unix 时间戳起点的秒数
+const MP4_EPOCH_OFFSET: i64 = 2_082_844_800;
+/// 读入内存的单个 box 的最大字节数
+const MAX_BOX_SIZE: u64 = 64 << 20;
+/// QuickTime 元数据中带时区的拍摄时间
+const QUICKTIME_CREATION_DATE: &str = "com.apple.quicktime.creationdate";
+/// QuickTime 元数据中实况照片的内容标识符
+const QUICKTIME_CONTENT_IDENTIFIER: &str = "com.apple.quicktime.content.identifier";
+
+const EBML_HEADER: u64 = 0x1A45_DFA3;
+const MKV_SEGMENT: u64 = 0x1853_8067;
+const MKV_INFO: u64 = 0x1549_A966;
+const MKV_DATE_UTC: u64 = 0x4461;
+const MKV_CLUSTER: u64 = 0x1F43_B675;
+/// Matroska `DateUTC` 起点（2001-01-01）的 Unix 时间戳
+const MKV_EPOCH: i64 = 978_307_200;
+/// 读取的 Matroska Info 元素的最大字节数
+const MAX_MKV_INFO_SIZE: u64 = 1 << 20;
+/// 查找 AVI `IDIT` 时读取的文件头字节数
+const MAX_AVI_HEADER_SIZE: u64 = 1 << 20;
+
+/// 从视频容器中提取创建时间，按文件头识别格式
+fn extract_container_date(path: &Path) -> Result<DateTime<Local>> {
+    let mut file = File::open(path).context("Failed to open file for container reading")?;
+    let mut magic = [0u8; 12];
+    file.read_exact(&mut magic)
+        .context("File too short for a video container")?;
+    file.seek(SeekFrom::Start(0))?;
+
+    if matches!(
+        &magic[4..8],
+        b"ftyp" | b"moov" | b"mdat" | b"wide" | b"free" | b"skip"
+    ) {
+        mp4_creation_date(&mut file)
+    } else if magic[..4] == [0x1A, 0x45, 0xDF, 0xA3] {
+        matroska_date(&mut file)
+    } else if &magic[..4] == b"RIFF" && &magic[8..12] == b"AVI " {
+        avi_date(&mut file)
+    } else {
+        anyhow::bail!("Unsupported container format")
+    }
+}
+
+/// MP4/MOV：优先使用 QuickTime 的 `creationdate`，其次 `mvhd`，最后最早的 `tkhd`
+///
+/// `moov` 中的采样表可能很大，只读入需要的子 box
+fn mp4_creation_date<R: Read + Seek>(reader: &mut R) -> Result<DateTime<Local>> {
+    let moov = find_top_level_box(reader, b"moov")?.context("No moov box found")?;
+    let children = child_boxes(reader, moov)?;
+    let child = |wanted: &[u8; 4]| {
+        children
+            .iter()
+            .find(|(kind, _)| kind == wanted)
+            .map(|(_, range)| *range)
+    };
+
+    if let Some(meta) = child(b"meta")
+        && let Some(date) = quicktime_creation_date(&read_box(reader, meta)?)
+    {
+        return Ok(date);
+    }
+    if let Some(mvhd) = child(b"mvhd")
+        && let Some(date) = mp4_header_time(&read_box(reader, mvhd)?)
+    {
+        return Ok(date);
+    }
+    let mut earliest = None;
+    for (_, trak) in children.iter().filter(|(kind, _)| kind == b"trak") {
+        let tkhd = child_boxes(reader, *trak)?
+            .into_iter()
+            .find(|(kind, _)| kind == b"tkhd");
+        if let Some((_, tkhd)) = tkhd
+            && let Some(date) = mp4_header_time(&read_box(reader, tkhd)?)
+        {
+            earliest = Some(earliest.map_or(date, |e: DateTime<Local>| e.min(date)));
+        }
+    }
+    earliest.context("No creation time in moov box")
+}
+
+/// box 内容在文件中的位置
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct BoxRange {
+    start: u64,
+    end: u64,
+}
+
+/// 按顺序跳过顶层 box，返回指定类型 box 的内容位置
+pub(crate) fn find_top_level_box<R: Read + Seek>(
+    reader: &mut R,
+    wanted: &[u8; 4],
+) -> Result<Option<BoxRange>> {
+    let end = reader.seek(SeekFrom::End(0))?;
+    let mut offset = 0;
+    while let Some((kind, range)) = read_box_header(reader, offset, end)? {
+        if &kind == wanted {
+            return Ok(Some(range));
+        }
+        offset = range.end;
+    }
+    Ok(None)
+}
+
+/// 只读取 box 头，列出 `parent` 中的子 box，遇到不完整的 box 时停止
+pub(crate) fn child_boxes<R: Read + Seek>(
+    reader: &mut R,
+    parent: BoxRange,
+) -> Result<Vec<([u8; 4], BoxRange)>> {
+    let mut children = Vec::new();
+    let mut offset = parent.start;
+    while let Some((kind, range)) = read_box_header(reader, offset, parent.end)? {
+        children.push((kind, range));
+        offset = range.end;
+    }
+    Ok(children)
+}
+
+/// 读取 `offset` 处的 box 头，返回类型和内容位置；到达 `end` 或 box 不完整时返回 None
+fn read_box_header<R: Read + Seek>(
+    reader: &mut R,
+    offset: u64,
+    end: u64,
+) -> Result<Option<([u8; 4], BoxRange)>> {
+    if end.saturating_sub(offset) < 8 {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(offset))?;
+    let mut header = [0u8; 8];
+    reader.read_exact(&mut header)?;
+    let kind = [header[4], header[5], header[6], header[7]];
+
+    // size 为 1 时后跟 64 位长度，为 0 时延伸到末尾
+    let (size, header_len) = match u32::from_be_bytes([header[0], header[1], header[2], header[3]])
+    {
+        0 => (end - offset, 8),
+        1 => {
+            let mut large = [0u8; 8];
+            if reader.read_exact(&mut large).is_err() {
+                return Ok(None);
+            }
+            (u64::from_be_bytes(large), 16)
+        },
+        n => (n as u64, 8),
+    };
+    if size < header_len || size > end - offset {
+        return Ok(None);
+    }
+    let range = BoxRange {
+        start: offset + header_len,
+        end: offset + size,
+    };
+    Ok(Some((kind, range)))
+}
+
+/// 把 box 的内容读入内存
+pub(crate) fn read_box<R: Read + Seek>(reader: &mut R, range: BoxRange) -> Result<Vec<u8>> {
+    let len = range.end - range.start;
+    if len > MAX_BOX_SIZE {
+        anyhow::bail!("Box too large: {} bytes", len);
+    }
+    reader.seek(SeekFrom::Start(range.start))?;
+    let mut payload = vec![0u8; len as usize];
+    reader.read_exact(&mut payload).context("Truncated box")?;
+    Ok(payload)
+}
+
+/// 遍历内存中的子 box，返回 (类型, 内容)
+pub(crate) fn mp4_boxes(data: &[u8]) -> impl Iterator<Item = (&[u8], &[u8])> {
+    let mut rest = data;
+    std::iter::from_fn(move || {
+        let size = be_u32(rest, 0)? as usize;
+        let (size, header_len) = match size {
+            0 => (rest.len(), 8),
+            1 => (usize::try_from(be_u64(rest, 8)?).ok()?, 16),
+            n => (n, 8),
+        };
+        if size < header_len || size > rest.len() {
+            return None;
+        }
+        let (current, next) = rest.split_at(size);
+        rest = next;
+        Some((&current[4..8], &current[header_len..]))
+    })
+}
+
+pub(crate) fn find_box<'a>(data: &'a [u8], wanted: &[u8; 4]) -> Option<&'a [u8]> {
+    mp4_boxes(data)
+        .find(|(kind, _)| kind == wanted)
+        .map(|(_, payload)| payload)
+}
+
+/// `mvhd` / `tkhd` 中的创建时间（UTC），0 表示未设置
+fn mp4_header_time(payload: &[u8]) -> Option<DateTime<Local>> {
+    let seconds = match *payload.first()? {
+        0 => be_u32(payload, 4)? as i64,
+        1 => i64::try_from(be_u64(payload, 4)?).ok()?,
+        _ => return None,
+    };
+    if seconds == 0 {
+        return None;
     }
+    DateTime::from_timestamp(seconds - MP4_EPOCH_OFFSET, 0).map(|t| t.with_timezone(&Local))
+}
+
+/// `moov/meta` 中 `keys` + `ilst` 形式的 QuickTime 元数据
+fn quicktime_creation_date(meta: &[u8]) -> Option<DateTime<Local>> {
+    let value = std::str::from_utf8(quicktime_value(meta, QUICKTIME_CREATION_DATE)?).ok()?;
+    parse_iso8601_local(value)
+}
+
+/// 读取 QuickTime 元数据中键名为 `wanted` 的值
+fn quicktime_value<'a>(meta: &'a [u8], wanted: &str) -> Option<&'a [u8]> {
+    // QuickTime 的 meta 直接包含子 box，ISO 的 meta 前面有版本和标志字段
+    let meta = if meta.get(4..8) == Some(b"hdlr") {
+        meta
+    } else {
+        meta.get(4..)?
+    };
+
+    // keys：版本和标志、条目数，之后每个条目形如 box（命名空间 + 键名），序号从 1 开始
+    let keys = find_box(meta, b"keys")?;
+    let index = mp4_boxes(keys.get(8..)?)
+        .position(|(namespace, key)| namespace == b"mdta" && key == wanted.as_bytes())?
+        as u32
+        + 1;
+
+    // ilst：每个条目的类型是键的序号，内容是 data box（类型、语言，之后是值）
+    let item = mp4_boxes(find_box(meta, b"ilst")?)
+        .find(|(kind, _)| be_u32(kind, 0) == Some(index))
+        .map(|(_, item)| item)?;
+    find_box(item, b"data")?.get(8..)
+}
+
+/// 解析带时区的 ISO 8601 时间，返回拍摄地的当地时间（与 EXIF 时间的含义一致）
+fn parse_iso8601_local(value: &str) -> Option<DateTime<Local>> {
+    let value = value.trim_matches(char::from(0)).trim();
+    let date = DateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S%z")
+        .or_else(|_| DateTime::parse_from_rfc3339(value))
+        .ok()?;
+    Local.from_local_datetime(&date.naive_local()).single()
+}
+
+/// Matroska/WebM：Segment/Info 中的 `DateUTC`
+fn matroska_date<R: Read + Seek>(reader: &mut R) -> Result<DateTime<Local>> {
+    let (id, size) = read_ebml_element(reader)?;
+    if id != EBML_HEADER {
+        anyhow::bail!("Missing EBML header");
+    }
+    let size = size.context("EBML header with unknown size")?;
+    reader.seek(SeekFrom::Current(i64::try_from(size)?))?;
+
+    if read_ebml_element(reader)?.0 != MKV_SEGMENT {
+        anyhow::bail!("Missing Matroska segment");
+    }
+
+    // Info 位于第一个 Cluster 之前
+    loop {
+        let (id, size) = read_ebml_element(reader).context("No Info element found")?;
+        let size = size.context("Element with unknown size before Info")?;
+        match id {
+            MKV_INFO => {
+                if size > MAX_MKV_INFO_SIZE {
+                    anyhow::bail!("Info element too large: {} bytes", size);
+                }
+                let mut info = vec![0u8; size as usize];
+                reader
+                    .read_exact(&mut info)
+                    .context("Truncated Info element")?;
+                return matroska_info_date(&info).context("No DateUTC in Info element");
+            },
+            MKV_CLUSTER => anyhow::bail!("No Info element before the first cluster"),
+            _ => {
+                reader.seek(SeekFrom::Current(i64::try_from(size)?))?;
+            },
+        }
+    }
+}
+
+fn matroska_info_date(info: &[u8]) -> Option<DateTime<Local>> {
+    let mut reader = Cursor::new(info);
+    while let Ok((id, Some(size))) = read_ebml_element(&mut reader) {
+        let start = reader.position() as usize;
+        let value = info.get(start..start.checked_add(size as usize)?)?;
+        if id == MKV_DATE_UTC {
+            // 自 2001-01-01 起的纳秒数（有符号大端整数）
+            let nanos = i64::from_be_bytes(value.try_into().ok()?);
+            let seconds = MKV_EPOCH + nanos.div_euclid(1_000_000_000);
+            let subsec = nanos.rem_euclid(1_000_000_000) as u32;
+            return DateTime::from_timestamp(seconds, subsec).map(|t| t.with_timezone(&Local));
+        }
+        reader.set_position((start + value.len()) as u64);
+    }
+    None
+}
+
+/// 读取 EBML 元素头，返回 (ID, 内容长度)，长度未知时为 None
+fn read_ebml_element<R: Read>(reader: &mut R) -> Result<(u64, Option<u64>)> {
+    let (id, _) = read_vint(reader)?;
+    let (size, len) = read_vint(reader)?;
+    let value_bits = 7 * len as u32;
+    let value = size & ((1u64 << value_bits) - 1);
+    // 数值位全为 1 表示长度未知
+    let size = (value != (1u64 << value_bits) - 1).then_some(value);
+    Ok((id, size))
+}
+
+/// 读取 EBML 变长整数，返回保留长度标记位的原始值和字节数
+fn read_vint<R: Read>(reader: &mut R) -> Result<(u64, usize)> {
+    let mut first = [0u8; 1];
+    reader.read_exact(&mut first)?;
+    let len = first[0].leading_zeros() as usize + 1;
+    if len > 8 {
+        anyhow::bail!("Invalid EBML variable-length integer");
+    }
+    let mut rest = [0u8; 7];
+    reader.read_exact(&mut rest[..len - 1])?;
+    let value = rest[..len - 1]
+        .iter()
+        .fold(first[0] as u64, |value, byte| (value << 8) | *byte as u64);
+    Ok((value, len))
+}
 
+/// AVI：`hdrl` 列表中的 `IDIT` 块（当地时间）
+fn avi_date<R: Read>(reader: &mut R) -> Result<DateTime<Local>> {
+    let mut header = Vec::new();
+    reader.take(MAX_AVI_HEADER_SIZE).read_to_end(&mut header)?;
+    let idit = header
+        .get(12..)
+        .and_then(|chunks| find_riff_chunk(chunks, b"IDIT"))
+        .context("No IDIT chunk found")?;
+    parse_avi_datetime(idit).context("Invalid IDIT date")
+}
+
+/// 在 RIFF 块序列中查找指定块，递归进入 `movi` 以外的 LIST
+fn find_riff_chunk<'a>(data: &'a [u8], wanted: &[u8; 4]) -> Option<&'a [u8]> {
+    let mut rest = data;
+    while rest.len() >= 8 {
+        let id = &rest[..4];
+        let size = u32::from_le_bytes([rest[4], rest[5], rest[6], rest[7]]) as usize;
+        // 只读取了文件头部，块可能被截断
+        let body = &rest[8..8usize.saturating_add(size).min(rest.len())];
+        if id == wanted {
+            return Some(body);
+        }
+        if id == b"LIST"
+            && body.get(..4) != Some(b"movi")
+            && let Some(found) = find_riff_chunk(body.get(4..)?, wanted)
+        {
+            return Some(found);
+        }
+        // 块按偶数字节对齐
+        rest = rest
+            .get(8usize.saturating_add(size).saturating_add(size % 2)..)
+            .unwrap_or(&[]);
+    }
     None
 }
 
+/// IDIT 格式: "TUE NOV 18 14:30:45 2025"，部分相机使用 EXIF 格式
+fn parse_avi_datetime(value: &[u8]) -> Option<DateTime<Local>> {
+    let text = String::from_utf8_lossy(value);
+    let text = text
+        .trim_matches(char::from(0))
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ");
+    let naive = NaiveDateTime::parse_from_str(&text, "%a %b %d %H:%M:%S %Y")
+        .ok()
+        .or_else(|| parse_exif_naive(&text))?;
+    Local.from_local_datetime(&naive).single()
+}
+
+pub(crate) fn be_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn be_u64(data: &[u8], at: usize) -> Option<u64> {
+    Some(u64::from_be_bytes(data.get(at..at + 8)?.try_into().ok()?))
+}
+
+/// 解析 EXIF 的亚秒字符串（小数点后的数字，如 "5" 表示 0.5 秒）为毫秒
+fn parse_subsec_millis(subsec: &str) -> i64 {
+    let digits: String = subsec
+        .trim_matches('"')
+        .trim()
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .chain(std::iter::repeat('0'))
+        .take(3)
+        .collect();
+    digits.parse().unwrap_or(0)
+}
+
 /// 从文件系统元数据中提取日期
 /// 优先使用创建时间，如果不可用则使用修改时间
-fn extract_file_date(path: &Path) -> Result<DateTime<Local>> {
+fn extract_file_date(path: &Path) -> Result<(DateTime<Local>, DateSource)> {
     let metadata = std::fs::metadata(path).context("Failed to read file metadata")?;
 
     // 尝试获取创建时间
     if let Ok(created) = metadata.created() {
         let datetime: DateTime<Local> = created.into();
         debug!("Using file creation time for {:?}: {}", path, datetime);
-        return Ok(datetime);
+        return Ok((datetime, DateSource::FileCreated));
     }
 
     // 回退到修改时间
@@ -92,7 +771,7 @@ fn extract_file_date(path: &Path) -> Result<DateTime<Local>> {
         .context("Failed to get file modified time")?;
     let datetime: DateTime<Local> = modified.into();
     debug!("Using file modified time for {:?}: {}", path, datetime);
-    Ok(datetime)
+    Ok((datetime, DateSource::FileModified))
 }
 
 /// 将日期格式化为 YYYYMMDD 格式
@@ -115,6 +794,368 @@ mod tests {
 
         let date3 = parse_exif_datetime("2025-11-18 14:30:45");
         assert!(date3.is_some());
+
+        assert_eq!(parse_subsec_millis("\"5\""), 500);
+        assert_eq!(parse_subsec_millis("042"), 42);
+        assert_eq!(parse_subsec_millis("12345"), 123);
+        assert_eq!(parse_subsec_millis(""), 0);
+    }
+
+    #[test]
+    fn test_gps_distance() {
+        let paris = GpsPosition {
+            latitude: 48.8566,
+            longitude: 2.3522,
+        };
+        let london = GpsPosition {
+            latitude: 51.5074,
+            longitude: -0.1278,
+        };
+        let distance = paris.distance_km(&london);
+        assert!((340.0..350.0).contains(&distance), "{}", distance);
+        assert_eq!(paris.distance_km(&paris), 0.0);
+    }
+
+    fn mp4_box(kind: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+        let mut data = ((payload.len() + 8) as u32).to_be_bytes().to_vec();
+        data.extend_from_slice(kind);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    /// version 0 的 mvhd/tkhd：版本和标志、创建时间、修改时间
+    fn header_box(kind: &[u8; 4], created: i64) -> Vec<u8> {
+        let mut payload = vec![0u8; 4];
+        payload.extend_from_slice(&((created + MP4_EPOCH_OFFSET) as u32).to_be_bytes());
+        payload.extend_from_slice(&[0u8; 16]);
+        mp4_box(kind, &payload)
+    }
+
+    /// 只含 QuickTime `creationdate` 的 meta box
+    fn quicktime_meta(date: &str) -> Vec<u8> {
+        quicktime_meta_items(&[(QUICKTIME_CREATION_DATE, date)])
+    }
+
+    /// `keys` + `ilst` 形式的 QuickTime meta box
+    fn quicktime_meta_items(items: &[(&str, &str)]) -> Vec<u8> {
+        let mut keys = vec![0, 0, 0, 0];
+        keys.extend_from_slice(&(items.len() as u32).to_be_bytes());
+        let mut ilst = Vec::new();
+        for (index, (key, value)) in items.iter().enumerate() {
+            keys.extend(mp4_box(b"mdta", key.as_bytes()));
+            let data = mp4_box(
+                b"data",
+                &[&[0, 0, 0, 1, 0, 0, 0, 0], value.as_bytes()].concat(),
+            );
+            ilst.extend(mp4_box(&(index as u32 + 1).to_be_bytes(), &data));
+        }
+        mp4_box(
+            b"meta",
+            &[
+                mp4_box(b"hdlr", &[0u8; 24]),
+                mp4_box(b"keys", &keys),
+                mp4_box(b"ilst", &ilst),
+            ]
+            .concat(),
+        )
+    }
+
+    /// `prefix` 之后是 moov：先是超过读取上限的 trak（稀疏文件，不占磁盘），再是 `tail`
+    fn sparse_moov(
+        dir: &tempfile::TempDir,
+        name: &str,
+        prefix: &[u8],
+        tail: &[u8],
+    ) -> std::path::PathBuf {
+        let large = MAX_BOX_SIZE + 8;
+        let moov_size = 8 + 8 + large + tail.len() as u64;
+        let mut data = prefix.to_vec();
+        data.extend_from_slice(&(moov_size as u32).to_be_bytes());
+        data.extend_from_slice(b"moov");
+        data.extend_from_slice(&((large + 8) as u32).to_be_bytes());
+        data.extend_from_slice(b"trak");
+        let path = write_fixture(dir, name, &data);
+        let mut file = std::fs::OpenOptions::new()
+            .append(true)
+            .open(&path)
+            .unwrap();
+        file.set_len(data.len() as u64 + large).unwrap();
+        std::io::Write::write_all(&mut file, tail).unwrap();
+        path
+    }
+
+    fn write_fixture(dir: &tempfile::TempDir, name: &str, data: &[u8]) -> std::path::PathBuf {
+        let path = dir.path().join(name);
+        std::fs::write(&path, data).unwrap();
+        path
+    }
+
+    fn utc(date: DateTime<Local>) -> String {
+        date.with_timezone(&chrono::Utc).to_rfc3339()
+    }
+
+    #[test]
+    fn test_mp4_creation_date() {
+        let dir = tempfile::TempDir::new().unwrap();
+        let mvhd_time = 1_763_472_645; // 2025-11-18T13:30:45Z
+        let tkhd_time = mvhd_time - 60;
+        let trak = mp4_box(b"trak", &header_box(b"tkhd", tkhd_time));
+        let ftyp = mp4_box(b"ftyp", b"qt  \0\0\0\0");
+        let mdat = mp4_box(b"mdat", &[0u8; 32]);
+
+        // moov 位于 mdat 之后
+        let moov = mp4_box(
+            b"moov",
+            &[header_box(b"mvhd", mvhd_time), trak.clone()].concat(),
+        );
+        let path = write_fixture(
+            &dir,
+            "clip.mp4",
+            &[ftyp.clone(), mdat.clone(), moov].concat(),
+        );
+        let (date, source) = extract_date_with_source(&path, false).unwrap();
+        assert_eq!(source, DateSource::Container);
+        assert_eq!(utc(date), "2025-11-18T13:30:45+00:00");
+
+        // mvhd 未设置时使用 tkhd
+        let moov = mp4_box(
+            b"moov",
+            &[header_box(b"mvhd", -MP4_EPOCH_OFFSET), trak.clone()].concat(),
+        );
+        let path = write_fixture(&dir, "tkhd.mp4", &[ftyp.clone(), moov].concat());
+        assert_eq!(
+            utc(extract_date(&path, false).unwrap()),
+            "2025-11-18T13:29:45+00:00"
+        );
+
+        // QuickTime creationdate 优先，保留拍摄地的当地时间
+        let meta = quicktime_meta("2025-11-18T09:15:00+0100");
+        let moov = mp4_box(
+            b"moov",
+            &[header_box(b"mvhd", mvhd_time), trak, meta].concat(),
+        );
+        let path = write_fixture(&dir, "iphone.mov", &[ftyp.clone(), moov, mdat].concat());
+        let date = extract_date(&path, false).unwrap();
+        assert_eq!(date.naive_local().to_string(), "2025-11-18 09:15:00");
+
+        // 超过读取上限的采样表不读入内存
+        let mvhd = header_box(b"mvhd", mvhd_time);
+        let path = sparse_moov(&dir, "long.mp4", &ftyp, &mvhd);
+        assert_eq!(
+            utc(extract_date(&path, false).unwrap()),
+            "2025-11-18T13:30:45+00:00"
+        );
+    }
+
+    #[test]
+    fn test_mp4_box_walker_seeks_past_large_and_broken_boxes() {
+        use std::io::Cursor;
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let mvhd_time = 1_763_472_645; // 2025-11-18T13:30:45Z
+        let ftyp = mp4_box(b"ftyp", b"isom\0\0\0\0");
+        let mvhd = header_box(b"mvhd", mvhd_time);
+        let date_of = |data: Vec<u8>| mp4_creation_date(&mut Cursor::new(data)).map(utc);
+
+        // 64 位长度（size == 1）的顶层 mdat 和 moov 中的 trak
+        let large_box = |kind: &[u8; 4], payload: &[u8]| {
+            let mut data = 1u32.to_be_bytes().to_vec();
+            data.extend_from_slice(kind);
+            data.extend_from_slice(&(payload.len() as u64 + 16).to_be_bytes());
+            data.extend_from_slice(payload);
+            data
+        };
+        let trak = large_box(b"trak", &header_box(b"tkhd", mvhd_time - 60));
+        let moov = mp4_box(b"moov", &[trak, mvhd.clone()].concat());
+        let data = [ftyp.clone(), large_box(b"mdat", &[0u8; 32]), moov].concat();
+        assert_eq!(date_of(data).unwrap(), "2025-11-18T13:30:45+00:00");
+
+        // 不完整的子 box 之前的内容照常读取，之后停止遍历而不报错
+        let mut truncated = mp4_box(b"udta", &[0u8; 16]);
+        truncated[..4].copy_from_slice(&64u32.to_be_bytes());
+        let moov = mp4_box(b"moov", &[mvhd.clone(), truncated.clone()].concat());
+        let data = [ftyp.clone(), moov].concat();
+        let mut reader = Cursor::new(data.clone());
+        let moov = find_top_level_box(&mut reader, b"moov").unwrap().unwrap();
+        let kinds: Vec<_> = child_boxes(&mut reader, moov)
+            .unwrap()
+            .into_iter()
+            .map(|(kind, _)| kind)
+            .collect();
+        assert_eq!(kinds, [*b"mvhd"]);
+        assert_eq!(date_of(data).unwrap(), "2025-11-18T13:30:45+00:00");
+
+        // 文件在 moov 中途截断：找不到 moov，返回错误而不是读取失败
+        let moov = mp4_box(b"moov", &[truncated, mvhd.clone()].concat());
+        let mut data = [ftyp.clone(), moov].concat();
+        data.truncate(data.len() - 4);
+        let error = date_of(data).unwrap_err();
+        assert!(error.to_string().contains("No moov box"), "{}", error);
+
+        // 超过读取上限的 trak 位于 meta 和 mvhd 之前，只跳过而不读入
+        let meta = quicktime_meta("2025-11-18T09:15:00+0100");
+        let path = sparse_moov(&dir, "long.mov", &ftyp, &[meta, mvhd].concat());
+        let date = extract_date(&path, false).unwrap();
+        assert_eq!(date.naive_local().to_string(), "2025-11-18 09:15:00");
+    }
+
+    #[test]
+    fn test_extract_content_identifier() {
+        use exif::experimental::Writer;
+        use exif::{Field, In, Tag, Value};
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let id = "6B1E2F0A-3C4D-4E5F-8A9B-0C1D2E3F4A5B";
+
+        // 实况视频：QuickTime 元数据中除拍摄时间外还有内容标识符
+        let meta = quicktime_meta_items(&[
+            (QUICKTIME_CREATION_DATE, "2025-11-18T09:15:00+0100"),
+            (QUICKTIME_CONTENT_IDENTIFIER, id),
+        ]);
+        let moov = mp4_box(
+            b"moov",
+            &[header_box(b"mvhd", 1_763_472_645), meta].concat(),
+        );
+        let ftyp = mp4_box(b"ftyp", b"qt  \0\0\0\0");
+        let path = write_fixture(&dir, "IMG_0001.MOV", &[ftyp.clone(), moov].concat());
+        assert_eq!(extract_content_identifier(&path).as_deref(), Some(id));
+        let moov = mp4_box(b"moov", &header_box(b"mvhd", 1_763_472_645));
+        let path = write_fixture(&dir, "clip.mov", &[ftyp, moov].concat());
+        assert_eq!(extract_content_identifier(&path), None);
+
+        // 实况照片：Apple MakerNote 中的 0x0011 标签，值存放在 IFD 之后
+        let mut maker_note = b"Apple iOS\0\0\x01MM".to_vec();
+        maker_note.extend_from_slice(&2u16.to_be_bytes());
+        let value_offset = 14 + 2 + 2 * 12 + 4;
+        for (tag, kind, count, value) in [
+            (0x0001u16, 9u16, 1u32, 14u32),
+            (0x0011, 2, id.len() as u32 + 1, value_offset),
+        ] {
+            maker_note.extend_from_slice(&tag.to_be_bytes());
+            maker_note.extend_from_slice(&kind.to_be_bytes());
+            maker_note.extend_from_slice(&count.to_be_bytes());
+            maker_note.extend_from_slice(&value.to_be_bytes());
+        }
+        maker_note.extend_from_slice(&[0u8; 4]);
+        maker_note.extend_from_slice(id.as_bytes());
+        maker_note.push(0);
+        assert_eq!(apple_content_identifier(&maker_note).as_deref(), Some(id));
+        assert_eq!(apple_content_identifier(&maker_note[..50]), None);
+
+        let field = Field {
+            tag: Tag::MakerNote,
+            ifd_num: In::PRIMARY,
+            value: Value::Undefined(maker_note, 0),
+        };
+        let mut writer = Writer::new();
+        writer.push_field(&field);
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+        let path = write_fixture(&dir, "IMG_0001.tif", tiff.get_ref());
+        assert_eq!(extract_content_identifier(&path).as_deref(), Some(id));
+    }
+
+    #[test]
+    fn test_matroska_and_avi_dates() {
+        let dir = tempfile::TempDir::new().unwrap();
+
+        // EBML 头、长度未知的 Segment、SeekHead，之后是带 DateUTC 的 Info
+        let nanos: i64 = (1_763_472_645 - MKV_EPOCH) * 1_000_000_000;
+        let date_utc = [&[0x44, 0x61, 0x88][..], &nanos.to_be_bytes()].concat();
+        let info = [
+            &[0x15, 0x49, 0xA9, 0x66, 0x80 | date_utc.len() as u8][..],
+            &date_utc,
+        ]
+        .concat();
+        let mkv = [
+            &[0x1A, 0x45, 0xDF, 0xA3, 0x84, 0x42, 0x86, 0x81, 0x01][..],
+            &[
+                0x18, 0x53, 0x80, 0x67, 0x01, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF,
+            ],
+            &[0x11, 0x4D, 0x9B, 0x74, 0x82, 0xEC, 0x80],
+            &info,
+        ]
+        .concat();
+        let path = write_fixture(&dir, "clip.mkv", &mkv);
+        let (date, source) = extract_date_with_source(&path, false).unwrap();
+        assert_eq!(source, DateSource::Container);
+        assert_eq!(utc(date), "2025-11-18T13:30:45+00:00");
+
+        let chunk = |id: &[u8; 4], body: &[u8]| {
+            let mut data = id.to_vec();
+            data.extend_from_slice(&(body.len() as u32).to_le_bytes());
+            data.extend_from_slice(body);
+            if body.len() % 2 == 1 {
+                data.push(0);
+            }
+            data
+        };
+        let hdrl = [
+            b"hdrl".to_vec(),
+            chunk(b"avih", &[0u8; 56]),
+            chunk(b"IDIT", b"TUE NOV 18 14:30:45 2025\n\0"),
+        ]
+        .concat();
+        let body = [
+            b"AVI ".to_vec(),
+            chunk(b"LIST", &hdrl),
+            chunk(b"LIST", b"movi"),
+        ]
+        .concat();
+        let path = write_fixture(&dir, "clip.avi", &chunk(b"RIFF", &body));
+        let date = extract_date(&path, false).unwrap();
+        assert_eq!(date.naive_local().to_string(), "2025-11-18 14:30:45");
+
+        // 不是视频容器时回退到文件系统时间
+        let path = write_fixture(&dir, "song.mp3", b"ID3\x03\0\0\0\0\0\0\0\0");
+        let (_, source) = extract_date_with_source(&path, false).unwrap();
+        assert_ne!(source, DateSource::Container);
+    }
+
+    #[test]
+    fn test_extract_exif_metadata() {
+        use exif::experimental::Writer;
+        use exif::{Field, In, Rational, Tag, Value};
+
+        let ascii = |tag, value: &str| Field {
+            tag,
+            ifd_num: In::PRIMARY,
+            value: Value::Ascii(vec![value.as_bytes().to_vec()]),
+        };
+        let fields = [
+            ascii(Tag::Make, "FUJIFILM"),
+            ascii(Tag::Model, "X-T5"),
+            ascii(Tag::LensModel, "XF23mmF1.4 R LM WR"),
+            ascii(Tag::DateTimeOriginal, "2025:11:18 14:30:45"),
+            Field {
+                tag: Tag::PhotographicSensitivity,
+                ifd_num: In::PRIMARY,
+                value: Value::Short(vec![400]),
+            },
+            Field {
+                tag: Tag::FocalLength,
+                ifd_num: In::PRIMARY,
+                value: Value::Rational(vec![Rational { num: 23, denom: 1 }]),
+            },
+        ];
+        let mut writer = Writer::new();
+        for field in &fields {
+            writer.push_field(field);
+        }
+        let mut tiff = std::io::Cursor::new(Vec::new());
+        writer.write(&mut tiff, false).unwrap();
+
+        let dir = tempfile::TempDir::new().unwrap();
+        let path = write_fixture(&dir, "photo.tif", tiff.get_ref());
+        let exif = extract_exif_metadata(&path).unwrap();
+        assert_eq!(exif.make.as_deref(), Some("FUJIFILM"));
+        assert_eq!(exif.lens.as_deref(), Some("XF23mmF1.4 R LM WR"));
+        assert_eq!(exif.date, parse_exif_datetime("2025:11:18 14:30:45"));
+        assert_eq!(exif.serial, None);
+
+        let values = exif.template_values();
+        assert_eq!(values[4], ("iso", Some("400".to_string())));
+        assert_eq!(values[5], ("focal_length", Some("23".to_string())));
     }
 
     #[test]
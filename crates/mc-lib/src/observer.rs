@@ -0,0 +1,162 @@
+//! 运行进度与事件观察者
+//!
+//! 前端实现 [`Observer`] 接收扫描、分类、传输和清理事件，
+//! 并通过 [`Progress`] 获得计数、已传输字节数和预计剩余时间。
+
+use anyhow::Result;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use crate::classifier::ClassifyResult;
+
+/// 一次运行的进度计数
+#[derive(Debug, Clone)]
+pub struct Progress {
+    /// 待处理的文件数
+    pub files_total: usize,
+    /// 已处理的文件数（含失效和跳过的文件）
+    pub files_done: usize,
+    /// 待处理文件的总字节数
+    pub bytes_total: u64,
+    /// 已处理文件的字节数
+    pub bytes_done: u64,
+    /// 实际传输的字节数（含随主文件传输的附属文件和组成员）
+    pub bytes_transferred: u64,
+    pub success: usize,
+    pub renamed: usize,
+    pub replaced: usize,
+    pub quarantined: usize,
+    pub skipped: usize,
+    pub failed: usize,
+    /// 运行是否被取消（此时只处理了部分文件）
+    pub cancelled: bool,
+    started: Instant,
+}
+
+impl Progress {
+    /// 开始计时
+    pub fn new(files_total: usize, bytes_total: u64) -> Self {
+        Progress {
+            files_total,
+            files_done: 0,
+            bytes_total,
+            bytes_done: 0,
+            bytes_transferred: 0,
+            success: 0,
+            renamed: 0,
+            replaced: 0,
+            quarantined: 0,
+            skipped: 0,
+            failed: 0,
+            cancelled: false,
+            started: Instant::now(),
+        }
+    }
+
+    /// 记录一个处理完成的文件，size 为该文件的字节数
+    pub fn record(&mut self, size: u64, result: &Result<ClassifyResult>) {
+        self.advance(size);
+        match result {
+            Ok(ClassifyResult::Success { .. }) => self.success += 1,
+            Ok(ClassifyResult::Renamed { .. }) => self.renamed += 1,
+            Ok(ClassifyResult::Replaced { .. }) => self.replaced += 1,
+            Ok(ClassifyResult::Quarantined { .. }) => self.quarantined += 1,
+            Ok(ClassifyResult::Skipped { .. }) => self.skipped += 1,
+            Ok(ClassifyResult::Failed { .. }) | Err(_) => self.failed += 1,
+        }
+    }
+
+    /// 记录一个未产生分类结果的已处理文件（如失效的计划条目）
+    pub fn advance(&mut self, size: u64) {
+        self.files_done += 1;
+        self.bytes_done += size;
+    }
+
+    /// 已用时间
+    pub fn elapsed(&self) -> Duration {
+        self.started.elapsed()
+    }
+
+    /// 完成比例（0.0 ~ 1.0），有字节数时按字节计算
+    pub fn fraction(&self) -> f32 {
+        if self.bytes_total > 0 {
+            (self.bytes_done as f64 / self.bytes_total as f64) as f32
+        } else if self.files_total > 0 {
+            self.files_done as f32 / self.files_total as f32
+        } else {
+            1.0
+        }
+    }
+
+    /// 按目前的处理速度估算的剩余时间，尚未处理任何文件时返回 None
+    pub fn eta(&self) -> Option<Duration> {
+        let fraction = self.fraction() as f64;
+        if self.files_done == 0 || fraction <= 0.0 {
+            return None;
+        }
+        let remaining = (1.0 - fraction).max(0.0) / fraction;
+        Some(self.elapsed().mul_f64(remaining))
+    }
+}
+
+/// 运行事件的观察者，所有方法默认不做任何事
+///
+/// 事件在调用批量接口的线程中按处理顺序依次发送
+pub trait Observer {
+    /// 开始扫描源目录
+    fn scan_started(&mut self, _sources: &[PathBuf]) {}
+
+    /// 扫描到一个媒体文件，count 为目前找到的文件数
+    fn file_discovered(&mut self, _path: &Path, _count: usize) {}
+
+    /// 一个文件处理完成
+    fn file_classified(
+        &mut self,
+        _source: &Path,
+        _result: &Result<ClassifyResult>,
+        _progress: &Progress,
+    ) {
+    }
+
+    /// 一个文件（连同附属文件和组成员）传输完成
+    fn bytes_transferred(&mut self, _source: &Path, _bytes: u64, _progress: &Progress) {}
+
+    /// 删除了一个空目录
+    fn dir_cleaned(&mut self, _dir: &Path) {}
+
+    /// 运行结束
+    fn run_finished(&mut self, _progress: &Progress) {}
+}
+
+/// 忽略所有事件的观察者
+#[derive(Debug, Default, Clone, Copy)]
+pub struct NoopObserver;
+
+impl Observer for NoopObserver {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_progress_counts_and_eta() {
+        let mut progress = Progress::new(4, 400);
+        assert_eq!(progress.eta(), None);
+
+        let skipped = Ok(ClassifyResult::Skipped {
+            path: PathBuf::from("a.jpg"),
+            reason: "same".to_string(),
+            policy: Default::default(),
+        });
+        progress.record(100, &skipped);
+        progress.record(100, &Err(anyhow::anyhow!("broken")));
+        assert_eq!((progress.skipped, progress.failed), (1, 1));
+        assert_eq!(progress.files_done, 2);
+        assert!((progress.fraction() - 0.5).abs() < f32::EPSILON);
+        assert!(progress.eta().unwrap() <= progress.elapsed() * 2);
+
+        progress.advance(200);
+        assert!((progress.fraction() - 1.0).abs() < f32::EPSILON);
+        assert_eq!(progress.eta(), Some(Duration::ZERO));
+    }
+}
@@ -0,0 +1,166 @@
+//! 并行处理流水线
+//!
+//! 耗时的元数据读取（EXIF 等）交给工作线程并行执行，结果按输入顺序
+//! 交回调用线程，依赖顺序的步骤（冲突解决、文件传输、日志）仍然串行进行。
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Condvar, Mutex, mpsc};
+use std::thread;
+use std::time::Duration;
+
+use crate::cancel::CancelToken;
+
+/// 默认线程数（CPU 核心数）
+pub fn default_jobs() -> usize {
+    thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// 每个工作线程最多领先调用线程的项数，限制暂存结果占用的内存
+const LOOKAHEAD_PER_JOB: usize = 4;
+
+/// 等待调用线程追上时检查取消的间隔
+const CANCEL_POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// 在 `jobs` 个工作线程中对每一项执行 `work`，并在调用线程中按输入顺序
+/// 把结果交给 `consume`
+///
+/// `jobs` 为 0 时使用 CPU 核心数，为 1 时直接在调用线程中串行执行。
+/// 工作线程最多领先尚未交出的第一项 `jobs * LOOKAHEAD_PER_JOB` 项；
+/// 指定 `cancel` 时，工作线程在暂停时等待，取消后不再开始新的项
+pub(crate) fn ordered_parallel<I, T, W, C>(
+    items: &[I],
+    jobs: usize,
+    cancel: Option<&CancelToken>,
+    work: W,
+    mut consume: C,
+) where
+    I: Sync,
+    T: Send,
+    W: Fn(&I) -> T + Sync,
+    C: FnMut(&I, T),
+{
+    let proceed = || cancel.is_none_or(CancelToken::wait_while_paused);
+    let jobs = if jobs == 0 { default_jobs() } else { jobs };
+    let jobs = jobs.min(items.len());
+    if jobs <= 1 {
+        for item in items {
+            if !proceed() {
+                break;
+            }
+            consume(item, work(item));
+        }
+        return;
+    }
+
+    let next = AtomicUsize::new(0);
+    let window = jobs * LOOKAHEAD_PER_JOB;
+    // 已交给 `consume` 的项数
+    let consumed = Mutex::new(0);
+    let advanced = Condvar::new();
+    let (sender, receiver) = mpsc::channel();
+
+    thread::scope(|scope| {
+        for _ in 0..jobs {
+            let sender = sender.clone();
+            let (next, work, consumed, advanced) = (&next, &work, &consumed, &advanced);
+            scope.spawn(move || {
+                loop {
+                    let index = next.fetch_add(1, Ordering::Relaxed);
+                    if index >= items.len() || !proceed() {
+                        break;
+                    }
+                    // 领先太多时等待调用线程追上
+                    let mut done = consumed.lock().unwrap_or_else(|e| e.into_inner());
+                    while index >= *done + window {
+                        if cancel.is_some_and(CancelToken::is_cancelled) {
+                            return;
+                        }
+                        done = advanced
+                            .wait_timeout(done, CANCEL_POLL_INTERVAL)
+                            .unwrap_or_else(|e| e.into_inner())
+                            .0;
+                    }
+                    drop(done);
+                    if sender.send((index, work(&items[index]))).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        drop(sender);
+
+        // 先完成的结果暂存，等前面的结果到齐后再按顺序交出
+        let mut pending = BTreeMap::new();
+        let mut expected = 0;
+        for (index, result) in receiver {
+            pending.insert(index, result);
+            while let Some(result) = pending.remove(&expected) {
+                consume(&items[expected], result);
+                expected += 1;
+                *consumed.lock().unwrap_or_else(|e| e.into_inner()) = expected;
+                advanced.notify_all();
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicBool;
+
+    #[test]
+    fn test_ordered_parallel_keeps_input_order() {
+        let items: Vec<u64> = (0..64).collect();
+        let mut seen = Vec::new();
+        ordered_parallel(
+            &items,
+            8,
+            None,
+            |&n| {
+                // 让靠前的项更慢完成
+                thread::sleep(Duration::from_millis((64 - n) % 7));
+                n * 2
+            },
+            |&n, doubled| seen.push((n, doubled)),
+        );
+
+        let expected: Vec<(u64, u64)> = items.iter().map(|&n| (n, n * 2)).collect();
+        assert_eq!(seen, expected);
+    }
+
+    #[test]
+    fn test_workers_stay_within_window_and_stop_on_cancel() {
+        let items: Vec<usize> = (0..200).collect();
+        let cancel = CancelToken::new();
+        let started = AtomicUsize::new(0);
+        let first_done = AtomicBool::new(false);
+        let mut seen = 0;
+        ordered_parallel(
+            &items,
+            2,
+            Some(&cancel),
+            |&n| {
+                started.fetch_add(1, Ordering::SeqCst);
+                if n == 0 {
+                    // 第一项完成前，其他工作线程最多领先窗口大小
+                    thread::sleep(Duration::from_millis(200));
+                    assert!(started.load(Ordering::SeqCst) <= 2 * LOOKAHEAD_PER_JOB);
+                    first_done.store(true, Ordering::SeqCst);
+                }
+            },
+            |&n, ()| {
+                seen += 1;
+                if n == 10 {
+                    cancel.cancel();
+                }
+            },
+        );
+        assert!(first_done.load(Ordering::SeqCst));
+        assert!(seen <= 10 + 1 + 2 * LOOKAHEAD_PER_JOB, "{}", seen);
+        assert!(started.load(Ordering::SeqCst) < items.len());
+    }
+}
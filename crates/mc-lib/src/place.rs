@@ -0,0 +1,126 @@
+//! 用户定义的地点
+//!
+//! 配置中的 `places` 用圆形（中心点 + 半径）或多边形描述办公室、家等地点，
+//! GPS 坐标落在范围内的文件属于该地点，可用于 `{place}` 变量和规则的 `places` 条件。
+
+use crate::config::{Place, PlaceArea};
+use crate::metadata::GpsPosition;
+
+impl Place {
+    /// 坐标是否在地点范围内
+    ///
+    /// 多边形按经纬度平面计算，不支持跨越 180° 经线的多边形
+    pub fn contains(&self, position: &GpsPosition) -> bool {
+        match &self.area {
+            PlaceArea::Circle { center, radius_m } => {
+                let center = GpsPosition {
+                    latitude: center[0],
+                    longitude: center[1],
+                };
+                center.distance_km(position) * 1000.0 <= *radius_m
+            },
+            PlaceArea::Polygon { polygon } => polygon_contains(polygon, position),
+        }
+    }
+}
+
+/// 查找坐标所在的地点，多个地点重叠时使用配置中靠前的地点
+pub(crate) fn find_place<'a>(places: &'a [Place], position: &GpsPosition) -> Option<&'a Place> {
+    places.iter().find(|place| place.contains(position))
+}
+
+/// 射线法判断点是否在多边形内
+fn polygon_contains(polygon: &[[f64; 2]], position: &GpsPosition) -> bool {
+    let (y, x) = (position.latitude, position.longitude);
+    let mut inside = false;
+    let mut previous = match polygon.last() {
+        Some(vertex) => vertex,
+        None => return false,
+    };
+    for vertex in polygon {
+        let ([y1, x1], [y2, x2]) = (*previous, *vertex);
+        if (y1 > y) != (y2 > y) && x < x1 + (y - y1) / (y2 - y1) * (x2 - x1) {
+            inside = !inside;
+        }
+        previous = vertex;
+    }
+    inside
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn position(latitude: f64, longitude: f64) -> GpsPosition {
+        GpsPosition {
+            latitude,
+            longitude,
+        }
+    }
+
+    #[test]
+    fn test_find_place_in_circle_and_polygon() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let yaml = r#"
+global:
+  date_format: "YYYYMMDD"
+  directory_template: "{place}"
+  clean_empty_dirs: false
+rules: []
+places:
+  - name: "Office"
+    center: [31.2304, 121.4737]
+    radius_m: 200
+  - name: "Studio B"
+    polygon: [[31.20, 121.40], [31.20, 121.50], [31.25, 121.50], [31.25, 121.40]]
+"#;
+        fs::write(&path, yaml).unwrap();
+        let config = Config::load(&path).unwrap();
+
+        let name = |lat, lon| find_place(&config.places, &position(lat, lon)).map(|p| &*p.name);
+        // 办公室与工作室重叠，使用靠前的办公室
+        assert_eq!(name(31.2310, 121.4740), Some("Office"));
+        assert_eq!(name(31.2200, 121.4300), Some("Studio B"));
+        assert_eq!(name(31.2600, 121.4300), None);
+        assert_eq!(name(31.2200, 121.5100), None);
+    }
+
+    #[test]
+    fn test_rule_with_unknown_place_is_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        let mut config = Config::default();
+        config.rules[0].places = vec!["Home".to_string()];
+        config.save(&path).unwrap();
+        let error = Config::load(&path).unwrap_err();
+        assert!(error.to_string().contains("unknown place"), "{}", error);
+    }
+
+    #[test]
+    fn test_invalid_place_area_is_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("config.yaml");
+        // 多边形顶点不足、半径无效或字段不完整的地点
+        let load = |places: &str| {
+            let yaml = format!(
+                "global:\n  date_format: YYYYMMDD\n  directory_template: \"{{place}}\"\n  \
+                 clean_empty_dirs: false\nrules: []\nplaces:\n{}",
+                places
+            );
+            fs::write(&path, yaml).unwrap();
+            format!("{:#}", Config::load(&path).unwrap_err())
+        };
+        let error = load("  - name: Line\n    polygon: [[31.2, 121.4], [31.3, 121.5]]\n");
+        assert!(error.contains("at least 3 polygon vertices"), "{}", error);
+        let error = load("  - name: Dot\n    center: [31.2, 121.4]\n    radius_m: 0\n");
+        assert!(error.contains("positive radius_m"), "{}", error);
+        let error = load("  - name: Dot\n    center: [31.2, 121.4]\n    radius_m: .nan\n");
+        assert!(error.contains("positive radius_m"), "{}", error);
+        let error = load("  - name: Office\n    center: [31.2, 121.4]\n");
+        assert!(error.contains("requires `radius_m`"), "{}", error);
+    }
+}
@@ -0,0 +1,313 @@
+//! 分类计划文件
+//!
+//! 计划可以导出为 YAML 或 JSON，手动编辑（修改目标路径或删除条目）后
+//! 再原样执行。执行前会校验源文件是否仍与生成计划时一致。
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use crate::asset_group::GroupMember;
+use crate::conflict::{CompareStrategy, ConflictPolicy, DEFAULT_COLLISION_SUFFIX};
+use crate::metadata::DateSource;
+use crate::sidecar::Sidecar;
+use crate::transfer::TransferMode;
+
+/// 当前计划文件格式版本
+pub const PLAN_VERSION: u32 = 1;
+
+/// 计划中预测的处理动作
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum PlannedAction {
+    /// 直接移动到目标路径
+    Move,
+    /// 目标冲突，移动到重命名后的路径
+    Rename,
+    /// 目标冲突，按策略替换已有文件
+    Replace,
+    /// 目标冲突，按策略移到隔离目录
+    Quarantine,
+    /// 跳过（目标已存在且相同）
+    Skip { reason: String },
+    /// 无法处理
+    Fail { error: String },
+}
+
+impl PlannedAction {
+    /// 该动作是否会传输文件
+    pub fn transfers(&self) -> bool {
+        matches!(
+            self,
+            PlannedAction::Move
+                | PlannedAction::Rename
+                | PlannedAction::Replace
+                | PlannedAction::Quarantine
+        )
+    }
+}
+
+/// 分类计划中的单个条目
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct PlanEntry {
+    /// 源文件路径
+    pub source: PathBuf,
+    /// 生成计划时的源文件大小
+    #[serde(default)]
+    pub size: Option<u64>,
+    /// 生成计划时的源文件修改时间（RFC 3339）
+    #[serde(default)]
+    pub modified: Option<String>,
+    /// 匹配到的规则名称
+    #[serde(default)]
+    pub rule: Option<String>,
+    /// 提取到的日期（RFC 3339）
+    #[serde(default)]
+    pub date: Option<String>,
+    /// 日期来源
+    #[serde(default)]
+    pub date_source: Option<DateSource>,
+    /// 目标路径（已解决冲突）
+    #[serde(default)]
+    pub target: Option<PathBuf>,
+    /// 预测的处理动作
+    pub action: PlannedAction,
+    /// 传输方式
+    #[serde(default)]
+    pub transfer_mode: TransferMode,
+    /// 判断目标文件是否相同的方式
+    #[serde(default)]
+    pub compare: CompareStrategy,
+    /// 目标位置已有不同文件时的处理策略
+    #[serde(default)]
+    pub conflict: ConflictPolicy,
+    /// `quarantine` 策略下的隔离路径
+    #[serde(default)]
+    pub quarantine: Option<PathBuf>,
+    /// 重名时的后缀模板
+    #[serde(default = "default_collision_suffix")]
+    pub collision_suffix: String,
+    /// 随主文件传输的附属文件
+    #[serde(default)]
+    pub sidecars: Vec<Sidecar>,
+    /// 随主成员传输的资产组成员
+    #[serde(default)]
+    pub members: Vec<GroupMember>,
+    /// 所属的连拍、包围曝光或分段视频序列
+    #[serde(default)]
+    pub sequence: Option<String>,
+    /// 所属事件的名称
+    #[serde(default)]
+    pub event: Option<String>,
+}
+
+fn default_collision_suffix() -> String {
+    DEFAULT_COLLISION_SUFFIX.to_string()
+}
+
+impl PlanEntry {
+    pub(crate) fn failed(source: &Path, error: String) -> Self {
+        PlanEntry {
+            source: source.to_path_buf(),
+            size: None,
+            modified: None,
+            rule: None,
+            date: None,
+            date_source: None,
+            target: None,
+            action: PlannedAction::Fail { error },
+            transfer_mode: TransferMode::Move,
+            compare: CompareStrategy::default(),
+            conflict: ConflictPolicy::default(),
+            quarantine: None,
+            collision_suffix: default_collision_suffix(),
+            sidecars: Vec::new(),
+            members: Vec::new(),
+            sequence: None,
+            event: None,
+        }
+    }
+
+    pub(crate) fn skipped(source: &Path, reason: String) -> Self {
+        PlanEntry {
+            action: PlannedAction::Skip { reason },
+            ..PlanEntry::failed(source, String::new())
+        }
+    }
+
+    /// 检查源文件自生成计划以来是否发生变化
+    ///
+    /// 返回 None 表示条目仍然有效，否则返回失效原因
+    pub fn stale_reason(&self) -> Option<String> {
+        let metadata = match fs::metadata(&self.source) {
+            Ok(m) => m,
+            Err(_) => return Some("Source file no longer exists".to_string()),
+        };
+
+        if let Some(size) = self.size
+            && size != metadata.len()
+        {
+            return Some(format!(
+                "Source size changed (planned {} bytes, now {} bytes)",
+                size,
+                metadata.len()
+            ));
+        }
+
+        if let Some(planned) = &self.modified {
+            let planned = match DateTime::parse_from_rfc3339(planned) {
+                Ok(t) => t.with_timezone(&Utc),
+                Err(_) => return Some(format!("Invalid modified time in plan: {}", planned)),
+            };
+            let current = metadata.modified().ok().map(DateTime::<Utc>::from);
+            if current != Some(planned) {
+                return Some("Source modified time changed".to_string());
+            }
+        }
+
+        None
+    }
+}
+
+/// 分类计划
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Plan {
+    /// 计划文件格式版本
+    pub version: u32,
+    /// 生成时间（RFC 3339）
+    pub created_at: String,
+    /// 生成计划时的目标根目录
+    pub target_dir: PathBuf,
+    /// 生成计划时扫描的源目录
+    #[serde(default)]
+    pub sources: Vec<PathBuf>,
+    /// 计划条目
+    pub entries: Vec<PlanEntry>,
+}
+
+impl Plan {
+    pub fn new(target_dir: &Path, sources: &[PathBuf], entries: Vec<PlanEntry>) -> Self {
+        Plan {
+            version: PLAN_VERSION,
+            created_at: Utc::now().to_rfc3339(),
+            target_dir: target_dir.to_path_buf(),
+            sources: sources.to_vec(),
+            entries,
+        }
+    }
+
+    /// 加载计划文件，`.json` 扩展名按 JSON 解析，其余按 YAML 解析
+    pub fn load(path: &Path) -> Result<Self> {
+        let content =
+            fs::read_to_string(path).context(format!("Failed to read plan file: {:?}", path))?;
+        let plan: Plan = if is_json(path) {
+            serde_json::from_str(&content).context("Failed to parse JSON plan")?
+        } else {
+            serde_yaml_bw::from_str(&content).context("Failed to parse YAML plan")?
+        };
+
+        if plan.version > PLAN_VERSION {
+            anyhow::bail!(
+                "Unsupported plan version {} (expected <= {})",
+                plan.version,
+                PLAN_VERSION
+            );
+        }
+        Ok(plan)
+    }
+
+    /// 保存计划文件，格式由扩展名决定
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let content = if is_json(path) {
+            serde_json::to_string_pretty(self)?
+        } else {
+            serde_yaml_bw::to_string(self)?
+        };
+        if let Some(parent) = path.parent()
+            && !parent.as_os_str().is_empty()
+        {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, content).context(format!("Failed to write plan file: {:?}", path))?;
+        Ok(())
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+}
+
+/// 将修改时间格式化为计划文件中使用的字符串
+pub(crate) fn format_modified(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn sample_entry(source: &Path) -> PlanEntry {
+        let metadata = fs::metadata(source).unwrap();
+        PlanEntry {
+            source: source.to_path_buf(),
+            size: Some(metadata.len()),
+            modified: metadata.modified().ok().map(format_modified),
+            rule: Some("Music".to_string()),
+            date: None,
+            date_source: None,
+            target: Some(PathBuf::from("Music/MP3/song.mp3")),
+            action: PlannedAction::Move,
+            transfer_mode: TransferMode::Copy,
+            compare: CompareStrategy::FullHash,
+            conflict: ConflictPolicy::KeepBoth,
+            quarantine: None,
+            collision_suffix: default_collision_suffix(),
+            sidecars: Vec::new(),
+            members: Vec::new(),
+            sequence: None,
+            event: None,
+        }
+    }
+
+    #[test]
+    fn test_plan_roundtrip_yaml_and_json() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("song.mp3");
+        fs::write(&source, b"music").unwrap();
+        let plan = Plan::new(
+            temp_dir.path(),
+            &[temp_dir.path().to_path_buf()],
+            vec![sample_entry(&source)],
+        );
+
+        for name in ["plan.yaml", "plan.json"] {
+            let path = temp_dir.path().join(name);
+            plan.save(&path).unwrap();
+            let loaded = Plan::load(&path).unwrap();
+            assert_eq!(loaded.entries.len(), 1);
+            assert_eq!(loaded.entries[0].action, PlannedAction::Move);
+            assert_eq!(loaded.entries[0].transfer_mode, TransferMode::Copy);
+            assert!(loaded.entries[0].stale_reason().is_none());
+        }
+    }
+
+    #[test]
+    fn test_stale_reason() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("song.mp3");
+        fs::write(&source, b"music").unwrap();
+        let entry = sample_entry(&source);
+
+        fs::write(&source, b"longer music").unwrap();
+        assert!(entry.stale_reason().unwrap().contains("size changed"));
+
+        fs::remove_file(&source).unwrap();
+        assert!(entry.stale_reason().unwrap().contains("no longer exists"));
+    }
+}
@@ -0,0 +1,140 @@
+//! 源目录与目标目录
+//!
+//! 导入场景下（如 SD 卡 → 媒体库）扫描的源目录和分类的目标目录不同。
+//! 只有当唯一的源目录就是目标目录时才是原地整理模式，其余情况下目标目录树
+//! 永远不会被当作输入扫描。
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// 一次分类运行的源目录和目标目录
+#[derive(Debug, Clone)]
+pub struct Roots {
+    sources: Vec<PathBuf>,
+    destination: PathBuf,
+}
+
+impl Roots {
+    /// 创建原地整理模式（源目录即目标目录）
+    pub fn in_place(dir: &Path) -> Result<Self> {
+        Self::new(vec![dir.to_path_buf()], dir)
+    }
+
+    /// 创建并校验源目录和目标目录
+    ///
+    /// - 源目录必须存在且为目录
+    /// - 除原地整理模式外，源目录不能是目标目录或位于目标目录内
+    /// - 目标目录位于某个源目录内时，扫描该源目录会跳过目标目录
+    pub fn new(sources: Vec<PathBuf>, destination: &Path) -> Result<Self> {
+        if sources.is_empty() {
+            anyhow::bail!("At least one source directory is required");
+        }
+
+        let destination = normalize(destination)?;
+        let mut normalized = Vec::with_capacity(sources.len());
+        for source in &sources {
+            if !source.is_dir() {
+                anyhow::bail!("Source directory does not exist: {:?}", source);
+            }
+            let source = normalize(source)?;
+            if !normalized.contains(&source) {
+                normalized.push(source);
+            }
+        }
+
+        let roots = Roots {
+            sources: normalized,
+            destination,
+        };
+
+        if !roots.is_in_place() {
+            for source in &roots.sources {
+                if source.starts_with(&roots.destination) {
+                    anyhow::bail!(
+                        "Source {:?} is inside the destination {:?}",
+                        source,
+                        roots.destination
+                    );
+                }
+            }
+        }
+
+        Ok(roots)
+    }
+
+    /// 源目录列表
+    pub fn sources(&self) -> &[PathBuf] {
+        &self.sources
+    }
+
+    /// 目标目录
+    pub fn destination(&self) -> &Path {
+        &self.destination
+    }
+
+    /// 是否为原地整理模式
+    pub fn is_in_place(&self) -> bool {
+        self.sources.len() == 1 && self.sources[0] == self.destination
+    }
+
+    /// 扫描时需要整体跳过的目录（非原地模式下为目标目录）
+    pub fn excluded_dirs(&self) -> Vec<PathBuf> {
+        if self.is_in_place() {
+            Vec::new()
+        } else {
+            vec![self.destination.clone()]
+        }
+    }
+}
+
+/// 转换为绝对路径，存在的路径会解析符号链接
+fn normalize(path: &Path) -> Result<PathBuf> {
+    if path.exists() {
+        path.canonicalize()
+            .context(format!("Failed to resolve path: {:?}", path))
+    } else {
+        std::path::absolute(path).context(format!("Failed to resolve path: {:?}", path))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_in_place_has_no_excluded_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let roots = Roots::in_place(temp_dir.path()).unwrap();
+        assert!(roots.is_in_place());
+        assert!(roots.excluded_dirs().is_empty());
+    }
+
+    #[test]
+    fn test_destination_inside_source_is_excluded() {
+        let temp_dir = TempDir::new().unwrap();
+        let library = temp_dir.path().join("library");
+        let roots = Roots::new(vec![temp_dir.path().to_path_buf()], &library).unwrap();
+        assert!(!roots.is_in_place());
+        assert_eq!(
+            roots.excluded_dirs(),
+            vec![temp_dir.path().canonicalize().unwrap().join("library")]
+        );
+    }
+
+    #[test]
+    fn test_source_inside_destination_is_rejected() {
+        let temp_dir = TempDir::new().unwrap();
+        let card = temp_dir.path().join("card");
+        fs::create_dir_all(&card).unwrap();
+        assert!(Roots::new(vec![card], temp_dir.path()).is_err());
+
+        let other = TempDir::new().unwrap();
+        let roots = Roots::new(
+            vec![other.path().to_path_buf(), temp_dir.path().to_path_buf()],
+            temp_dir.path(),
+        );
+        assert!(roots.is_err());
+    }
+}
@@ -1,10 +1,55 @@
 use crate::config::{Config, FileSize, Rule};
-use crate::media_types::MediaType;
+use crate::event::EventSpan;
+use crate::media_types::{MediaInfo, MediaType};
+use crate::sequence::SequenceRef;
 use anyhow::{Context, Result};
 use chrono::{DateTime, Local};
+use std::cell::OnceCell;
 use std::collections::HashMap;
 use std::path::Path;
 
+/// 批量规划时识别出的文件上下文，用于展开 `{sequence}` 和 `{event}` 等变量
+#[derive(Debug, Clone, Default)]
+pub struct BatchContext {
+    /// 文件所属的序列
+    pub sequence: Option<SequenceRef>,
+    /// 文件所属事件的时间范围
+    pub event: Option<EventSpan>,
+}
+
+/// 音频标签变量
+const TAG_VARS: [&str; 8] = [
+    "{artist}",
+    "{album_artist}",
+    "{album}",
+    "{track}",
+    "{disc}",
+    "{title}",
+    "{genre}",
+    "{tag_year}",
+];
+
+/// EXIF 相机和镜头变量
+const CAMERA_VARS: [&str; 7] = [
+    "{make}",
+    "{model}",
+    "{lens}",
+    "{serial}",
+    "{iso}",
+    "{focal_length}",
+    "{software}",
+];
+
+/// GPS 地点变量
+const LOCATION_VARS: [&str; 6] = [
+    "{country}",
+    "{region}",
+    "{city}",
+    "{lat}",
+    "{lon}",
+    "{place}",
+];
+
 /// 规则匹配器
 pub struct RuleMatcher<'a> {
     config: &'a Config,
@@ -81,24 +126,42 @@ impl<'a> RuleMatcher<'a> {
         self.match_file(extension, file_size)
     }
 
-    /// 匹配文件（O(1) 优化版本）
+    /// 匹配文件（O(1) 优化版本），限定了地点的规则不会匹配
     pub fn match_file(&self, extension: &str, file_size: u64) -> Option<&Rule> {
+        self.match_file_at(extension, file_size, || None)
+    }
+
+    /// 匹配文件，遇到限定了地点的规则时调用 `place` 获取文件所在的地点（至多调用一次）
+    pub fn match_file_at<'p>(
+        &self,
+        extension: &str,
+        file_size: u64,
+        place: impl FnOnce() -> Option<&'p str>,
+    ) -> Option<&Rule> {
         let ext_lower = extension.to_lowercase();
         let file_size_obj = FileSize { bytes: file_size };
+        let mut place = Some(place);
+        let file_place = OnceCell::new();
+        let mut in_place = |rule: &Rule| {
+            rule.places.is_empty()
+                || file_place
+                    .get_or_init(|| place.take().and_then(|place| place()))
+                    .is_some_and(|name| rule.places.iter().any(|p| p == name))
+        };
 
         // 1. O(1) 查找：从扩展名索引中查找匹配的规则列表
         if let Some(rules) = self.extension_rules.get(&ext_lower) {
-            // 按规则顺序检查文件大小匹配（规则已按配置文件中的顺序排列，即优先级）
+            // 按规则顺序检查文件大小和地点匹配（规则已按配置文件中的顺序排列，即优先级）
             for &rule in rules {
-                if self.check_file_size_match(rule, &file_size_obj) {
+                if self.check_file_size_match(rule, &file_size_obj) && in_place(rule) {
                     return Some(rule);
                 }
             }
         }
 
         // 2. 如果是已知的媒体类型但未在规则中配置，使用全局默认规则
-        if self.is_supported_media_type(&ext_lower) && self.global_rule.is_some() {
-            return self.global_rule;
+        if self.is_supported_media_type(&ext_lower) {
+            return self.global_rule.filter(|rule| in_place(rule));
         }
         None
     }
@@ -137,7 +200,48 @@ impl<'a> RuleMatcher<'a> {
             || crate::media_types::is_audio_extension(ext)
     }
 
+    /// 规则是否需要提取日期（目录或文件名模板使用了日期变量）
+    pub fn needs_date(&self, rule: &Rule) -> bool {
+        rule.date_format.is_some()
+            || template_uses_event(&rule.directory_template)
+            || rule
+                .effective_filename_template(&self.config.global)
+                .is_some_and(template_uses_date)
+    }
+
+    /// 规则的目录或文件名模板是否使用了音频标签变量
+    pub fn needs_tags(&self, rule: &Rule) -> bool {
+        self.rule_uses_any(rule, &TAG_VARS)
+    }
+
+    /// 规则的目录或文件名模板是否使用了相机和镜头变量
+    pub fn needs_camera(&self, rule: &Rule) -> bool {
+        self.rule_uses_any(rule, &CAMERA_VARS)
+    }
+
+    /// 规则的目录或文件名模板是否使用了地点变量
+    pub fn needs_location(&self, rule: &Rule) -> bool {
+        self.rule_uses_any(rule, &LOCATION_VARS)
+    }
+
+    fn rule_uses_any(&self, rule: &Rule, vars: &[&str]) -> bool {
+        let uses = |template: &str| vars.iter().any(|var| template.contains(var));
+        uses(&rule.directory_template)
+            || rule
+                .effective_filename_template(&self.config.global)
+                .is_some_and(uses)
+    }
+
     /// 根据规则构建目标路径
+    ///
+    /// 设置了 `filename_template` 时按模板命名文件并保留原扩展名；
+    /// 其中的 `{counter}` 保持原样，由计划器按目录依次编号。
+    /// `{sequence}` 展开为文件所属序列的名称，不属于序列时为空（空的目录层级被省略）；
+    /// 启用 `sequences.keep_together` 时序列成员的目录使用第一个成员的日期，
+    /// 并放进 `sequences.subfolder` 子目录。`{event}` 等变量使用 `context` 中的事件，
+    /// 没有事件时文件自成一个事件。`{artist}` 等音频标签变量使用 `media_info.tags`，
+    /// 标签中的年份是 `{tag_year}`，`{year}` 始终是 `date` 的年份（未使用日期的规则中原样保留）；
+    /// `{model}` 等相机变量使用 `media_info.exif`，`{city}` 等地点变量使用 `media_info.location`
     pub fn build_target_path(
         &self,
         base_dir: &Path,
@@ -145,74 +249,220 @@ impl<'a> RuleMatcher<'a> {
         media_info: &crate::media_types::MediaInfo,
         date: Option<&DateTime<Local>>,
         rule: &Rule,
+        context: &BatchContext,
     ) -> Result<std::path::PathBuf> {
         let filename = source.file_name().context("Failed to get filename")?;
+        let date_format = rule
+            .date_format
+            .as_deref()
+            .unwrap_or(&self.config.global.date_format);
+        let sequence = context.sequence.as_ref();
+        let sequences = &self.config.sequences;
+        let keep_together = sequences.keep_together && sequence.is_some();
+        let sequence_name = sequence.map(|s| s.name.as_str()).unwrap_or_default();
+        let directory_date = match sequence.and_then(|s| s.date.as_ref()) {
+            Some(first) if keep_together => Some(first),
+            _ => date,
+        };
+
+        let dated = rule.date_format.is_some() || template_uses_event(&rule.directory_template);
+        let placeholder = &self.config.global.missing_placeholder;
+        let directory_template = expand_metadata(&rule.directory_template, media_info, placeholder);
+        let directory = match directory_date {
+            Some(date) if dated => expand_template(
+                &directory_template,
+                &media_info.extension,
+                media_info.media_type.clone(),
+                date,
+                date_format,
+                context.event.as_ref(),
+            )?,
+            // 如果规则不需要日期，使用简单模板
+            _ => {
+                let mut template = directory_template;
+                template = template.replace("{ext}", &media_info.extension);
+                template = template.replace("{type}", type_name(&media_info.media_type));
+                template
+            },
+        };
+        let mut path = base_dir.join(expand_sequence(&directory, sequence_name));
+        if keep_together && !rule.directory_template.contains("{sequence}") {
+            path.push(expand_sequence(&sequences.subfolder, sequence_name));
+        }
 
-        // 如果规则不需要日期，使用简单模板
-        if date.is_none() || rule.date_format.is_none() {
-            let mut template = rule.directory_template.clone();
-            template = template.replace("{ext}", &media_info.extension);
-            template = template.replace(
-                "{type}",
-                match media_info.media_type {
-                    MediaType::Image => "Image",
-                    MediaType::Video => "Video",
-                    MediaType::Audio => "Audio",
-                },
-            );
-            return Ok(base_dir.join(template).join(filename));
+        match rule.effective_filename_template(&self.config.global) {
+            Some(template) => path.push(expand_filename(
+                &expand_metadata(
+                    &expand_sequence_in_name(template, sequence_name),
+                    media_info,
+                    placeholder,
+                ),
+                source,
+                &media_info.extension,
+                media_info.media_type.clone(),
+                date,
+                date_format,
+                context.event.as_ref(),
+            )?),
+            None => path.push(filename),
         }
+        Ok(path)
+    }
+}
 
-        let date = date.unwrap();
-        self.build_path(
-            rule,
-            base_dir,
-            &media_info.extension,
-            media_info.media_type.clone(),
-            date,
-            filename.to_str().unwrap(),
-        )
+/// 展开目录模板中的 `{sequence}`，省略展开后为空的目录层级
+fn expand_sequence(template: &str, name: &str) -> String {
+    template
+        .replace("{sequence}", name)
+        .split('/')
+        .filter(|segment| !segment.is_empty())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// 展开文件名模板中的 `{sequence}`，不属于序列时连同相邻的一个分隔符
+/// （`-` `_` `.` 或空格）一起省略，如 `{sequence}-{stem}` 展开为 `{stem}`
+fn expand_sequence_in_name(template: &str, name: &str) -> String {
+    const VAR: &str = "{sequence}";
+    const SEPARATORS: [char; 4] = ['-', '_', '.', ' '];
+    if !name.is_empty() {
+        return template.replace(VAR, name);
+    }
+    let mut result = template.to_string();
+    while let Some(start) = result.find(VAR) {
+        let end = start + VAR.len();
+        let range = if result[end..].starts_with(SEPARATORS) {
+            start..end + 1
+        } else if result[..start].ends_with(SEPARATORS) {
+            start - 1..end
+        } else {
+            start..end
+        };
+        result.replace_range(range, "");
     }
+    result
+}
 
-    /// 根据规则构建目标路径（内部方法）
-    fn build_path(
-        &self,
-        rule: &Rule,
-        base_dir: &Path,
-        extension: &str,
-        media_type: MediaType,
-        date: &DateTime<Local>,
-        filename: &str,
-    ) -> Result<std::path::PathBuf> {
-        let template = &rule.directory_template;
-        let date_format = rule
-            .date_format
-            .as_deref()
-            .unwrap_or(&self.config.global.date_format);
+/// 模板是否使用了日期、时间或事件变量
+fn template_uses_date(template: &str) -> bool {
+    [
+        "{year}", "{month}", "{day}", "{date}", "{hour}", "{minute}", "{second}",
+    ]
+    .iter()
+    .any(|var| template.contains(var))
+        || template_uses_event(template)
+}
 
-        let expanded = expand_template(template, extension, media_type, date, date_format)?;
+/// 模板是否使用了事件变量
+fn template_uses_event(template: &str) -> bool {
+    ["{event}", "{event_start}", "{event_end}"]
+        .iter()
+        .any(|var| template.contains(var))
+}
 
-        Ok(base_dir.join(expanded).join(filename))
+/// 展开音频标签、相机和地点变量
+///
+/// 文件中没有的值展开为 `placeholder`；值中的 `{` `}` 被去掉，不会再被当作变量展开
+fn expand_metadata(template: &str, media_info: &MediaInfo, placeholder: &str) -> String {
+    let tags = media_info.tags.clone().unwrap_or_default();
+    let exif = media_info.exif.clone().unwrap_or_default();
+    let location = media_info.location.clone().unwrap_or_default();
+    let mut result = template.to_string();
+    for (name, value) in tags
+        .template_values()
+        .into_iter()
+        .chain(exif.template_values())
+        .chain(location.template_values())
+    {
+        let var = format!("{{{}}}", name);
+        match value.map(|v| sanitize_value(&v)) {
+            Some(value) if !value.is_empty() => result = result.replace(&var, &value),
+            _ => result = result.replace(&var, placeholder),
+        }
     }
+    result
+}
+
+/// 将元数据值转换为可用作目录名的字符串：替换路径分隔符和 Windows 保留字符，
+/// 去掉模板变量的括号、首尾空白和末尾的点
+fn sanitize_value(value: &str) -> String {
+    let value: String = value
+        .chars()
+        .filter(|c| !matches!(c, '{' | '}'))
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    value.trim().trim_end_matches('.').trim_end().to_string()
+}
+
+/// 媒体类型在模板中的名称
+fn type_name(media_type: &MediaType) -> &'static str {
+    match media_type {
+        MediaType::Image => "Image",
+        MediaType::Video => "Video",
+        MediaType::Audio => "Audio",
+    }
+}
+
+/// 展开文件名模板并附加原扩展名
+///
+/// 除目录模板的变量外还支持 `{hour}` `{minute}` `{second}` 和原文件名 `{stem}`；
+/// 路径分隔符替换为 `-`，文件名模板不会创建子目录
+fn expand_filename(
+    template: &str,
+    source: &Path,
+    extension: &str,
+    media_type: MediaType,
+    date: Option<&DateTime<Local>>,
+    date_format: &str,
+    event: Option<&EventSpan>,
+) -> Result<String> {
+    let stem = source
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .context("Failed to get file stem")?;
+
+    let mut name = match date {
+        Some(date) => expand_template(template, extension, media_type, date, date_format, event)?
+            .replace("{hour}", &date.format("%H").to_string())
+            .replace("{minute}", &date.format("%M").to_string())
+            .replace("{second}", &date.format("%S").to_string()),
+        None if template_uses_date(template) => {
+            anyhow::bail!("Filename template needs a date: {}", template)
+        },
+        None => template
+            .replace("{type}", type_name(&media_type))
+            .replace("{ext}", &extension.to_uppercase()),
+    };
+    name = name.replace("{stem}", stem).replace(['/', '\\'], "-");
+    if name.trim().is_empty() {
+        anyhow::bail!("Filename template expands to an empty name: {}", template);
+    }
+
+    Ok(match source.extension().and_then(|e| e.to_str()) {
+        Some(ext) => format!("{}.{}", name, ext),
+        None => name,
+    })
 }
 
 /// 展开模板变量
+///
+/// `event` 为文件所属事件的时间范围，未提供时文件自成一个事件
 fn expand_template(
     template: &str,
     extension: &str,
     media_type: MediaType,
     date: &DateTime<Local>,
     date_format: &str,
+    event: Option<&EventSpan>,
 ) -> Result<String> {
     let mut result = template.to_string();
 
     // {type}
-    let type_str = match media_type {
-        MediaType::Image => "Image",
-        MediaType::Video => "Video",
-        MediaType::Audio => "Audio",
-    };
-    result = result.replace("{type}", type_str);
+    result = result.replace("{type}", type_name(&media_type));
 
     // {ext}
     result = result.replace("{ext}", &extension.to_uppercase());
@@ -230,6 +480,21 @@ fn expand_template(
     let formatted_date = format_date_string(date, date_format)?;
     result = result.replace("{date}", &formatted_date);
 
+    // {event} {event_start} {event_end}
+    if template_uses_event(&result) {
+        let event = event.copied().unwrap_or(EventSpan {
+            start: *date,
+            end: *date,
+        });
+        result = result
+            .replace("{event}", &event.name())
+            .replace(
+                "{event_start}",
+                &format_date_string(&event.start, date_format)?,
+            )
+            .replace("{event_end}", &format_date_string(&event.end, date_format)?);
+    }
+
     Ok(result)
 }
 
@@ -256,6 +521,9 @@ fn format_date_string(date: &DateTime<Local>, format: &str) -> Result<String> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::audio_tags::AudioTags;
+    use crate::geocoder::Location;
+    use crate::metadata::ExifMetadata;
     use chrono::TimeZone;
 
     #[test]
@@ -280,8 +548,15 @@ mod tests {
     fn test_expand_template() {
         let date = Local.with_ymd_and_hms(2025, 11, 18, 14, 30, 0).unwrap();
 
-        let result =
-            expand_template("{ext}/{date}", "jpg", MediaType::Image, &date, "YYYYMMDD").unwrap();
+        let result = expand_template(
+            "{ext}/{date}",
+            "jpg",
+            MediaType::Image,
+            &date,
+            "YYYYMMDD",
+            None,
+        )
+        .unwrap();
         assert_eq!(result, "JPG/20251118");
 
         let result = expand_template(
@@ -290,13 +565,154 @@ mod tests {
             MediaType::Image,
             &date,
             "YYYYMMDD",
+            None,
         )
         .unwrap();
         assert_eq!(result, "Photos/2025/11");
 
-        let result =
-            expand_template("{type}/{year}", "mp4", MediaType::Video, &date, "YYYY").unwrap();
+        let result = expand_template(
+            "{type}/{year}",
+            "mp4",
+            MediaType::Video,
+            &date,
+            "YYYY",
+            None,
+        )
+        .unwrap();
         assert_eq!(result, "Video/2025");
+
+        let event = EventSpan {
+            start: Local.with_ymd_and_hms(2025, 11, 15, 18, 0, 0).unwrap(),
+            end: date,
+        };
+        let result = expand_template(
+            "Events/{event}/{event_start}-{event_end}",
+            "jpg",
+            MediaType::Image,
+            &date,
+            "YYYYMMDD",
+            Some(&event),
+        )
+        .unwrap();
+        assert_eq!(result, "Events/2025-11-15_to_18/20251115-20251118");
+    }
+
+    #[test]
+    fn test_expand_filename() {
+        let date = Local.with_ymd_and_hms(2025, 11, 18, 14, 30, 5).unwrap();
+        let source = Path::new("/card/DCIM/IMG_0001.JPG");
+
+        let name = expand_filename(
+            "{year}{month}{day}_{hour}{minute}{second}_{stem}",
+            source,
+            "jpg",
+            MediaType::Image,
+            Some(&date),
+            "YYYYMMDD",
+            None,
+        )
+        .unwrap();
+        assert_eq!(name, "20251118_143005_IMG_0001.JPG");
+
+        // 路径分隔符不会创建子目录，{counter} 留给计划器展开
+        let name = expand_filename(
+            "{date}_{counter:3}",
+            source,
+            "jpg",
+            MediaType::Image,
+            Some(&date),
+            "YYYY/MM",
+            None,
+        )
+        .unwrap();
+        assert_eq!(name, "2025-11_{counter:3}.JPG");
+
+        assert!(
+            expand_filename(
+                "{date}",
+                source,
+                "jpg",
+                MediaType::Image,
+                None,
+                "YYYY",
+                None
+            )
+            .is_err()
+        );
+        let name = expand_filename(
+            "{type}_{stem}",
+            source,
+            "jpg",
+            MediaType::Image,
+            None,
+            "YYYY",
+            None,
+        )
+        .unwrap();
+        assert_eq!(name, "Image_IMG_0001.JPG");
+    }
+
+    #[test]
+    fn test_expand_metadata() {
+        let mut song = MediaInfo {
+            media_type: MediaType::Audio,
+            extension: "FLAC".to_string(),
+            tags: Some(AudioTags {
+                artist: Some("AC/DC".to_string()),
+                album: Some("Back in Black...".to_string()),
+                track: Some(6),
+                ..AudioTags::default()
+            }),
+            exif: None,
+            location: None,
+        };
+        assert_eq!(
+            expand_metadata(
+                "Music/{album_artist}/{album}/{track} {artist}",
+                &song,
+                "Unknown"
+            ),
+            "Music/Unknown/Back in Black/06 AC_DC"
+        );
+        // 年份标签使用 {tag_year}，{year} 始终留给日期展开
+        assert_eq!(expand_metadata("{year}/{tag_year}", &song, "_"), "{year}/_");
+        song.tags.as_mut().unwrap().year = Some(1980);
+        assert_eq!(
+            expand_metadata("{year}/{tag_year}", &song, "_"),
+            "{year}/1980"
+        );
+        // 标签值中的变量不会再被展开
+        song.tags.as_mut().unwrap().title = Some("{date} {ext}".to_string());
+        assert_eq!(expand_metadata("{title}", &song, "_"), "date ext");
+
+        let photo = MediaInfo {
+            media_type: MediaType::Image,
+            extension: "NEF".to_string(),
+            tags: None,
+            exif: Some(ExifMetadata {
+                make: Some("NIKON CORPORATION".to_string()),
+                model: Some("NIKON Z 6_2".to_string()),
+                focal_length: Some(24.5),
+                ..ExifMetadata::default()
+            }),
+            location: Some(Location {
+                country: Some("JP".to_string()),
+                city: Some("Kyoto".to_string()),
+                ..Location::default()
+            }),
+        };
+        assert_eq!(
+            expand_metadata(
+                "RAW/{make}-{model}/{lens}/{focal_length}mm",
+                &photo,
+                "NoLens"
+            ),
+            "RAW/NIKON CORPORATION-NIKON Z 6_2/NoLens/24.5mm"
+        );
+        assert_eq!(
+            expand_metadata("{country}/{region}/{city}", &photo, "Unknown"),
+            "JP/Unknown/Kyoto"
+        );
     }
 
     #[test]
@@ -324,4 +740,27 @@ mod tests {
         assert!(rule.is_some());
         assert_eq!(rule.unwrap().name, "Videos");
     }
+
+    #[test]
+    fn test_rule_limited_to_place() {
+        let mut config = Config::default();
+        let mut office = config.rules[0].clone();
+        office.name = "Office Photos".to_string();
+        office.places = vec!["Office".to_string()];
+        config.rules.insert(0, office);
+        let matcher = RuleMatcher::new(&config);
+        let size = 10 * 1024 * 1024;
+
+        let rule = matcher.match_file_at("jpg", size, || Some("Office"));
+        assert_eq!(rule.unwrap().name, "Office Photos");
+        let rule = matcher.match_file_at("jpg", size, || Some("Home"));
+        assert_eq!(rule.unwrap().name, "High Quality Photos");
+        // 不知道地点时限定了地点的规则不匹配，不限定地点的规则不读取地点
+        assert_eq!(
+            matcher.match_file("jpg", size).unwrap().name,
+            "High Quality Photos"
+        );
+        let rule = matcher.match_file_at("mp4", size, || panic!("place not needed"));
+        assert_eq!(rule.unwrap().name, "Videos");
+    }
 }
@@ -0,0 +1,326 @@
+//! 扫描源目录
+//!
+//! [`Scanner`] 遍历 [`Roots`] 中的源目录，按排除规则、扫描深度、符号链接和
+//! 文件系统边界收集媒体文件，同时记录每个被跳过的目录及原因。CLI 和 GUI
+//! 共用同一个扫描器，保证两者找到的文件一致。
+
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::cancel::CancelToken;
+use crate::config::{Config, ExcludeConfig, ScanConfig};
+use crate::filter::FileFilter;
+use crate::journal::DATA_DIR;
+use crate::media_types::get_media_info;
+use crate::observer::Observer;
+use crate::roots::Roots;
+
+/// 目录被跳过的原因
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SkipReason {
+    /// 在 `exclude.directories` 中
+    Excluded,
+    /// 隐藏目录
+    Hidden,
+    /// 目标目录（非原地整理模式）
+    Destination,
+    /// 超过最大扫描深度，目录中的内容未扫描
+    DepthLimit,
+    /// 位于其他文件系统上
+    OtherFilesystem,
+    /// 符号链接指向其祖先目录
+    SymlinkLoop,
+    /// 无法读取
+    Unreadable(String),
+}
+
+impl fmt::Display for SkipReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SkipReason::Excluded => write!(f, "excluded"),
+            SkipReason::Hidden => write!(f, "hidden"),
+            SkipReason::Destination => write!(f, "destination directory"),
+            SkipReason::DepthLimit => write!(f, "max depth reached"),
+            SkipReason::OtherFilesystem => write!(f, "other filesystem"),
+            SkipReason::SymlinkLoop => write!(f, "symlink loop"),
+            SkipReason::Unreadable(error) => write!(f, "unreadable: {}", error),
+        }
+    }
+}
+
+/// 被跳过的目录
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SkippedDir {
+    pub path: PathBuf,
+    pub reason: SkipReason,
+}
+
+/// 扫描结果
+#[derive(Debug, Clone, Default)]
+pub struct ScanResult {
+    /// 找到的媒体文件，按源目录和文件名排序
+    pub files: Vec<PathBuf>,
+    /// 被跳过的目录
+    pub skipped: Vec<SkippedDir>,
+    /// 扫描是否被取消（此时只找到了部分文件）
+    pub cancelled: bool,
+}
+
+/// 源目录扫描器
+pub struct Scanner<'a> {
+    roots: &'a Roots,
+    exclude: ExcludeConfig,
+    options: ScanConfig,
+    cancel: CancelToken,
+}
+
+impl<'a> Scanner<'a> {
+    /// 按配置中的 `scan` 和 `exclude` 设置创建扫描器
+    pub fn new(config: &Config, roots: &'a Roots) -> Self {
+        Scanner {
+            roots,
+            exclude: config.exclude.clone(),
+            options: config.scan.clone(),
+            cancel: CancelToken::new(),
+        }
+    }
+
+    /// 设置最大扫描深度，None 表示不限制
+    pub fn with_max_depth(mut self, max_depth: Option<usize>) -> Self {
+        self.options.max_depth = max_depth;
+        self
+    }
+
+    /// 设置是否跟随符号链接
+    pub fn with_follow_symlinks(mut self, follow: bool) -> Self {
+        self.options.follow_symlinks = follow;
+        self
+    }
+
+    /// 设置是否限制在源目录所在的文件系统内
+    pub fn with_same_filesystem(mut self, same: bool) -> Self {
+        self.options.same_filesystem = same;
+        self
+    }
+
+    /// 设置是否跳过隐藏文件和目录
+    pub fn with_skip_hidden(mut self, skip: bool) -> Self {
+        self.exclude.hidden_files = skip;
+        self
+    }
+
+    /// 使用指定的取消标志，取消后停止扫描
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// 扫描所有源目录
+    pub fn scan(&self, observer: &mut dyn Observer) -> ScanResult {
+        observer.scan_started(self.roots.sources());
+        let filter = FileFilter::new(&self.exclude).with_excluded_dirs(self.roots.excluded_dirs());
+        // 程序数据目录（日志、被替换文件的备份）永远不作为输入扫描，也不报告为跳过
+        let mut data_dirs = vec![self.roots.destination().join(DATA_DIR)];
+        data_dirs.extend(self.roots.sources().iter().map(|root| root.join(DATA_DIR)));
+        let mut result = ScanResult::default();
+
+        'roots: for root in self.roots.sources() {
+            let mut walker = WalkDir::new(root)
+                .min_depth(1)
+                .follow_links(self.options.follow_symlinks)
+                .same_file_system(self.options.same_filesystem)
+                .sort_by_file_name();
+            if let Some(max_depth) = self.options.max_depth {
+                walker = walker.max_depth(max_depth);
+            }
+            let root_device = device_id(root);
+
+            let mut entries = walker.into_iter();
+            while let Some(entry) = entries.next() {
+                if self.cancel.is_cancelled() {
+                    result.cancelled = true;
+                    break 'roots;
+                }
+
+                let entry = match entry {
+                    Ok(entry) => entry,
+                    Err(e) => {
+                        result.skipped.push(skipped_from_error(root, e));
+                        continue;
+                    },
+                };
+                let path = entry.path();
+
+                if entry.file_type().is_dir() {
+                    if data_dirs.iter().any(|dir| path == dir) {
+                        entries.skip_current_dir();
+                        continue;
+                    }
+                    let reason = if let Some(reason) = filter.dir_exclusion(&entry) {
+                        entries.skip_current_dir();
+                        Some(reason)
+                    } else if self.options.same_filesystem && device_id(path) != root_device {
+                        // walkdir 不会进入其他文件系统上的目录
+                        Some(SkipReason::OtherFilesystem)
+                    } else if self.options.max_depth == Some(entry.depth()) && has_entries(path) {
+                        Some(SkipReason::DepthLimit)
+                    } else {
+                        None
+                    };
+                    if let Some(reason) = reason {
+                        result.skipped.push(SkippedDir {
+                            path: entry.into_path(),
+                            reason,
+                        });
+                    }
+                    continue;
+                }
+
+                if !entry.file_type().is_file()
+                    || filter.should_exclude_file(path)
+                    || get_media_info(path).is_none()
+                {
+                    continue;
+                }
+                result.files.push(entry.into_path());
+                observer.file_discovered(&result.files[result.files.len() - 1], result.files.len());
+            }
+        }
+
+        result
+    }
+}
+
+fn skipped_from_error(root: &Path, error: walkdir::Error) -> SkippedDir {
+    let path = error.path().unwrap_or(root).to_path_buf();
+    let reason = if error.loop_ancestor().is_some() {
+        SkipReason::SymlinkLoop
+    } else {
+        let message = match error.io_error() {
+            Some(e) => e.to_string(),
+            None => error.to_string(),
+        };
+        SkipReason::Unreadable(message)
+    };
+    SkippedDir { path, reason }
+}
+
+fn has_entries(dir: &Path) -> bool {
+    fs::read_dir(dir).is_ok_and(|mut entries| entries.next().is_some())
+}
+
+#[cfg(unix)]
+fn device_id(path: &Path) -> Option<u64> {
+    use std::os::unix::fs::MetadataExt;
+    fs::metadata(path).ok().map(|m| m.dev())
+}
+
+/// 其他平台上由 walkdir 负责不进入其他文件系统，但无法报告原因
+#[cfg(not(unix))]
+fn device_id(_path: &Path) -> Option<u64> {
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::observer::NoopObserver;
+    use tempfile::TempDir;
+
+    fn touch(path: &Path) {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, b"data").unwrap();
+    }
+
+    fn reason_of(result: &ScanResult, name: &str) -> Option<SkipReason> {
+        result
+            .skipped
+            .iter()
+            .find(|s| s.path.file_name().is_some_and(|n| n == name))
+            .map(|s| s.reason.clone())
+    }
+
+    #[test]
+    fn test_scan_reports_files_and_skipped_dirs() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path();
+        touch(&source.join("a.jpg"));
+        touch(&source.join("notes.txt"));
+        touch(&source.join("2025/trip/b.mp4"));
+        touch(&source.join("2025/trip/deep/c.jpg"));
+        touch(&source.join(".cache/d.jpg"));
+        touch(&source.join("node_modules/e.jpg"));
+        touch(&source.join("library/f.jpg"));
+
+        let roots = Roots::new(vec![source.to_path_buf()], &source.join("library")).unwrap();
+        let config = Config::default();
+        let result = Scanner::new(&config, &roots)
+            .with_max_depth(Some(3))
+            .scan(&mut NoopObserver);
+
+        let names: Vec<_> = result
+            .files
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["b.mp4", "a.jpg"]);
+        assert_eq!(reason_of(&result, ".cache"), Some(SkipReason::Hidden));
+        assert_eq!(
+            reason_of(&result, "node_modules"),
+            Some(SkipReason::Excluded)
+        );
+        assert_eq!(reason_of(&result, "library"), Some(SkipReason::Destination));
+        assert_eq!(reason_of(&result, "deep"), Some(SkipReason::DepthLimit));
+
+        // 不限制深度且包含隐藏目录
+        let result = Scanner::new(&config, &roots)
+            .with_skip_hidden(false)
+            .scan(&mut NoopObserver);
+        assert_eq!(result.files.len(), 4);
+        assert_eq!(reason_of(&result, ".cache"), None);
+    }
+
+    #[test]
+    fn test_scan_skips_data_dir_with_hidden_files_included() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path();
+        touch(&source.join("a.jpg"));
+        touch(&source.join(DATA_DIR).join("replaced/run-1/b.jpg"));
+        touch(&source.join(".thumbs/c.jpg"));
+
+        let roots = Roots::in_place(source).unwrap();
+        let data_dir = roots.destination().join(DATA_DIR);
+        let config = Config::default();
+        let result = Scanner::new(&config, &roots)
+            .with_skip_hidden(false)
+            .scan(&mut NoopObserver);
+
+        let names: Vec<_> = result
+            .files
+            .iter()
+            .map(|p| p.file_name().unwrap().to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names, ["c.jpg", "a.jpg"]);
+        assert!(result.files.iter().all(|p| !p.starts_with(&data_dir)));
+        assert!(result.skipped.is_empty());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_scan_skips_symlink_loop() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path();
+        touch(&source.join("album/a.jpg"));
+        std::os::unix::fs::symlink(source, source.join("album/loop")).unwrap();
+
+        let roots = Roots::in_place(source).unwrap();
+        let config = Config::default();
+        let result = Scanner::new(&config, &roots)
+            .with_follow_symlinks(true)
+            .scan(&mut NoopObserver);
+        assert_eq!(result.files.len(), 1);
+        assert_eq!(reason_of(&result, "loop"), Some(SkipReason::SymlinkLoop));
+    }
+}
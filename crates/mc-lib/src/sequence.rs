@@ -0,0 +1,347 @@
+//! 连拍、包围曝光和分段视频序列
+//!
+//! 连拍和包围曝光是短时间内连续拍摄的一组照片，GoPro/DJI 的长视频会被切成
+//! 多个分段文件（`GH010123.MP4`、`GH020123.MP4` …）。它们在逻辑上是一次拍摄，
+//! 跨过午夜时却会被按日期分到不同目录。序列以第一个成员的文件名命名，
+//! 可以通过模板变量 `{sequence}` 引用，也可以整体放进公共子目录。
+
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use crate::config::SequenceConfig;
+use crate::media_types::{MediaType, get_media_info};
+use crate::metadata::CaptureInfo;
+
+/// DJI 分段视频的上一段结束（修改时间）与下一段开始（文件名时间）的最大间隔（秒）
+const DJI_CHAPTER_GAP_SECS: i64 = 5;
+
+/// 序列种类
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum SequenceKind {
+    /// 连拍
+    Burst,
+    /// 包围曝光（曝光补偿不同）
+    Bracket,
+    /// 分段视频
+    Chapters,
+}
+
+impl SequenceKind {
+    /// 用于显示的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            SequenceKind::Burst => "burst",
+            SequenceKind::Bracket => "bracket",
+            SequenceKind::Chapters => "chapters",
+        }
+    }
+}
+
+/// 一个序列
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Sequence {
+    pub kind: SequenceKind,
+    /// 序列名称（第一个成员的文件名主干），即 `{sequence}` 的值
+    pub name: String,
+    /// 按拍摄顺序排列的成员
+    pub files: Vec<PathBuf>,
+}
+
+/// 规划时文件所属的序列
+#[derive(Debug, Clone, PartialEq)]
+pub struct SequenceRef {
+    /// 序列名称
+    pub name: String,
+    /// 第一个成员的日期，`keep_together` 时整个序列的目录使用该日期
+    pub date: Option<DateTime<Local>>,
+}
+
+/// 从文件列表中找出序列（至少两个成员）
+///
+/// 同一目录下的图片按 `capture_info` 提供的拍摄时间排序，相邻两张间隔不超过
+/// `burst_gap_ms`，或曝光补偿不同且间隔不超过 `bracket_gap_ms` 时属于同一序列，
+/// 不少于 `min_length` 张才算连拍或包围曝光。视频按 GoPro 和 DJI 的分段命名识别
+pub(crate) fn find_sequences<F>(
+    files: &[PathBuf],
+    config: &SequenceConfig,
+    capture_info: F,
+) -> Vec<Sequence>
+where
+    F: Fn(&Path) -> Option<CaptureInfo>,
+{
+    let mut shots: HashMap<&Path, Vec<(&PathBuf, CaptureInfo)>> = HashMap::new();
+    let mut gopro: HashMap<(&Path, String), Vec<(u32, &PathBuf)>> = HashMap::new();
+    let mut dji: HashMap<&Path, Vec<DjiChapter>> = HashMap::new();
+    let (mut shot_dirs, mut gopro_keys, mut dji_dirs) = (Vec::new(), Vec::new(), Vec::new());
+
+    for file in files {
+        let Some(info) = get_media_info(file) else {
+            continue;
+        };
+        let parent = file.parent().unwrap_or(Path::new(""));
+        match info.media_type {
+            MediaType::Image => {
+                if let Some(capture) = capture_info(file) {
+                    push_grouped(&mut shots, &mut shot_dirs, parent, (file, capture));
+                }
+            },
+            MediaType::Video => {
+                if let Some((key, chapter)) = gopro_chapter(file) {
+                    push_grouped(&mut gopro, &mut gopro_keys, (parent, key), (chapter, file));
+                } else if let Some((index, start)) = dji_chapter(file) {
+                    // 修改时间只读取一次，之后比较缓存的值
+                    let end = std::fs::metadata(file)
+                        .and_then(|metadata| metadata.modified())
+                        .ok()
+                        .map(DateTime::<Local>::from);
+                    let chapter = DjiChapter {
+                        index,
+                        start,
+                        end,
+                        file,
+                    };
+                    push_grouped(&mut dji, &mut dji_dirs, parent, chapter);
+                }
+            },
+            MediaType::Audio => {},
+        }
+    }
+
+    let mut sequences = Vec::new();
+    for dir in shot_dirs {
+        let shots = shots.get_mut(dir).unwrap();
+        shots.sort_by(|a, b| a.1.time.cmp(&b.1.time).then_with(|| a.0.cmp(b.0)));
+        for run in split_runs(shots, |a, b| shots_linked(&a.1, &b.1, config)) {
+            if run.len() < config.min_length.max(2) {
+                continue;
+            }
+            let bracket = run
+                .iter()
+                .any(|(_, shot)| shot.exposure_bias != run[0].1.exposure_bias);
+            let kind = if bracket {
+                SequenceKind::Bracket
+            } else {
+                SequenceKind::Burst
+            };
+            sequences.push(sequence(kind, run.iter().map(|(file, _)| *file)));
+        }
+    }
+    for key in gopro_keys {
+        let chapters = gopro.get_mut(&key).unwrap();
+        if chapters.len() < 2 {
+            continue;
+        }
+        chapters.sort();
+        sequences.push(sequence(
+            SequenceKind::Chapters,
+            chapters.iter().map(|(_, file)| *file),
+        ));
+    }
+    for dir in dji_dirs {
+        let chapters = dji.get_mut(dir).unwrap();
+        chapters.sort_by(|a, b| (a.index, a.start, a.file).cmp(&(b.index, b.start, b.file)));
+        for run in split_runs(chapters, dji_linked) {
+            if run.len() >= 2 {
+                sequences.push(sequence(
+                    SequenceKind::Chapters,
+                    run.iter().map(|chapter| chapter.file),
+                ));
+            }
+        }
+    }
+    sequences
+}
+
+fn push_grouped<K, V>(groups: &mut HashMap<K, Vec<V>>, order: &mut Vec<K>, key: K, value: V)
+where
+    K: std::hash::Hash + Eq + Clone,
+{
+    let values = groups.entry(key.clone()).or_default();
+    if values.is_empty() {
+        order.push(key);
+    }
+    values.push(value);
+}
+
+/// 把有序列表切分为相邻元素两两相连的段
+fn split_runs<T>(items: &[T], linked: impl Fn(&T, &T) -> bool) -> Vec<&[T]> {
+    let mut runs = Vec::new();
+    let mut start = 0;
+    for i in 1..=items.len() {
+        if i == items.len() || !linked(&items[i - 1], &items[i]) {
+            runs.push(&items[start..i]);
+            start = i;
+        }
+    }
+    runs
+}
+
+fn sequence<'a>(kind: SequenceKind, files: impl Iterator<Item = &'a PathBuf>) -> Sequence {
+    let files: Vec<PathBuf> = files.cloned().collect();
+    let name = files[0]
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or_default()
+        .to_string();
+    Sequence { kind, name, files }
+}
+
+/// 相邻两张照片是否属于同一连拍或包围曝光
+fn shots_linked(a: &CaptureInfo, b: &CaptureInfo, config: &SequenceConfig) -> bool {
+    let gap = (b.time - a.time).num_milliseconds().unsigned_abs();
+    let bracketed = matches!(
+        (a.exposure_bias, b.exposure_bias),
+        (Some(x), Some(y)) if x != y
+    );
+    gap <= config.burst_gap_ms || (bracketed && gap <= config.bracket_gap_ms)
+}
+
+/// GoPro 分段视频的分组键和分段序号
+///
+/// HERO6 及以后为 `GHccnnnn` / `GXccnnnn`，更早的机型第一段为 `GOPRnnnn`，
+/// 后续分段为 `GPccnnnn`，`cc` 为分段序号，`nnnn` 为录制编号
+fn gopro_chapter(path: &Path) -> Option<(String, u32)> {
+    let stem = path.file_stem()?.to_str()?.to_ascii_uppercase();
+    if stem.len() != 8 || !stem.is_ascii() {
+        return None;
+    }
+    let (prefix, chapter, number) = (&stem[..2], &stem[2..4], &stem[4..]);
+    if !number.bytes().all(|b| b.is_ascii_digit()) {
+        return None;
+    }
+    if stem.starts_with("GOPR") {
+        return Some((format!("GP{}", number), 0));
+    }
+    if ["GH", "GX", "GP"].contains(&prefix) && chapter.bytes().all(|b| b.is_ascii_digit()) {
+        return Some((format!("{}{}", prefix, number), chapter.parse().ok()?));
+    }
+    None
+}
+
+/// DJI 分段视频
+struct DjiChapter<'a> {
+    /// 文件编号
+    index: u32,
+    /// 文件名中的开始时间
+    start: NaiveDateTime,
+    /// 修改时间，即写完这一段的时间
+    end: Option<DateTime<Local>>,
+    file: &'a PathBuf,
+}
+
+/// DJI 视频 `DJI_<YYYYMMDDhhmmss>_<nnnn>[_x]` 的文件编号和开始时间
+fn dji_chapter(path: &Path) -> Option<(u32, NaiveDateTime)> {
+    let stem = path.file_stem()?.to_str()?;
+    let mut parts = stem.split('_');
+    if !parts.next()?.eq_ignore_ascii_case("DJI") {
+        return None;
+    }
+    let start = NaiveDateTime::parse_from_str(parts.next()?, "%Y%m%d%H%M%S").ok()?;
+    let index = parts.next()?.parse().ok()?;
+    Some((index, start))
+}
+
+/// DJI 视频 `b` 是否为 `a` 的下一段：编号连续，且在 `a` 写完（修改时间）时开始
+fn dji_linked(a: &DjiChapter, b: &DjiChapter) -> bool {
+    let Some(end) = a.end else {
+        return false;
+    };
+    let Some(start) = Local.from_local_datetime(&b.start).earliest() else {
+        return false;
+    };
+    b.index == a.index + 1 && (start - end).num_seconds().abs() <= DJI_CHAPTER_GAP_SECS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+    use std::fs;
+    use tempfile::TempDir;
+
+    fn capture(h: u32, m: u32, s: u32, ms: u32, bias: Option<f64>) -> CaptureInfo {
+        CaptureInfo {
+            time: NaiveDate::from_ymd_opt(2025, 12, 31)
+                .unwrap()
+                .and_hms_milli_opt(h, m, s, ms)
+                .unwrap(),
+            exposure_bias: bias,
+        }
+    }
+
+    #[test]
+    fn test_find_sequences() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let names = [
+            "IMG_0001.JPG",
+            "IMG_0002.JPG",
+            "IMG_0003.JPG",
+            "IMG_0004.JPG",
+            "DSC_0010.JPG",
+            "DSC_0011.JPG",
+            "DSC_0012.JPG",
+            "GH010042.MP4",
+            "GH020042.MP4",
+            "GH010043.MP4",
+            "GOPR0007.MP4",
+            "GP010007.MP4",
+            "DJI_20251231235800_0001_D.MP4",
+            "DJI_20260101000300_0002_D.MP4",
+            "DJI_20260101001000_0003_D.MP4",
+        ];
+        for name in names {
+            fs::write(dir.join(name), name).unwrap();
+        }
+        // 第一段 DJI 视频在 00:03:01 写完，第二段在 00:20:00 写完
+        for (name, end) in [
+            ("DJI_20251231235800_0001_D.MP4", (2026, 1, 1, 0, 3, 1)),
+            ("DJI_20260101000300_0002_D.MP4", (2026, 1, 1, 0, 5, 0)),
+        ] {
+            let (y, mo, d, h, mi, s) = end;
+            let end = Local.with_ymd_and_hms(y, mo, d, h, mi, s).unwrap();
+            fs::File::options()
+                .write(true)
+                .open(dir.join(name))
+                .unwrap()
+                .set_modified(end.into())
+                .unwrap();
+        }
+
+        let captures: HashMap<PathBuf, CaptureInfo> = [
+            // 连拍，IMG_0004 与其他照片间隔太久
+            ("IMG_0001.JPG", capture(23, 59, 59, 800, None)),
+            ("IMG_0002.JPG", capture(23, 59, 59, 950, None)),
+            ("IMG_0003.JPG", capture(23, 59, 59, 999, None)),
+            ("IMG_0004.JPG", capture(23, 59, 57, 0, None)),
+            // 包围曝光，间隔超过连拍间隔但曝光补偿不同
+            ("DSC_0010.JPG", capture(12, 0, 0, 0, Some(0.0))),
+            ("DSC_0011.JPG", capture(12, 0, 2, 0, Some(-2.0))),
+            ("DSC_0012.JPG", capture(12, 0, 4, 0, Some(2.0))),
+        ]
+        .into_iter()
+        .map(|(name, capture)| (dir.join(name), capture))
+        .collect();
+        let files: Vec<PathBuf> = names.iter().map(|name| dir.join(name)).collect();
+
+        let sequences = find_sequences(&files, &SequenceConfig::default(), |path| {
+            captures.get(path).copied()
+        });
+        let summary: Vec<(SequenceKind, &str, usize)> = sequences
+            .iter()
+            .map(|s| (s.kind, s.name.as_str(), s.files.len()))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                (SequenceKind::Bracket, "DSC_0010", 3),
+                (SequenceKind::Burst, "IMG_0001", 3),
+                (SequenceKind::Chapters, "GH010042", 2),
+                (SequenceKind::Chapters, "GOPR0007", 2),
+                (SequenceKind::Chapters, "DJI_20251231235800_0001_D", 2),
+            ]
+        );
+        assert_eq!(sequences[2].files[1], dir.join("GH020042.MP4"));
+    }
+}
@@ -0,0 +1,179 @@
+//! 附属文件（sidecar）
+//!
+//! Lightroom 的 `.xmp`、iPhone 的 `.AAE`、GoPro 的 `.THM`/`.LRV` 以及相机的
+//! `.WAV` 语音备注与照片或视频同名存放。这些文件随主文件一起传输，
+//! 目标文件名跟随主文件的最终文件名（包括文件名模板和重名后缀）。
+
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::media_types::{MediaType, get_media_info};
+
+/// 随主文件传输的附属文件
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Sidecar {
+    /// 附属文件路径
+    pub source: PathBuf,
+    /// 跟随主文件目标路径计算出的目标路径
+    pub target: PathBuf,
+}
+
+/// 扩展名是否在附属文件扩展名列表中
+fn is_sidecar_extension(path: &Path, extensions: &[String]) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| extensions.iter().any(|e| e.eq_ignore_ascii_case(ext)))
+}
+
+/// 能够拥有附属文件的主文件：不属于附属文件扩展名的图片或视频
+fn is_primary(path: &Path, extensions: &[String]) -> bool {
+    !is_sidecar_extension(path, extensions)
+        && get_media_info(path).is_some_and(|info| info.media_type != MediaType::Audio)
+}
+
+/// 查找主文件旁边的附属文件
+///
+/// 支持 `IMG_0001.xmp` 和 `IMG_0001.JPG.xmp` 两种命名，扩展名按原样、
+/// 小写和大写三种形式查找
+pub(crate) fn find_sidecars(primary: &Path, extensions: &[String]) -> Vec<PathBuf> {
+    let (Some(parent), Some(stem), Some(name)) = (
+        primary.parent(),
+        primary.file_stem().and_then(|s| s.to_str()),
+        primary.file_name().and_then(|s| s.to_str()),
+    ) else {
+        return Vec::new();
+    };
+    if !is_primary(primary, extensions) {
+        return Vec::new();
+    }
+
+    let mut sidecars: Vec<PathBuf> = Vec::new();
+    let mut seen: Vec<PathBuf> = Vec::new();
+    for ext in extensions {
+        for base in [stem, name] {
+            for ext in [ext.clone(), ext.to_lowercase(), ext.to_uppercase()] {
+                let candidate = parent.join(format!("{}.{}", base, ext));
+                if !candidate.is_file() {
+                    continue;
+                }
+                // 不区分大小写的文件系统上多种写法指向同一个文件
+                let canonical = candidate.canonicalize().unwrap_or(candidate.clone());
+                if !seen.contains(&canonical) {
+                    seen.push(canonical);
+                    sidecars.push(candidate);
+                }
+            }
+        }
+    }
+    sidecars
+}
+
+/// 查找附属文件所属的主文件，不是附属文件或找不到主文件时返回 None
+///
+/// 多个主文件同名时（如 `IMG_0001.JPG` 和 `IMG_0001.MOV`）取文件名排序最前的一个
+pub(crate) fn find_primary(path: &Path, extensions: &[String]) -> Option<PathBuf> {
+    if !is_sidecar_extension(path, extensions) {
+        return None;
+    }
+    let parent = path.parent()?;
+    let stem = path.file_stem()?.to_str()?;
+
+    let mut primaries: Vec<PathBuf> = fs::read_dir(parent)
+        .ok()?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|candidate| {
+            let matches_stem = candidate
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .is_some_and(|s| s.eq_ignore_ascii_case(stem));
+            let matches_name = candidate
+                .file_name()
+                .and_then(|s| s.to_str())
+                .is_some_and(|s| s.eq_ignore_ascii_case(stem));
+            (matches_stem || matches_name)
+                && candidate.is_file()
+                && is_primary(candidate, extensions)
+        })
+        .collect();
+    primaries.sort();
+    primaries.into_iter().next()
+}
+
+/// 根据主文件的目标路径计算附属文件的目标路径
+///
+/// `IMG_0001.JPG.xmp` 形式的附属文件跟随主文件的完整文件名，
+/// 其余跟随主文件名去掉扩展名的部分，附属文件的扩展名保持不变
+pub(crate) fn sidecar_target(primary: &Path, sidecar: &Path, primary_target: &Path) -> PathBuf {
+    let sidecar_stem = sidecar.file_stem().unwrap_or_default();
+    let base = if primary.file_name() == Some(sidecar_stem) {
+        primary_target.file_name()
+    } else {
+        primary_target.file_stem()
+    }
+    .unwrap_or_default();
+
+    let mut name = base.to_os_string();
+    if let Some(ext) = sidecar.extension() {
+        name.push(".");
+        name.push(ext);
+    }
+    primary_target.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::SidecarConfig;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_find_sidecars_and_targets() {
+        let temp_dir = TempDir::new().unwrap();
+        let dir = temp_dir.path();
+        let extensions = SidecarConfig::default().extensions;
+        for name in [
+            "DSC_0001.JPG",
+            "DSC_0001.xmp",
+            "DSC_0001.JPG.xmp",
+            "DSC_0001.WAV",
+            "DSC_0002.JPG",
+            "song.mp3",
+            "song.wav",
+        ] {
+            fs::write(dir.join(name), name).unwrap();
+        }
+
+        let primary = dir.join("DSC_0001.JPG");
+        let mut sidecars = find_sidecars(&primary, &extensions);
+        sidecars.sort();
+        assert_eq!(
+            sidecars,
+            [
+                dir.join("DSC_0001.JPG.xmp"),
+                dir.join("DSC_0001.WAV"),
+                dir.join("DSC_0001.xmp"),
+            ]
+        );
+        assert!(find_sidecars(&dir.join("DSC_0002.JPG"), &extensions).is_empty());
+
+        // 语音备注属于照片，音乐文件旁的 WAV 不是附属文件
+        assert_eq!(
+            find_primary(&dir.join("DSC_0001.WAV"), &extensions),
+            Some(primary.clone())
+        );
+        assert_eq!(find_primary(&dir.join("song.wav"), &extensions), None);
+        assert!(find_sidecars(&dir.join("song.mp3"), &extensions).is_empty());
+
+        let target = Path::new("/library/Photos/2025/20251118_143005.JPG");
+        assert_eq!(
+            sidecar_target(&primary, &dir.join("DSC_0001.xmp"), target),
+            Path::new("/library/Photos/2025/20251118_143005.xmp")
+        );
+        assert_eq!(
+            sidecar_target(&primary, &dir.join("DSC_0001.JPG.xmp"), target),
+            Path::new("/library/Photos/2025/20251118_143005.JPG.xmp")
+        );
+    }
+}
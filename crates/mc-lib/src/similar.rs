@@ -0,0 +1,319 @@
+//! 相似图片检测
+//!
+//! 重新压缩或缩放过的图片（聊天软件、网盘导出）字节内容与原图不同，
+//! 内容哈希无法匹配。这里对解码后的图片计算 64 位差值哈希（dHash），
+//! 两张图片哈希的汉明距离越小越相似。
+
+use anyhow::{Context, Result};
+use image::{DynamicImage, ImageDecoder, ImageReader};
+use log::{debug, info};
+use std::fs;
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::config::SimilarConfig;
+use crate::journal::DATA_DIR;
+use crate::pipeline::ordered_parallel;
+use crate::utils::relocate;
+
+/// 支持计算感知哈希的图片扩展名
+const SUPPORTED_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp"];
+
+/// 图片的感知指纹
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ImageFingerprint {
+    /// 64 位差值哈希
+    pub hash: u64,
+    /// 按 EXIF 方向旋转后的宽度
+    pub width: u32,
+    /// 按 EXIF 方向旋转后的高度
+    pub height: u32,
+}
+
+impl ImageFingerprint {
+    /// 两个指纹之间不同的位数
+    pub fn distance(&self, other: &ImageFingerprint) -> u32 {
+        (self.hash ^ other.hash).count_ones()
+    }
+
+    /// 相似度百分比（0-100）
+    pub fn similarity(&self, other: &ImageFingerprint) -> u8 {
+        (100 - self.distance(other) * 100 / 64) as u8
+    }
+}
+
+/// 判断文件是否支持计算感知哈希
+pub(crate) fn supports_fingerprint(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| SUPPORTED_EXTENSIONS.contains(&ext.to_lowercase().as_str()))
+}
+
+/// 解码图片并计算感知指纹
+///
+/// 先按 EXIF 方向旋转，使带方向标记的原图与已旋转的导出图得到相同的哈希
+pub(crate) fn fingerprint_image(path: &Path) -> Result<ImageFingerprint> {
+    let mut decoder = ImageReader::open(path)
+        .context(format!("Failed to open image: {:?}", path))?
+        .with_guessed_format()
+        .context("Failed to detect image format")?
+        .into_decoder()
+        .context(format!("Unsupported image: {:?}", path))?;
+    let orientation = decoder.orientation().ok();
+    let mut image =
+        DynamicImage::from_decoder(decoder).context(format!("Failed to decode {:?}", path))?;
+    if let Some(orientation) = orientation {
+        image.apply_orientation(orientation);
+    }
+
+    Ok(ImageFingerprint {
+        hash: dhash(&image),
+        width: image.width(),
+        height: image.height(),
+    })
+}
+
+/// 差值哈希：缩小为 9x8 灰度图，每一位表示某像素是否比右侧相邻像素亮
+fn dhash(image: &DynamicImage) -> u64 {
+    let small = image.thumbnail_exact(9, 8).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | u64::from(left > right);
+        }
+    }
+    hash
+}
+
+/// 相似度阈值（百分比）换算为允许的最大汉明距离
+fn max_distance(threshold: u8) -> u32 {
+    (100 - u32::from(threshold.min(100))) * 64 / 100
+}
+
+/// 目标库中图片的感知指纹索引
+#[derive(Debug)]
+pub struct SimilarIndex {
+    images: Vec<(PathBuf, ImageFingerprint)>,
+    max_distance: u32,
+    review_dir: PathBuf,
+}
+
+impl SimilarIndex {
+    /// 扫描目标目录中的图片并计算指纹（复查目录除外）
+    pub fn build(root: &Path, config: &SimilarConfig, jobs: usize) -> Result<Self> {
+        let review_dir = root.join(&config.review_dir);
+        let files: Vec<PathBuf> = library_images(root)
+            .filter(|path| !path.starts_with(&review_dir))
+            .collect();
+
+        let mut images = Vec::with_capacity(files.len());
+        ordered_parallel(
+            &files,
+            jobs,
+            None,
+            |path| fingerprint_image(path),
+            |path, result| match result {
+                Ok(fingerprint) => images.push((path.clone(), fingerprint)),
+                Err(e) => debug!("Skipping {:?} for similarity index: {:#}", path, e),
+            },
+        );
+        info!("Indexed {} library images for similarity", images.len());
+
+        Ok(Self {
+            images,
+            max_distance: max_distance(config.threshold),
+            review_dir,
+        })
+    }
+
+    /// 查找与指纹最相似且超过阈值的库中图片，返回 (路径, 相似度)
+    pub(crate) fn find_similar(&self, fingerprint: &ImageFingerprint) -> Option<(&Path, u8)> {
+        self.images
+            .iter()
+            .map(|(path, other)| (path, fingerprint.distance(other), other))
+            .filter(|(_, distance, _)| *distance <= self.max_distance)
+            .min_by_key(|(_, distance, _)| *distance)
+            .map(|(path, _, other)| (path.as_path(), fingerprint.similarity(other)))
+    }
+
+    /// 记录本次运行计划写入的图片，后续文件也会与之比较
+    pub(crate) fn add_planned(&mut self, target: &Path, fingerprint: ImageFingerprint) {
+        self.images.push((target.to_path_buf(), fingerprint));
+    }
+
+    /// 将目标路径改写到复查目录中，保留其在库中的相对路径
+    pub fn review_target(&self, root: &Path, target: &Path) -> PathBuf {
+        relocate(root, &self.review_dir, target)
+    }
+}
+
+/// 相似图片簇中的一张图片
+#[derive(Debug, Clone)]
+pub struct SimilarImage {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub size: u64,
+    /// 与簇中第一张图片的相似度（百分比）
+    pub similarity: u8,
+}
+
+/// 扫描目录，找出相似图片簇
+///
+/// 每个簇按分辨率和文件大小从大到小排列，第一张通常是原图
+pub fn find_similar_clusters(
+    root: &Path,
+    threshold: u8,
+    jobs: usize,
+) -> Result<Vec<Vec<SimilarImage>>> {
+    let files: Vec<PathBuf> = library_images(root).collect();
+    let mut images: Vec<(PathBuf, ImageFingerprint)> = Vec::with_capacity(files.len());
+    ordered_parallel(
+        &files,
+        jobs,
+        None,
+        |path| fingerprint_image(path),
+        |path, result| match result {
+            Ok(fingerprint) => images.push((path.clone(), fingerprint)),
+            Err(e) => debug!("Skipping {:?}: {:#}", path, e),
+        },
+    );
+
+    // 并查集合并距离在阈值内的图片
+    let limit = max_distance(threshold);
+    let mut parent: Vec<usize> = (0..images.len()).collect();
+    fn find(parent: &mut [usize], mut i: usize) -> usize {
+        while parent[i] != i {
+            parent[i] = parent[parent[i]];
+            i = parent[i];
+        }
+        i
+    }
+    for i in 0..images.len() {
+        for j in (i + 1)..images.len() {
+            if images[i].1.distance(&images[j].1) <= limit {
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                parent[a.max(b)] = a.min(b);
+            }
+        }
+    }
+
+    let mut clusters: Vec<Vec<usize>> = Vec::new();
+    let mut cluster_of = vec![usize::MAX; images.len()];
+    for i in 0..images.len() {
+        let root = find(&mut parent, i);
+        if cluster_of[root] == usize::MAX {
+            cluster_of[root] = clusters.len();
+            clusters.push(Vec::new());
+        }
+        clusters[cluster_of[root]].push(i);
+    }
+
+    let mut result = Vec::new();
+    for members in clusters.into_iter().filter(|m| m.len() > 1) {
+        let mut cluster: Vec<(SimilarImage, ImageFingerprint)> = members
+            .into_iter()
+            .map(|i| {
+                let (path, fingerprint) = &images[i];
+                let size = fs::metadata(path).map(|m| m.len()).unwrap_or_default();
+                let image = SimilarImage {
+                    path: path.clone(),
+                    width: fingerprint.width,
+                    height: fingerprint.height,
+                    size,
+                    similarity: 100,
+                };
+                (image, *fingerprint)
+            })
+            .collect();
+        cluster.sort_by(|(a, _), (b, _)| {
+            (u64::from(b.width) * u64::from(b.height), b.size, &a.path).cmp(&(
+                u64::from(a.width) * u64::from(a.height),
+                a.size,
+                &b.path,
+            ))
+        });
+        let first = cluster[0].1;
+        result.push(
+            cluster
+                .into_iter()
+                .map(|(mut image, fingerprint)| {
+                    image.similarity = first.similarity(&fingerprint);
+                    image
+                })
+                .collect(),
+        );
+    }
+
+    Ok(result)
+}
+
+/// 库中支持计算指纹的图片（跳过程序数据目录）
+fn library_images(root: &Path) -> impl Iterator<Item = PathBuf> {
+    let data_dir = root.join(DATA_DIR);
+    WalkDir::new(root)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_entry(move |e| !e.path().starts_with(&data_dir))
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file() && supports_fingerprint(e.path()))
+        .map(|e| e.into_path())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{ImageFormat, Rgb, RgbImage};
+    use tempfile::TempDir;
+
+    /// 生成带渐变和色块的测试图片
+    fn test_image(width: u32, height: u32, inverted: bool) -> DynamicImage {
+        let image = RgbImage::from_fn(width, height, |x, y| {
+            let mut v = ((x * 255 / width) ^ (y * 255 / height)) as u8;
+            if x < width / 3 && y < height / 2 {
+                v = 255;
+            }
+            if inverted {
+                v = 255 - v;
+            }
+            Rgb([v, v / 2, 255 - v])
+        });
+        DynamicImage::ImageRgb8(image)
+    }
+
+    #[test]
+    fn test_resized_copy_is_similar() {
+        let temp_dir = TempDir::new().unwrap();
+        let original = temp_dir.path().join("IMG_0001.png");
+        let resized = temp_dir.path().join("IMG-WA0001.jpg");
+        let other = temp_dir.path().join("other.png");
+
+        let image = test_image(640, 480, false);
+        image.save_with_format(&original, ImageFormat::Png).unwrap();
+        image
+            .resize_exact(200, 150, image::imageops::FilterType::Triangle)
+            .save_with_format(&resized, ImageFormat::Jpeg)
+            .unwrap();
+        test_image(640, 480, true)
+            .save_with_format(&other, ImageFormat::Png)
+            .unwrap();
+
+        let a = fingerprint_image(&original).unwrap();
+        let b = fingerprint_image(&resized).unwrap();
+        let c = fingerprint_image(&other).unwrap();
+        assert_eq!((a.width, a.height), (640, 480));
+        assert!(
+            a.distance(&b) <= max_distance(90),
+            "distance {}",
+            a.distance(&b)
+        );
+        assert!(a.distance(&c) > max_distance(90));
+
+        let clusters = find_similar_clusters(temp_dir.path(), 90, 2).unwrap();
+        assert_eq!(clusters.len(), 1);
+        assert_eq!(clusters[0][0].path, original);
+        assert_eq!(clusters[0][1].path, resized);
+    }
+}
@@ -0,0 +1,301 @@
+use anyhow::{Context, Result};
+use log::debug;
+use serde::{Deserialize, Serialize};
+use std::fs::{self, File, FileTimes};
+use std::io::{ErrorKind, Read, Write};
+use std::path::Path;
+
+use crate::cancel::{CancelToken, Cancelled};
+use crate::hash::{BUFFER_SIZE, hash_file};
+
+/// 文件传输方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferMode {
+    /// 移动（重命名）文件
+    #[default]
+    Move,
+    /// 复制并校验，保留源文件
+    Copy,
+    /// 复制并校验成功后删除源文件
+    CopyThenDelete,
+}
+
+impl TransferMode {
+    /// 用于显示和配置的名称
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TransferMode::Move => "move",
+            TransferMode::Copy => "copy",
+            TransferMode::CopyThenDelete => "copy_then_delete",
+        }
+    }
+}
+
+impl std::str::FromStr for TransferMode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_lowercase().replace('-', "_").as_str() {
+            "move" => Ok(TransferMode::Move),
+            "copy" => Ok(TransferMode::Copy),
+            "copy_then_delete" => Ok(TransferMode::CopyThenDelete),
+            other => anyhow::bail!("Unknown transfer mode: {}", other),
+        }
+    }
+}
+
+/// 按指定方式将文件传输到目标位置
+pub fn transfer_file(source: &Path, target: &Path, mode: TransferMode) -> Result<()> {
+    transfer_file_with_cancel(source, target, mode, &CancelToken::new())
+}
+
+/// 按指定方式将文件传输到目标位置，复制过程中请求取消时中止
+///
+/// 中止时删除不完整的目标文件，源文件保持不变
+pub fn transfer_file_with_cancel(
+    source: &Path,
+    target: &Path,
+    mode: TransferMode,
+    cancel: &CancelToken,
+) -> Result<()> {
+    // 确保目标目录存在
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).context("Failed to create target directory")?;
+    }
+
+    match mode {
+        TransferMode::Move => {
+            move_file(source, target, cancel)?;
+        },
+        TransferMode::Copy => {
+            copy_verified(source, target, cancel)?;
+        },
+        TransferMode::CopyThenDelete => {
+            copy_verified(source, target, cancel)?;
+            fs::remove_file(source).context("Failed to remove source after verified copy")?;
+        },
+    }
+
+    Ok(())
+}
+
+/// 移动文件，跨文件系统时回退为复制后删除
+fn move_file(source: &Path, target: &Path, cancel: &CancelToken) -> Result<()> {
+    match fs::rename(source, target) {
+        Ok(()) => Ok(()),
+        Err(e) if e.kind() == ErrorKind::CrossesDevices => {
+            debug!(
+                "Cross-device rename {:?} → {:?}, falling back to copy",
+                source, target
+            );
+            move_across_devices(source, target, cancel)
+        },
+        Err(e) => Err(e).context("Failed to move file"),
+    }
+}
+
+/// 跨文件系统移动：流式复制、同步到磁盘、校验大小后删除源文件
+fn move_across_devices(source: &Path, target: &Path, cancel: &CancelToken) -> Result<()> {
+    copy_with_hash(source, target, cancel)?;
+
+    let source_size = fs::metadata(source)
+        .context("Failed to get source file metadata")?
+        .len();
+    let target_size = fs::metadata(target)
+        .context("Failed to get target file metadata")?
+        .len();
+    if source_size != target_size {
+        let _ = fs::remove_file(target);
+        anyhow::bail!(
+            "Copy verification failed for {:?}: size mismatch ({} != {} bytes)",
+            target,
+            source_size,
+            target_size
+        );
+    }
+
+    fs::remove_file(source).context("Failed to remove source after cross-device copy")?;
+    Ok(())
+}
+
+/// 复制文件并重新读取目标文件校验哈希
+///
+/// 校验失败时删除目标文件并返回错误，源文件保持不变
+fn copy_verified(source: &Path, target: &Path, cancel: &CancelToken) -> Result<()> {
+    let source_hash = copy_with_hash(source, target, cancel)?;
+
+    let target_hash = match hash_file(target) {
+        Ok(hash) => hash,
+        Err(e) => {
+            let _ = fs::remove_file(target);
+            return Err(e.context("Failed to verify copied file"));
+        },
+    };
+
+    if source_hash != target_hash {
+        let _ = fs::remove_file(target);
+        anyhow::bail!(
+            "Copy verification failed for {:?}: hash mismatch ({} != {})",
+            target,
+            source_hash,
+            target_hash
+        );
+    }
+
+    debug!(
+        "Verified copy {:?} → {:?} ({})",
+        source, target, target_hash
+    );
+    Ok(())
+}
+
+/// 流式复制文件，同时计算源文件哈希；每个数据块之前检查是否已请求取消
+fn copy_with_hash(source: &Path, target: &Path, cancel: &CancelToken) -> Result<String> {
+    let mut reader = File::open(source).context("Failed to open source file")?;
+    let mut writer = File::create_new(target).context("Failed to create target file")?;
+    let mut hasher = blake3::Hasher::new();
+    let mut buffer = vec![0u8; BUFFER_SIZE];
+
+    let result: Result<()> = (|| {
+        loop {
+            if cancel.is_cancelled() {
+                anyhow::bail!(Cancelled);
+            }
+            let read = reader
+                .read(&mut buffer)
+                .context("Failed to read source file")?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            writer
+                .write_all(&buffer[..read])
+                .context("Failed to write target file")?;
+        }
+
+        // 保留权限和时间戳，之后再同步，保证断电后修改时间和数据一样可靠
+        let metadata = fs::metadata(source).context("Failed to get source file metadata")?;
+        let mut times = FileTimes::new();
+        if let Ok(accessed) = metadata.accessed() {
+            times = times.set_accessed(accessed);
+        }
+        if let Ok(modified) = metadata.modified() {
+            times = times.set_modified(modified);
+        }
+        if let Err(e) = writer.set_times(times) {
+            debug!("Failed to preserve timestamps for {:?}: {}", target, e);
+        }
+        writer.set_permissions(metadata.permissions()).ok();
+        writer.sync_all().context("Failed to flush target file")?;
+        Ok(())
+    })();
+
+    if let Err(e) = result {
+        drop(writer);
+        let _ = fs::remove_file(target);
+        return Err(e);
+    }
+    drop(writer);
+
+    sync_parent_dir(target);
+
+    Ok(hasher.finalize().to_hex().to_string())
+}
+
+/// 将目标所在目录的元数据同步到磁盘，确保新建的目录项不会因断电丢失
+fn sync_parent_dir(path: &Path) {
+    #[cfg(unix)]
+    if let Some(parent) = path.parent()
+        && let Ok(dir) = File::open(parent)
+        && let Err(e) = dir.sync_all()
+    {
+        debug!("Failed to sync directory {:?}: {}", parent, e);
+    }
+
+    #[cfg(not(unix))]
+    let _ = path;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_transfer_modes() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("photo.jpg");
+        fs::write(&source, b"image data").unwrap();
+
+        let copied = temp_dir.path().join("copy/photo.jpg");
+        transfer_file(&source, &copied, TransferMode::Copy).unwrap();
+        assert!(source.exists());
+        assert_eq!(fs::read(&copied).unwrap(), b"image data");
+
+        let moved = temp_dir.path().join("moved/photo.jpg");
+        transfer_file(&source, &moved, TransferMode::CopyThenDelete).unwrap();
+        assert!(!source.exists());
+        assert_eq!(fs::read(&moved).unwrap(), b"image data");
+    }
+
+    #[test]
+    fn test_move_across_devices_preserves_mtime() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("clip.mp4");
+        fs::write(&source, b"video data").unwrap();
+        let mtime =
+            std::time::SystemTime::UNIX_EPOCH + std::time::Duration::from_secs(1_700_000_000);
+        File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(mtime)
+            .unwrap();
+
+        let target = temp_dir.path().join("clip_moved.mp4");
+        move_across_devices(&source, &target, &CancelToken::new()).unwrap();
+
+        assert!(!source.exists());
+        assert_eq!(fs::read(&target).unwrap(), b"video data");
+        assert_eq!(fs::metadata(&target).unwrap().modified().unwrap(), mtime);
+    }
+
+    #[test]
+    fn test_copy_refuses_existing_target() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("a.jpg");
+        let target = temp_dir.path().join("b.jpg");
+        fs::write(&source, b"new").unwrap();
+        fs::write(&target, b"old").unwrap();
+
+        assert!(transfer_file(&source, &target, TransferMode::Copy).is_err());
+        assert_eq!(fs::read(&target).unwrap(), b"old");
+    }
+
+    #[test]
+    fn test_cancelled_copy_keeps_source() {
+        let temp_dir = TempDir::new().unwrap();
+        let source = temp_dir.path().join("clip.mp4");
+        let target = temp_dir.path().join("out/clip.mp4");
+        fs::write(&source, b"video data").unwrap();
+
+        let cancel = CancelToken::new();
+        cancel.cancel();
+        let err =
+            transfer_file_with_cancel(&source, &target, TransferMode::CopyThenDelete, &cancel)
+                .unwrap_err();
+        assert!(Cancelled::is_cause_of(&err));
+        assert!(source.exists());
+        assert!(!target.exists());
+    }
+
+    #[test]
+    fn test_transfer_mode_from_str() {
+        assert_eq!(
+            "copy-then-delete".parse::<TransferMode>().unwrap(),
+            TransferMode::CopyThenDelete
+        );
+        assert!("teleport".parse::<TransferMode>().is_err());
+    }
+}
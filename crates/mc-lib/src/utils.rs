@@ -1,10 +1,19 @@
 use log::info;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use walkdir::WalkDir;
 
-pub fn remove_empty_dirs<P: AsRef<Path>>(root: P) -> std::io::Result<()> {
+use crate::observer::Observer;
+
+/// 删除 root 下的空目录，返回被删除的目录列表
+///
+/// 每删除一个目录都通知 observer
+pub fn remove_empty_dirs<P: AsRef<Path>>(
+    root: P,
+    observer: &mut dyn Observer,
+) -> std::io::Result<Vec<PathBuf>> {
     let root = root.as_ref();
+    let mut removed = Vec::new();
 
     for entry in WalkDir::new(root)
         .contents_first(true)
@@ -18,11 +27,12 @@ pub fn remove_empty_dirs<P: AsRef<Path>>(root: P) -> std::io::Result<()> {
         if path.is_dir() && path != root && is_empty_dir(path) {
             fs::remove_dir(path)?;
             info!("Removed empty directory: {:?}", path);
-            println!("🗑️  Removed empty directory: {}", path.display());
+            observer.dir_cleaned(path);
+            removed.push(path.to_path_buf());
         }
     }
 
-    Ok(())
+    Ok(removed)
 }
 
 fn is_empty_dir<P: AsRef<Path>>(path: P) -> bool {
@@ -31,3 +41,53 @@ fn is_empty_dir<P: AsRef<Path>>(path: P) -> bool {
         Err(_) => false,
     }
 }
+
+/// 将 root 下的 target 改写到 dir 下，保留其相对于 root 的路径；
+/// target 不在 root 下时只保留文件名
+pub(crate) fn relocate(root: &Path, dir: &Path, target: &Path) -> PathBuf {
+    let relative = target
+        .strip_prefix(root)
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|_| target.file_name().map(PathBuf::from).unwrap_or_default());
+    dir.join(relative)
+}
+
+/// 将模板中的编号变量 `{name}` 或补零形式 `{name:N}` 替换为 value
+///
+/// 例如 `{n:3}` 展开为 `007`；其他花括号内容原样保留
+pub(crate) fn expand_number(template: &str, name: &str, value: u32) -> String {
+    let open = format!("{{{}", name);
+    let mut result = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find(&open) {
+        result.push_str(&rest[..start]);
+        let after = &rest[start + open.len()..];
+        let width = if after.starts_with('}') {
+            Some((0, 1))
+        } else if let Some(spec) = after.strip_prefix(':')
+            && let Some(end) = spec.find('}')
+            && let Ok(width) = spec[..end].parse::<usize>()
+        {
+            Some((width, end + 2))
+        } else {
+            None
+        };
+        match width {
+            Some((width, len)) => {
+                result.push_str(&format!("{:0width$}", value));
+                rest = &after[len..];
+            },
+            None => {
+                result.push_str(&open);
+                rest = after;
+            },
+        }
+    }
+    result.push_str(rest);
+    result
+}
+
+/// 模板中是否包含编号变量 `{name}` 或 `{name:N}`
+pub(crate) fn has_number_var(template: &str, name: &str) -> bool {
+    expand_number(template, name, 0) != template
+}